@@ -0,0 +1,72 @@
+//! The `config.json` that luals addons ship at the root of their repository.
+//!
+//! Only the fields llam cares about are typed, everything else is preserved in `other`.
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Error;
+
+pub static ADDON_CONFIG: &str = "config.json";
+
+/// Directory luals addons keep their definition files in
+pub static LIBRARY_DIR: &str = "library";
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct AddonConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Other addons this addon depends on, in the same format accepted by `llam add`
+    ///
+    /// This is added and custom to `lua-language-addon-manager (llam)`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+
+    /// Oldest luals version the addon works with, e.g. `3.7.0`
+    ///
+    /// This is added and custom to `lua-language-addon-manager (llam)`.
+    #[serde(default, rename = "minVersion", alias = "luals", skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+impl AddonConfig {
+    /// Read the config of an installed addon, returning `None` if it doesn't have one
+    pub fn read(addon_dir: impl AsRef<Path>) -> Result<Option<Self>, Error> {
+        let path = addon_dir.as_ref().join(ADDON_CONFIG);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let bytes = std::fs::read(&path)?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|err| Error::context(format!("failed to parse {}", path.display()), err))
+    }
+
+    /// Directories the addon's luals `settings` add to `workspace.ignoreDir`
+    pub fn ignore_dirs(&self) -> Vec<String> {
+        self.other
+            .get("settings")
+            .and_then(|settings| settings.get("Lua.workspace.ignoreDir"))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| dir.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Whether a cloned repository looks like a luals addon, it has a `config.json` or a
+    /// `library` directory
+    ///
+    /// Errors when the repository has a `config.json` that can't be parsed.
+    pub fn is_addon(addon_dir: impl AsRef<Path>) -> Result<bool, Error> {
+        let addon_dir = addon_dir.as_ref();
+        Ok(Self::read(addon_dir)?.is_some() || addon_dir.join(LIBRARY_DIR).is_dir())
+    }
+}
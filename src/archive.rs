@@ -0,0 +1,119 @@
+//! Downloading and extracting addons that are distributed as tarballs instead of repositories.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use reqwest::Url;
+
+use crate::{addon_config::LIBRARY_DIR, Error};
+
+/// File in an extracted addon recording the content hash of the tarball it was extracted from
+pub static HASH_FILE: &str = ".llam-archive";
+
+/// Download the tarball at `url` to the file `to`
+///
+/// `file://` urls are copied, `http://` and `https://` urls are fetched with their own runtime
+/// so this can be called from inside and outside of an async context.
+pub fn download(url: &str, to: &Path) -> Result<(), Error> {
+    let parsed = Url::parse(url).map_err(|err| Error::custom(format!("invalid archive url `{url}`: {err}")))?;
+    if parsed.scheme() == "file" {
+        let from = parsed
+            .to_file_path()
+            .map_err(|_| Error::custom(format!("invalid archive path `{url}`")))?;
+        std::fs::copy(&from, to).map_err(|err| Error::context(format!("failed to read {}", from.display()), err))?;
+        return Ok(());
+    }
+
+    let url = url.to_string();
+    let bytes = std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(async { Ok::<_, Error>(reqwest::get(url).await?.error_for_status()?.bytes().await?) })
+    })
+    .join()
+    .map_err(|_| Error::custom("archive download panicked"))??;
+    std::fs::write(to, bytes)?;
+    Ok(())
+}
+
+/// Extract a tarball into `dir`, returning the directory holding the addon
+///
+/// Release tarballs usually wrap their files in a single top level directory, which is the
+/// addon when it exists. Otherwise, or when that directory is the addon's `library`, the files
+/// are the addon and `dir` is returned.
+pub fn extract(archive: &Path, dir: &Path) -> Result<PathBuf, Error> {
+    std::fs::create_dir_all(dir)?;
+    let result = Command::new("tar").arg("-xf").arg(archive).arg("-C").arg(dir).output()?;
+    if !result.status.success() {
+        return Err(Error::custom(format!(
+            "failed to extract {}:\n{}",
+            archive.display(),
+            String::from_utf8_lossy(&result.stderr)
+        )));
+    }
+
+    let entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() && entry.file_name() != LIBRARY_DIR => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Content hash recorded in an extracted addon, `None` for addons that weren't extracted
+pub fn installed_hash(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path.join(HASH_FILE))
+        .ok()
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn extract_single_and_flat_tarballs() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join("src/penlight/library")).unwrap();
+        std::fs::write(dir.join("src/penlight/library/pl.lua"), "return {}").unwrap();
+        std::fs::write(dir.join("src/config.json"), "{}").unwrap();
+
+        let tar = |name: &str, files: &str| {
+            let status = Command::new("tar")
+                .args(["-czf", &dir.join(name).to_string_lossy(), "-C", &dir.join(files).to_string_lossy(), "."])
+                .status()
+                .unwrap();
+            assert!(status.success());
+            dir.join(name)
+        };
+
+        // Only a `library` directory, which is the addon's own
+        let library = tar("library.tar.gz", "src/penlight");
+        let addon = extract(&library, &dir.join("library")).unwrap();
+        assert_eq!(addon, dir.join("library"));
+        assert!(addon.join("library/pl.lua").exists());
+
+        let flat = tar("flat.tar.gz", "src");
+        assert_eq!(extract(&flat, &dir.join("flat")).unwrap(), dir.join("flat"));
+
+        std::fs::remove_file(dir.join("src/config.json")).unwrap();
+        let wrapped = tar("wrapped.tgz", "src");
+        let addon = extract(&wrapped, &dir.join("wrapped")).unwrap();
+        assert_eq!(addon, dir.join("wrapped/penlight"));
+        assert!(addon.join("library/pl.lua").exists());
+
+        let copy = dir.join("copy.tar.gz");
+        download(Url::from_file_path(&library).unwrap().as_str(), &copy).unwrap();
+        assert_eq!(std::fs::read(&copy).unwrap(), std::fs::read(&library).unwrap());
+        assert!(download("file:///missing/addon.tar.gz", &copy).is_err());
+
+        assert!(extract(&dir.join("src/penlight/library/pl.lua"), &dir.join("broken")).is_err());
+        assert_eq!(installed_hash(&addon), None);
+        std::fs::write(addon.join(HASH_FILE), "abc\n").unwrap();
+        assert_eq!(installed_hash(&addon).as_deref(), Some("abc"));
+    }
+}
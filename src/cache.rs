@@ -0,0 +1,161 @@
+//! Short lived cache of remote branch checksums so repeated runs don't hit the network.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Where the cache is stored relative to the project
+pub static CACHE_FILE: &str = ".llam/cache.json";
+
+/// How long a cached checksum is used before asking the remote again
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    checksum: String,
+    /// Seconds since the unix epoch
+    fetched_at: u64,
+}
+
+/// Remote tip checksums keyed by url and branch
+///
+/// Every lookup takes the current time so the clock can be controlled in tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteCache {
+    path: PathBuf,
+    ttl: Duration,
+    entries: BTreeMap<String, Entry>,
+    dirty: bool,
+}
+
+fn key(url: &str, branch: &str) -> String {
+    format!("{url}#{branch}")
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+impl RemoteCache {
+    /// Load the cache from `path`. A missing or unreadable cache file starts out empty.
+    pub fn load(path: impl AsRef<Path>, ttl: Duration) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            ttl,
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// Cached checksum of a branch if it was fetched less than the ttl before `now`
+    pub fn get(&self, url: &str, branch: &str, now: SystemTime) -> Option<&str> {
+        let entry = self.entries.get(&key(url, branch))?;
+        (seconds(now).saturating_sub(entry.fetched_at) < self.ttl.as_secs())
+            .then_some(entry.checksum.as_str())
+    }
+
+    pub fn insert(&mut self, url: &str, branch: &str, checksum: impl Into<String>, now: SystemTime) {
+        self.entries.insert(
+            key(url, branch),
+            Entry {
+                checksum: checksum.into(),
+                fetched_at: seconds(now),
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Drop entries fetched more than `age` before `now`, returning how many were dropped
+    pub fn remove_older_than(&mut self, age: Duration, now: SystemTime) -> usize {
+        let now = seconds(now);
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| now.saturating_sub(entry.fetched_at) <= age.as_secs());
+        let removed = before - self.entries.len();
+        self.dirty |= removed > 0;
+        removed
+    }
+
+    /// Write the cache if anything was inserted, dropping expired entries
+    pub fn save(&mut self, now: SystemTime) -> Result<(), Error> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let now = seconds(now);
+        let ttl = self.ttl.as_secs();
+        self.entries.retain(|_, entry| now.saturating_sub(entry.fetched_at) < ttl);
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const URL: &str = "https://github.com/LuaCATS/love2d.git";
+
+    #[test]
+    fn hit_miss_and_expiry() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut cache = RemoteCache::load("/nonexistent/cache.json", Duration::from_secs(60));
+
+        assert_eq!(cache.get(URL, "main", start), None);
+        cache.insert(URL, "main", "abc123", start);
+
+        assert_eq!(cache.get(URL, "main", start + Duration::from_secs(59)), Some("abc123"));
+        assert_eq!(cache.get(URL, "develop", start), None);
+        assert_eq!(cache.get(URL, "main", start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn remove_entries_older_than() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let hour = Duration::from_secs(60 * 60);
+        let mut cache = RemoteCache::load("/nonexistent/cache.json", hour * 24);
+        cache.insert(URL, "old", "abc123", start);
+        cache.insert(URL, "new", "def456", start + hour * 10);
+        cache.dirty = false;
+
+        assert_eq!(cache.remove_older_than(hour * 12, start + hour * 12), 0);
+        assert!(!cache.dirty);
+        assert_eq!(cache.remove_older_than(hour * 12, start + hour * 13), 1);
+        assert!(cache.dirty);
+        assert_eq!(cache.get(URL, "old", start + hour * 13), None);
+        assert_eq!(cache.get(URL, "new", start + hour * 13), Some("def456"));
+    }
+
+    #[test]
+    fn persists_between_runs() {
+        let dir = TempDir::new();
+        let path = dir.join(CACHE_FILE);
+        let now = SystemTime::now();
+
+        let mut cache = RemoteCache::load(&path, DEFAULT_TTL);
+        cache.insert(URL, "main", "abc123", now);
+        cache.insert(URL, "old", "def456", now - DEFAULT_TTL);
+        cache.save(now).unwrap();
+
+        let cache = RemoteCache::load(&path, DEFAULT_TTL);
+        assert_eq!(cache.get(URL, "main", now), Some("abc123"));
+        assert_eq!(cache.entries.len(), 1);
+    }
+}
@@ -1,6 +1,6 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
-use crate::{lua_rc::{diagnostics::Diagnostic, Severity}, manager::SomeOrAll, Addon};
+use crate::{logging::ColorMode, lua_rc::{diagnostics::Diagnostic, Hover, Misc, Semantic, Severity, Spell, Workspace}, manager::SomeOrAll, output::Format, Addon, AddonTarget};
 
 /// Lua Language Addon Manager
 ///
@@ -12,6 +12,84 @@ pub struct LLAM {
     /// Manually define the root path of the project
     #[arg(long)]
     pub path: Option<PathBuf>,
+    /// Path to the git executable to use
+    #[arg(long, env = "LLAM_GIT", default_value = "git")]
+    pub git_binary: PathBuf,
+    /// Branch to follow for addons that don't specify one, when the branch exists on the remote
+    #[arg(long, env = "LLAM_PREFER_BRANCH", global = true)]
+    pub prefer_branch: Option<String>,
+    /// Fail instead of changing the .luarc.json, printing what would have changed
+    #[arg(long, global = true)]
+    pub frozen: bool,
+    /// Commit the .luarc.json to the project's git repository after it changes
+    #[arg(long, global = true)]
+    pub commit: bool,
+    /// Print how long each git operation took per addon
+    #[arg(long, global = true)]
+    pub timings: bool,
+    /// Shallow clone and fetch addons with this many commits of history. Recorded for addons
+    /// that are added and overrides the depth recorded for existing addons.
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: Option<u32>,
+    /// Always ask remotes for their latest commits instead of using recently cached results
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+    /// Seconds cached remote commits are used for
+    #[arg(long, global = true, default_value_t = crate::cache::DEFAULT_TTL.as_secs(), value_name = "SECONDS")]
+    pub cache_ttl: u64,
+    /// Store addon pins in a separate .llam.lock instead of the .luarc.json. Used automatically
+    /// when the lockfile already exists.
+    #[arg(long, global = true)]
+    pub lockfile: bool,
+    /// Number of addon directories removed at the same time by `remove` and `clean`
+    #[arg(
+        long,
+        global = true,
+        default_value_t = crate::disk::REMOVE_THREADS,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        value_name = "THREADS"
+    )]
+    pub parallel_remove: usize,
+    /// Branches tried in order when an addon's default branch can't be determined
+    #[arg(
+        long = "fallback-branch",
+        global = true,
+        value_name = "BRANCH",
+        value_delimiter = ',',
+        default_values_t = crate::git::DEFAULT_BRANCH_FALLBACKS.map(String::from)
+    )]
+    pub fallback_branches: Vec<String>,
+    /// Pass `--quiet` to git commands that support it so failures only report git's errors
+    #[arg(long, global = true)]
+    pub quiet_git: bool,
+    /// Download the git-lfs files of addons that use git-lfs after cloning them
+    #[arg(long, global = true)]
+    pub lfs: bool,
+    /// Use the addons of a profile in `workspace.profiles` instead of `workspace.addons`. Clones
+    /// are shared between profiles
+    #[arg(long, global = true, env = "LLAM_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Directory addons are installed in, relative to the project unless absolute. Directories
+    /// inside the project are stored relative in the config
+    #[arg(long, global = true, value_name = "DIR", default_value = ".addons")]
+    pub addons_dir: PathBuf,
+    /// Clone addons once into a directory shared by every project and check them out into
+    /// projects as git worktrees. Worktrees are checked out detached, a branch can only be checked
+    /// out by one project at a time.
+    #[arg(long, global = true)]
+    pub shared: bool,
+    /// Directory of the shared clones [default: <data dir>/llam/addons]
+    #[arg(long, global = true, env = "LLAM_SHARED_DIR", value_name = "DIR")]
+    pub shared_dir: Option<PathBuf>,
+    /// When to use colors in the output
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub color: ColorMode,
+    /// Write a JSON summary of what `add`, `update`, `remove`, or `reinstall` did to each addon
+    #[arg(long, global = true, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// Print a failure as a JSON object, `{"error": "...", "kind": "..."}`, to stderr
+    #[arg(long, global = true)]
+    pub json_errors: bool,
     #[command(subcommand)]
     pub command: Subcommand,
 }
@@ -19,15 +97,125 @@ pub struct LLAM {
 #[derive(Debug, clap::Subcommand)]
 pub enum Subcommand {
     /// Add one or more lua language addons
-    Add { addons: Vec<Addon> },
+    Add {
+        /// LuaCATS names, git urls, or urls of `.tar.gz`, `.tgz`, or `.tar` archives to download,
+        /// optionally followed by `@<tag or commit>`, `@tag:<name>`, `@branch:<name>`, or
+        /// `@commit:<hash>`
+        #[arg(value_parser = parse_addon)]
+        addons: Vec<Addon>,
+        /// Only clone the addons without recording them in the .luarc.json
+        #[arg(long)]
+        no_update_luarc: bool,
+        /// Add the addons directory to the project's .gitignore
+        #[arg(long)]
+        gitignore: bool,
+        /// Fail addons that don't have a luals config.json or library directory instead of
+        /// warning about them
+        #[arg(long)]
+        strict: bool,
+        /// Don't set the luals settings schema as the `$schema` of a newly created .luarc.json
+        #[arg(long, alias = "no-luarc-schema")]
+        no_schema: bool,
+        /// Update addons that are already installed, the same as running `update` for them
+        #[arg(long, alias = "update-if-present")]
+        update: bool,
+        /// Install addons again when the same repository is already installed under another name
+        #[arg(long)]
+        force: bool,
+        /// Name to install a single addon as instead of the name derived from its url, e.g. when
+        /// another addon already uses that name
+        #[arg(long, value_parser = parse_name)]
+        name: Option<String>,
+        /// Search the LuaCATS addons and pick one to add, along with a branch, tag, or commit
+        #[arg(short, long)]
+        interactive: bool,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
     /// Remove one or more lua language addons
     Remove(ListOrAll),
     /// Update one, many, or all lua language addons
-    Update(ListOrAll),
+    Update {
+        #[command(flatten)]
+        addons: ListOrAll,
+        /// Only check for updates. Exits with 0 when everything is current, 1 when updates are
+        /// available, and 2 when an addon could not be checked
+        #[arg(long)]
+        check: bool,
+        /// Leave addons that follow the default branch on a branch that was checked out locally
+        #[arg(long)]
+        keep_local_branch: bool,
+        /// Update pinned addons that are named explicitly, `--all` always skips them
+        #[arg(long)]
+        force_pinned: bool,
+        /// Move a single addon to a branch, tag, or commit, e.g. `v1.0`, `tag:v1.0`, or `1a2b3c4`
+        #[arg(long, value_name = "REF", conflicts_with_all = ["check", "all"])]
+        to: Option<String>,
+    },
+    /// Delete and clone addons again, restoring the branch and checksum recorded in the config
+    Reinstall(ListOrAll),
+    /// Prune stale remote branches and garbage collect addon repositories to reclaim disk space
+    Gc(ListOrAll),
+    /// Find addons in the config that aren't git repositories, e.g. copied without their `.git`
+    Doctor {
+        /// Clone broken addons again at their recorded branch and checksum
+        #[arg(long)]
+        repair: bool,
+    },
     /// Remove any addons that are not in the config/lockfile
-    Clean,
+    Clean {
+        /// Confirm removing a large number of addon directories
+        #[arg(long, short)]
+        yes: bool,
+        /// Addon directories to keep even though they are not in the config
+        #[arg(long, value_name = "NAME", num_args = 1..)]
+        exclude: Vec<String>,
+        /// Print the directories and config entries that would be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Only remove temporary clones and cached remote checksums older than this, e.g. `7d`
+        /// or `12h`, instead of unknown addon directories
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<Duration>,
+    },
+    /// Expose disabled addons to luals again
+    Enable { names: Vec<String> },
+    /// Stop exposing addons to luals without removing them
+    Disable { names: Vec<String> },
+    /// Skip addons when updating
+    Pin { names: Vec<String> },
+    /// Update pinned addons again
+    Unpin { names: Vec<String> },
+    /// Print the JSON Schema for the .luarc.json including llam's `workspace.addons`
+    ///
+    /// Reference the written file from the config's `$schema` so editors accept the addons
+    Schema {
+        /// Write the schema to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Show the details of an installed addon
+    Info {
+        name: String,
+        /// Print the addon's entry exactly as it is stored in the config, as json unless
+        /// --format is given
+        #[arg(long)]
+        raw: bool,
+        /// Print the addon as json, yaml, or toml instead
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
     /// List all the install addons known to the manager
-    List,
+    ///
+    /// Each addon is shown with where it is sourced from and what it is checked out to
+    List {
+        /// Print the addons as json, yaml, or toml instead
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+        /// Show which addons were installed as dependencies of which, as a tree
+        #[arg(long)]
+        tree: bool,
+    },
     /// Update the .luarc.json config settings
     Config {
         #[command(subcommand)]
@@ -38,11 +226,46 @@ pub enum Subcommand {
 #[derive(Debug, clap::Args)]
 #[group(required = true, multiple = false)]
 pub struct ListOrAll {
+    #[arg(value_parser = parse_addon)]
     pub addons: Vec<Addon>,
     #[arg(long)]
     pub all: bool,
 }
 
+/// What to check out, set explicitly instead of with an `@` suffix
+#[derive(Debug, Default, clap::Args)]
+#[group(multiple = false)]
+pub struct TargetArgs {
+    /// Branch to check out and follow, overrides an `@` suffix
+    #[arg(long)]
+    pub branch: Option<String>,
+    /// Tag to check out, overrides an `@` suffix
+    #[arg(long)]
+    pub tag: Option<String>,
+    /// Commit to check out, overrides an `@` suffix
+    #[arg(long, value_parser = parse_commit)]
+    pub rev: Option<String>,
+}
+
+impl TargetArgs {
+    pub fn target(self) -> Option<AddonTarget> {
+        match self {
+            Self { branch: Some(branch), .. } => Some(AddonTarget::Branch(branch)),
+            Self { tag: Some(tag), .. } => Some(AddonTarget::Tag(tag)),
+            Self { rev: Some(rev), .. } => Some(AddonTarget::Commit(rev)),
+            _ => None,
+        }
+    }
+}
+
+fn parse_commit(s: &str) -> Result<String, String> {
+    if AddonTarget::is_commit(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("`{s}` is not a commit hash of 7 to 40 hex characters"))
+    }
+}
+
 impl From<ListOrAll> for SomeOrAll<Addon> {
     fn from(value: ListOrAll) -> Self {
        if value.all {
@@ -65,6 +288,36 @@ pub enum Config {
         #[command(subcommand)]
         setting: DocSetting,
     },
+    /// Change what is shown when hovering over a symbol
+    Hover {
+        #[command(subcommand)]
+        setting: HoverSetting,
+    },
+    /// Change semantic highlighting settings
+    Semantic {
+        #[command(subcommand)]
+        setting: SemanticSetting,
+    },
+    /// Change which files are part of the workspace
+    Workspace {
+        #[command(subcommand)]
+        setting: WorkspaceSetting,
+    },
+    /// Change how the language server is launched
+    Misc {
+        #[command(subcommand)]
+        setting: MiscSetting,
+    },
+    /// Change the words the spell checker accepts
+    Spell {
+        #[command(subcommand)]
+        setting: SpellSetting,
+    },
+    /// Change the code formatter settings
+    Format {
+        #[command(subcommand)]
+        setting: FormatSetting,
+    },
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -74,13 +327,52 @@ pub enum DiagnosticSetting {
     /// Enable a diagnostic that has been disabled
     Enable { diagnostics: Vec<Diagnostic> },
     /// Add variables that are declared as globals
-    AddGlobal { globals: Vec<String> },
+    AddGlobal {
+        /// Lua identifiers or dotted paths, e.g. `love` or `vim.api`
+        #[arg(value_parser = parse_global)]
+        globals: Vec<String>,
+    },
     /// Remove variables that are declared as globals
     RemoveGlobal { globals: Vec<String> },
+    /// Show the disabled diagnostics and changed severities by group
+    List,
     /// Set the severity of diagnostics
     Severity {
+        #[arg(required_unless_present = "from")]
         severity: Vec<Set<Diagnostic, Severity>>,
+        /// Read `diagnostic=severity` lines from a file, `#` starts a comment
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Milliseconds to wait after a change before diagnosing the workspace (default 3000)
+    WorkspaceDelay {
+        #[arg(allow_negative_numbers = true, value_parser = parse_workspace_delay)]
+        ms: usize,
+    },
+    /// Percentage of the cpu used to diagnose the workspace (default 100)
+    WorkspaceRate {
+        #[arg(allow_negative_numbers = true, value_parser = parse_workspace_rate)]
+        percent: usize,
     },
+    /// Remove every diagnostic setting, including disabled diagnostics, globals, and severities
+    Reset {
+        /// Confirm removing the settings
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+fn parse_workspace_delay(s: &str) -> Result<usize, String> {
+    let value = s.parse::<i64>().map_err(|e| e.to_string())?;
+    usize::try_from(value).map_err(|_| format!("workspace delay must not be negative, got {value}"))
+}
+
+fn parse_workspace_rate(s: &str) -> Result<usize, String> {
+    let value = s.parse::<i64>().map_err(|e| e.to_string())?;
+    match value {
+        0..=100 => Ok(value as usize),
+        _ => Err(format!("workspace rate must be a percentage between 0 and 100, got {value}")),
+    }
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -91,6 +383,265 @@ pub enum DocSetting {
     Private { patterns: Vec<String> },
     /// Set patterns to mark table keys as protected
     Protected { patterns: Vec<String> },
+    /// Remove every doc setting
+    Reset {
+        /// Confirm removing the settings
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum HoverSetting {
+    /// Enable or disable hover
+    Enable {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Number of enum values shown when hovering over a value with an enum type
+    EnumsLimit { value: usize },
+    /// Expand aliases when hovering over a value with an alias type
+    ExpandAlias {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Number of fields shown when hovering over a table
+    PreviewFields { value: usize },
+    /// Show number literals in other bases when hovering over them
+    ViewNumber {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Show the contents of string literals when hovering over them
+    ViewString {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Maximum length of a string literal shown when hovering over it
+    ViewStringMax { value: usize },
+}
+
+impl HoverSetting {
+    pub fn apply(self, hover: &mut Hover) {
+        match self {
+            Self::Enable { value } => hover.enable = value,
+            Self::EnumsLimit { value } => hover.enums_limit = value,
+            Self::ExpandAlias { value } => hover.expand_alias = value,
+            Self::PreviewFields { value } => hover.preview_fields = value,
+            Self::ViewNumber { value } => hover.view_number = value,
+            Self::ViewString { value } => hover.view_string = value,
+            Self::ViewStringMax { value } => hover.view_string_max = value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum SemanticSetting {
+    /// Enable or disable semantic highlighting
+    Enable {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Highlight type annotations
+    Annotation {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Highlight keywords, literals, and operators. Only useful when the editor can't
+    /// highlight them itself.
+    Keyword {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+    /// Highlight variables, fields, and parameters
+    Variable {
+        #[arg(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+}
+
+impl SemanticSetting {
+    pub fn apply(self, semantic: &mut Semantic) {
+        match self {
+            Self::Enable { value } => semantic.enable = value,
+            Self::Annotation { value } => semantic.annotation = value,
+            Self::Keyword { value } => semantic.keyword = value,
+            Self::Variable { value } => semantic.variable = value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum WorkspaceSetting {
+    /// Ignore directories, using the same syntax as `.gitignore`
+    AddIgnoreDir {
+        #[arg(required = true)]
+        dirs: Vec<String>,
+    },
+    /// Stop ignoring directories
+    RemoveIgnoreDir {
+        #[arg(required = true)]
+        dirs: Vec<String>,
+    },
+}
+
+impl WorkspaceSetting {
+    pub fn apply(self, workspace: &mut Workspace) {
+        match self {
+            Self::AddIgnoreDir { dirs } => workspace.add_ignore_dirs(dirs),
+            Self::RemoveIgnoreDir { dirs } => workspace.ignore_dir.retain(|dir| !dirs.contains(dir)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum MiscSetting {
+    /// Add command line parameters passed to the language server
+    AddParameter {
+        #[arg(required = true, allow_hyphen_values = true)]
+        parameters: Vec<String>,
+    },
+    /// Remove command line parameters passed to the language server
+    RemoveParameter {
+        #[arg(required = true, allow_hyphen_values = true)]
+        parameters: Vec<String>,
+    },
+    /// Path of the language server executable to launch instead of the bundled one
+    ExecutablePath {
+        #[arg(required_unless_present = "clear", conflicts_with = "clear")]
+        path: Option<String>,
+        /// Use the bundled executable again
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+impl MiscSetting {
+    pub fn apply(self, misc: &mut Misc) {
+        match self {
+            Self::AddParameter { parameters } => {
+                for parameter in parameters {
+                    if !misc.parameters.contains(&parameter) {
+                        misc.parameters.push(parameter);
+                    }
+                }
+            }
+            Self::RemoveParameter { parameters } => {
+                misc.parameters.retain(|item| !parameters.contains(item));
+            }
+            Self::ExecutablePath { path, .. } => misc.executable_path = path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum SpellSetting {
+    /// Add words to the custom dictionary, e.g. project specific jargon
+    #[command(alias = "add-dict")]
+    Add {
+        #[arg(required = true)]
+        words: Vec<String>,
+    },
+    /// Remove words from the custom dictionary
+    #[command(alias = "remove-dict")]
+    Remove {
+        #[arg(required = true)]
+        words: Vec<String>,
+    },
+}
+
+impl SpellSetting {
+    pub fn apply(self, spell: &mut Spell) {
+        match self {
+            Self::Add { words } => {
+                for word in words {
+                    if !spell.dict.contains(&word) {
+                        spell.dict.push(word);
+                    }
+                }
+            }
+            Self::Remove { words } => spell.dict.retain(|word| !words.contains(word)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum FormatSetting {
+    /// Set options of the default formatter config, e.g. `indent_style=space`
+    Set {
+        #[arg(required = true)]
+        options: Vec<Set<String, String>>,
+    },
+    /// Remove options from the default formatter config
+    Unset {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Enable the code formatter
+    Enable,
+    /// Disable the code formatter
+    Disable,
+}
+
+impl FormatSetting {
+    pub fn apply(self, format: &mut crate::lua_rc::Format) {
+        match self {
+            Self::Set { options } => format
+                .default_config
+                .extend(options.into_iter().map(|option| (option.key.into(), option.value.into()))),
+            Self::Unset { keys } => format.default_config.retain(|key, _| !keys.iter().any(|k| k == key)),
+            Self::Enable => format.enable = true,
+            Self::Disable => format.enable = false,
+        }
+    }
+}
+
+static LUA_KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Accept a Lua identifier or a dotted path of identifiers
+fn parse_global(s: &str) -> Result<String, String> {
+    let valid = s.split('.').all(|part| {
+        let mut chars = part.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !LUA_KEYWORDS.contains(&part)
+    });
+    if valid {
+        Ok(s.to_string())
+    } else {
+        Err(format!("`{s}` is not a valid Lua identifier or dotted path"))
+    }
+}
+
+/// Parse a duration such as `30s`, `15m`, `12h`, `7d`, or `2w`
+fn parse_age(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value.parse::<u64>().map_err(|_| format!("invalid age `{s}`, expected e.g. `7d` or `12h`"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid age unit in `{s}`, expected one of s, m, h, d, or w")),
+    };
+    Ok(Duration::from_secs(value * seconds))
+}
+
+fn parse_addon(s: &str) -> Result<Addon, String> {
+    Addon::from_str(s).map_err(|e| e.to_string())
+}
+
+fn parse_name(s: &str) -> Result<String, String> {
+    if crate::is_valid_name(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("`{s}` can't be used as a directory name, use letters, digits, `-`, `_`, and `.`"))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -121,3 +672,292 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+    use crate::lua_rc::{Diagnostics, LuaRc};
+
+    fn config(args: &[&str]) -> Config {
+        let llam = LLAM::try_parse_from([&["llam", "config"], args].concat()).unwrap();
+        match llam.command {
+            Subcommand::Config { subcommand } => subcommand,
+            command => panic!("unexpected command: {command:?}"),
+        }
+    }
+
+    #[test]
+    fn hover_settings() {
+        let mut hover = Hover::default();
+        for args in [["hover", "enums-limit", "10"], ["hover", "view-string", "false"]] {
+            match config(&args) {
+                Config::Hover { setting } => setting.apply(&mut hover),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&hover).unwrap(),
+            serde_json::json!({ "enumsLimit": 10, "viewString": false })
+        );
+
+        // Defaults are omitted
+        HoverSetting::EnumsLimit { value: 5 }.apply(&mut hover);
+        HoverSetting::ViewString { value: true }.apply(&mut hover);
+        assert_eq!(hover, Hover::default());
+        assert_eq!(serde_json::to_value(&hover).unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn semantic_settings() {
+        let mut semantic = Semantic::default();
+        for args in [["semantic", "keyword", "true"], ["semantic", "variable", "false"]] {
+            match config(&args) {
+                Config::Semantic { setting } => setting.apply(&mut semantic),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&semantic).unwrap(),
+            serde_json::json!({ "keyword": true, "variable": false })
+        );
+
+        assert!(LLAM::try_parse_from(["llam", "config", "semantic", "keyword", "maybe"]).is_err());
+    }
+
+    #[test]
+    fn misc_settings() {
+        let mut misc = Misc::default();
+        for args in [
+            &["misc", "add-parameter", "--locale=en-us", "--develop=true"][..],
+            &["misc", "add-parameter", "--locale=en-us"],
+            &["misc", "executable-path", "/opt/luals/bin/lua-language-server"],
+        ] {
+            match config(args) {
+                Config::Misc { setting } => setting.apply(&mut misc),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&misc).unwrap(),
+            serde_json::json!({
+                "parameters": ["--locale=en-us", "--develop=true"],
+                "executablePath": "/opt/luals/bin/lua-language-server",
+            })
+        );
+
+        for args in [
+            &["misc", "remove-parameter", "--develop=true"][..],
+            &["misc", "executable-path", "--clear"],
+        ] {
+            match config(args) {
+                Config::Misc { setting } => setting.apply(&mut misc),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(serde_json::to_value(&misc).unwrap(), serde_json::json!({ "parameters": ["--locale=en-us"] }));
+
+        for args in [&["executable-path"][..], &["executable-path", "luals", "--clear"], &["add-parameter"]] {
+            let args = [&["llam", "config", "misc"], args].concat();
+            assert!(LLAM::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn workspace_settings() {
+        let mut workspace = Workspace::default();
+        for args in [
+            &["workspace", "add-ignore-dir", "build", ".git"][..],
+            &["workspace", "add-ignore-dir", "build"],
+            &["workspace", "remove-ignore-dir", ".git"],
+        ] {
+            match config(args) {
+                Config::Workspace { setting } => setting.apply(&mut workspace),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(workspace.ignore_dir, ["build"]);
+        assert!(LLAM::try_parse_from(["llam", "config", "workspace", "add-ignore-dir"]).is_err());
+    }
+
+    #[test]
+    fn empty_misc_block_is_dropped() {
+        let mut rc = LuaRc::default();
+        MiscSetting::AddParameter { parameters: vec!["--develop=true".into()] }.apply(rc.misc_mut());
+        assert_eq!(
+            serde_json::to_value(&rc).unwrap(),
+            serde_json::json!({ "misc": { "parameters": ["--develop=true"] } })
+        );
+
+        MiscSetting::RemoveParameter { parameters: vec!["--develop=true".into()] }.apply(rc.misc_mut());
+        assert_eq!(serde_json::to_value(&rc).unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn format_settings() {
+        let mut rc = LuaRc::default();
+        for args in [
+            &["format", "set", "indent_style=space", "indent_size=2"][..],
+            &["format", "set", "indent_size=4", "quote_style=double"],
+            &["format", "unset", "quote_style"],
+            &["format", "disable"],
+        ] {
+            match config(args) {
+                Config::Format { setting } => setting.apply(rc.format_mut()),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&rc).unwrap(),
+            serde_json::json!({
+                "format": {
+                    "enable": false,
+                    "defaultConfig": { "indent_size": "4", "indent_style": "space" },
+                }
+            })
+        );
+
+        for args in [&["format", "unset", "indent_size", "indent_style"][..], &["format", "enable"]] {
+            match config(args) {
+                Config::Format { setting } => setting.apply(rc.format_mut()),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(serde_json::to_value(&rc).unwrap(), serde_json::json!({}));
+
+        for args in [&["set"][..], &["set", "indent_size"], &["unset"]] {
+            let args = [&["llam", "config", "format"], args].concat();
+            assert!(LLAM::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn spell_settings() {
+        let mut rc = LuaRc::default();
+        for args in [
+            &["spell", "add", "luals", "llam"][..],
+            &["spell", "add-dict", "llam", "lovr"],
+        ] {
+            match config(args) {
+                Config::Spell { setting } => setting.apply(rc.spell_mut()),
+                other => panic!("unexpected config: {other:?}"),
+            }
+        }
+        assert_eq!(
+            serde_json::to_value(&rc).unwrap(),
+            serde_json::json!({ "spell": { "dict": ["luals", "llam", "lovr"] } })
+        );
+
+        match config(&["spell", "remove", "luals", "llam", "lovr"]) {
+            Config::Spell { setting } => setting.apply(rc.spell_mut()),
+            other => panic!("unexpected config: {other:?}"),
+        }
+        assert_eq!(serde_json::to_value(&rc).unwrap(), serde_json::json!({}));
+
+        assert!(LLAM::try_parse_from(["llam", "config", "spell", "add"]).is_err());
+    }
+
+    #[test]
+    fn workspace_diagnostic_settings() {
+        let diagnostic = |args: &[&str]| match config(&[&["diagnostic"], args].concat()) {
+            Config::Diagnostic { setting } => setting,
+            other => panic!("unexpected config: {other:?}"),
+        };
+
+        assert!(matches!(diagnostic(&["workspace-delay", "500"]), DiagnosticSetting::WorkspaceDelay { ms: 500 }));
+        assert!(matches!(diagnostic(&["workspace-rate", "0"]), DiagnosticSetting::WorkspaceRate { percent: 0 }));
+        assert!(matches!(diagnostic(&["workspace-rate", "100"]), DiagnosticSetting::WorkspaceRate { percent: 100 }));
+
+        for args in [["workspace-delay", "-1"], ["workspace-rate", "101"], ["workspace-rate", "-5"]] {
+            let args = [&["llam", "config", "diagnostic"], args.as_slice()].concat();
+            assert!(LLAM::try_parse_from(args).is_err());
+        }
+
+        assert!(matches!(diagnostic(&["reset"]), DiagnosticSetting::Reset { yes: false }));
+        assert!(matches!(diagnostic(&["reset", "--yes"]), DiagnosticSetting::Reset { yes: true }));
+
+        let mut diagnostics = Diagnostics { workspace_delay: 500, workspace_rate: 50, ..Default::default() };
+        assert_eq!(
+            serde_json::to_value(&diagnostics).unwrap(),
+            serde_json::json!({ "workspaceDelay": 500, "workspaceRate": 50 })
+        );
+        diagnostics.workspace_delay = 3000;
+        diagnostics.workspace_rate = 100;
+        assert_eq!(serde_json::to_value(&diagnostics).unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn severity_from_file() {
+        match config(&["diagnostic", "severity", "--from", "policy.txt", "global:lowercase-global=Hint"]) {
+            Config::Diagnostic { setting: DiagnosticSetting::Severity { severity, from } } => {
+                assert_eq!(severity.len(), 1);
+                assert_eq!(from, Some(PathBuf::from("policy.txt")));
+            }
+            other => panic!("unexpected config: {other:?}"),
+        }
+        assert!(LLAM::try_parse_from(["llam", "config", "diagnostic", "severity"]).is_err());
+    }
+
+    #[test]
+    fn global_names() {
+        let globals = |args: &[&str]| {
+            let args = [&["llam", "config", "diagnostic", "add-global"], args].concat();
+            match LLAM::try_parse_from(args).map(|llam| llam.command) {
+                Ok(Subcommand::Config {
+                    subcommand: Config::Diagnostic { setting: DiagnosticSetting::AddGlobal { globals } },
+                }) => Ok(globals),
+                Ok(command) => panic!("unexpected command: {command:?}"),
+                Err(err) => Err(err.to_string()),
+            }
+        };
+
+        assert_eq!(globals(&["love", "_G", "vim.api", "x1"]).unwrap(), ["love", "_G", "vim.api", "x1"]);
+        for invalid in ["my global", "1x", "vim.", "a..b", "end", "love-2d"] {
+            let err = globals(&[invalid]).unwrap_err();
+            assert!(err.contains("not a valid Lua identifier"), "{invalid}: {err}");
+        }
+
+        let mut diagnostics = Diagnostics { globals: vec!["love".into()], ..Default::default() };
+        diagnostics.add_globals(["vim".to_string(), "love".to_string(), "vim".to_string()]);
+        assert_eq!(diagnostics.globals, ["love", "vim"]);
+    }
+
+    #[test]
+    fn explicit_add_targets() {
+        let target = |args: &[&str]| {
+            let args = [&["llam", "add", "love2d@v11.4"], args].concat();
+            match LLAM::try_parse_from(args).map(|llam| llam.command) {
+                Ok(Subcommand::Add { target, .. }) => Ok(target.target()),
+                Ok(command) => panic!("unexpected command: {command:?}"),
+                Err(err) => Err(err.kind()),
+            }
+        };
+
+        assert_eq!(target(&[]), Ok(None));
+        assert_eq!(target(&["--branch", "feature@2"]), Ok(Some(AddonTarget::Branch("feature@2".into()))));
+        assert_eq!(target(&["--tag", "v11.5"]), Ok(Some(AddonTarget::Tag("v11.5".into()))));
+        assert_eq!(target(&["--rev", "abc1234"]), Ok(Some(AddonTarget::Commit("abc1234".into()))));
+        assert!(target(&["--rev", "main"]).is_err());
+        assert_eq!(target(&["--branch", "main", "--tag", "v1"]), Err(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(target(&["--tag", "v1", "--rev", "abc1234"]), Err(clap::error::ErrorKind::ArgumentConflict));
+
+        let mut addon = Addon::from("love2d@v11.4");
+        addon.checkout = AddonTarget::Branch("feature@2".into());
+        assert_eq!((addon.branch(), addon.checksum()), (Some("feature@2"), None));
+        addon.checkout = AddonTarget::Commit("abc1234".into());
+        assert_eq!(addon.checkout, AddonTarget::Commit("abc1234".into()));
+    }
+
+    #[test]
+    fn ages() {
+        let hour = Duration::from_secs(60 * 60);
+        assert_eq!(parse_age("7d"), Ok(hour * 24 * 7));
+        assert_eq!(parse_age("12h"), Ok(hour * 12));
+        assert_eq!(parse_age("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_age("45s"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_age("2w"), Ok(hour * 24 * 14));
+        for invalid in ["", "d", "7", "7y", "-1d", "1.5h"] {
+            assert!(parse_age(invalid).is_err(), "{invalid}");
+        }
+    }
+}
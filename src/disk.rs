@@ -0,0 +1,203 @@
+//! Best effort checks for available disk space before cloning addons, and removing addon
+//! directories.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use crate::Error;
+
+/// Space that must be available before an addon is cloned
+pub const MIN_FREE_SPACE: u64 = 50 * 1024 * 1024;
+
+/// Number of directories removed at the same time by default
+pub const REMOVE_THREADS: usize = 4;
+
+/// Bytes available to the current user on the filesystem containing `path`
+///
+/// `path` doesn't have to exist, the closest existing ancestor is used instead. Returns `None`
+/// when the available space can't be determined.
+pub fn available_space(path: impl AsRef<Path>) -> Option<u64> {
+    let path = path.as_ref().ancestors().find(|p| p.exists())?;
+    platform::available_space(path)
+}
+
+/// Error when less than `required` bytes are `available`
+///
+/// An unknown amount of available space is never an error.
+pub fn check_space(path: impl AsRef<Path>, available: Option<u64>, required: u64) -> Result<(), Error> {
+    match available {
+        Some(available) if available < required => Err(Error::custom(format!(
+            "only {} MiB of disk space is available at {}, at least {} MiB is needed",
+            available / 1024 / 1024,
+            path.as_ref().display(),
+            required / 1024 / 1024,
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Entries of `dir` that were last modified more than `age` before `now`
+///
+/// A missing `dir` has no entries, entries without a modification time are never old.
+pub fn older_than(dir: impl AsRef<Path>, age: Duration, now: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut old = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|elapsed| elapsed > age))
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    old.sort();
+    old
+}
+
+/// Total size in bytes of the files in `path`, recursively. Symlinks are not followed and
+/// entries that can't be read are skipped.
+pub fn dir_size(path: impl AsRef<Path>) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map(|m| m.len()).unwrap_or_default(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Remove every directory in `paths` using up to `threads` threads
+///
+/// A failed removal doesn't stop the others, the result of each removal is returned in the same
+/// order as `paths`.
+pub fn remove_dirs(paths: &[PathBuf], threads: usize) -> Vec<std::io::Result<()>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, paths.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else {
+                    break;
+                };
+                let result = std::fs::remove_dir_all(path);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every path is removed by a worker"))
+        .collect()
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    pub fn available_space(path: &Path) -> Option<u64> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `path` is a valid nul terminated string and `stat` is only read when the call
+        // succeeds, in which case it has been initialized.
+        unsafe {
+            if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return None;
+            }
+            let stat = stat.assume_init();
+            #[allow(clippy::unnecessary_cast)]
+            Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    use std::path::Path;
+
+    pub fn available_space(_: &Path) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn entries_older_than() {
+        let dir = TempDir::new();
+        for name in ["old", "new"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        std::fs::File::open(dir.join("old")).unwrap().set_modified(now - day * 10).unwrap();
+
+        assert_eq!(older_than(&dir, day * 7, now), [dir.join("old")]);
+        assert_eq!(older_than(&dir, day * 7, now + day * 8), [dir.join("new"), dir.join("old")]);
+        assert!(older_than(&dir, day * 30, now).is_empty());
+        assert!(older_than(dir.join("missing"), day, now).is_empty());
+    }
+
+    #[test]
+    fn size_of_directory() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a"), [0; 10]).unwrap();
+        std::fs::write(dir.join("nested").join("b"), [0; 32]).unwrap();
+
+        assert_eq!(dir_size(&dir), 42);
+        assert_eq!(dir_size(dir.join("missing")), 0);
+    }
+
+    #[test]
+    fn remove_dirs_continues_after_failure() {
+        let dir = TempDir::new();
+        let paths = (0..6).map(|i| dir.join(format!("addon-{i}"))).collect::<Vec<_>>();
+        for path in paths.iter().filter(|p| !p.ends_with("addon-2")) {
+            std::fs::create_dir_all(path.join("library")).unwrap();
+            std::fs::write(path.join("library").join("init.lua"), "").unwrap();
+        }
+
+        let results = remove_dirs(&paths, 3);
+        assert_eq!(results.len(), paths.len());
+        for (path, result) in paths.iter().zip(results) {
+            assert_eq!(result.is_err(), path.ends_with("addon-2"), "{}", path.display());
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn low_space_is_rejected() {
+        let path = Path::new("/tmp");
+        assert!(check_space(path, Some(MIN_FREE_SPACE - 1), MIN_FREE_SPACE).is_err());
+        assert!(check_space(path, Some(MIN_FREE_SPACE), MIN_FREE_SPACE).is_ok());
+        // Unknown space doesn't block cloning
+        assert!(check_space(path, None, MIN_FREE_SPACE).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_available_space() {
+        let missing = std::env::temp_dir().join("llam-missing").join("nested");
+        assert!(available_space(missing).is_some());
+    }
+}
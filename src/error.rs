@@ -1,8 +1,10 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
 pub enum Error {
     Context(String, Box<Error>),
     Custom(String),
+    /// A config file that exists but can't be parsed
+    InvalidConfig(PathBuf, String),
     Reqwest(reqwest::Error),
     Json(Box<dyn std::error::Error + Send>),
     Io(std::io::Error),
@@ -18,6 +20,26 @@ impl Error {
     pub fn custom(message: impl Display) -> Self {
         Self::Custom(message.to_string())
     }
+
+    /// Machine readable category of the error, context is looked through to the underlying error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Context(_, err) => err.kind(),
+            Self::Custom(_) => "custom",
+            Self::InvalidConfig(..) => "config",
+            Self::Reqwest(_) => "network",
+            Self::Json(_) => "parse",
+            Self::Io(_) => "io",
+        }
+    }
+
+    /// The error as `{"error": "...", "kind": "..."}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": self.to_string(),
+            "kind": self.kind(),
+        })
+    }
 }
 
 impl std::fmt::Debug for Error {
@@ -34,6 +56,7 @@ impl Display for Error {
             Self::Io(io) => write!(f, "{io}"),
             Self::Context(context, err) => write!(f, "ctx: {context}\n{err}"),
             Self::Custom(message) => write!(f, "{message}"),
+            Self::InvalidConfig(_, message) => write!(f, "{message}"),
         }
     }
 }
@@ -50,6 +73,18 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<serde_yaml::Error> for Error {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Json(Box::new(value))
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::Json(Box::new(value))
+    }
+}
+
 impl<E: std::error::Error + Send + 'static> From<serde_path_to_error::Error<E>> for Error {
     fn from(value: serde_path_to_error::Error<E>) -> Self {
         Self::Json(Box::new(value))
@@ -61,3 +96,28 @@ impl From<std::io::Error> for Error {
         Self::Io(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+
+    #[test]
+    fn json_errors() {
+        let err = Error::custom("love2d is not installed");
+        assert_eq!(
+            err.to_json(),
+            serde_json::json!({ "error": "love2d is not installed", "kind": "custom" })
+        );
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let err = Error::context("reading .luarc.json", io);
+        assert_eq!(err.kind(), "io");
+        assert_eq!(err.to_json()["error"], "ctx: reading .luarc.json\nno such file");
+
+        let err = Error::from(serde_json::from_str::<u8>("x").unwrap_err());
+        assert_eq!(err.kind(), "parse");
+
+        let err = Error::InvalidConfig(".luarc.json".into(), "invalid `workspace` in .luarc.json".into());
+        assert_eq!(err.kind(), "config");
+    }
+}
@@ -1,7 +1,18 @@
-use std::path::Path;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    time::{Duration, Instant},
+};
 
 use crate::Error;
 
+/// Branches tried in order when a repository's default branch can't be determined
+pub const DEFAULT_BRANCH_FALLBACKS: [&str; 2] = ["main", "master"];
+
+/// Commands that are passed `--quiet` when the [`Cli`] is quiet
+const QUIET_COMMANDS: [&str; 5] = ["clone", "fetch", "pull", "switch", "reset"];
+
 pub enum ResetType {
     Soft,
     Hard,
@@ -10,32 +21,328 @@ pub enum ResetType {
 impl AsRef<str> for ResetType {
     fn as_ref(&self) -> &str {
         match self {
-            Self::Soft => "soft",
-            Self::Hard => "hard",
+            Self::Soft => "--soft",
+            Self::Hard => "--hard",
+        }
+    }
+}
+
+/// `(checksum, ref)` pairs of `git ls-remote` output, lines that aren't tab separated are skipped
+fn parse_ls_remote(output: &str) -> Vec<(&str, &str)> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(checksum, name)| (checksum.trim(), name.trim()))
+        .filter(|(checksum, name)| !checksum.is_empty() && !name.is_empty())
+        .collect()
+}
+
+/// Options for [`Cli::fetch_with`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchOptions {
+    /// Remove remote tracking branches that no longer exist on the remote
+    pub prune: bool,
+    /// Fetch every tag, not only the tags pointing into the fetched history
+    pub tags: bool,
+    /// Only fetch this many commits of history, keeping a shallow clone shallow
+    pub depth: Option<u32>,
+    /// Fetch the branches from this url into `origin`'s remote tracking branches instead of
+    /// fetching from `origin`, e.g. a mirror of the same repository
+    pub url: Option<String>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            prune: true,
+            tags: false,
+            depth: None,
+            url: None,
+        }
+    }
+}
+
+/// Options for [`Cli::clone_with`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CloneOptions {
+    /// Only clone this many commits of history
+    pub depth: Option<u32>,
+    /// Clone without a working tree, e.g. for a clone that worktrees are added to
+    pub bare: bool,
+    /// Bare clone that also mirrors every ref of the remote
+    pub mirror: bool,
+}
+
+impl CloneOptions {
+    /// Arguments passed to git before the url and directory name
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec!["clone".to_string(), "--progress".to_string()];
+        if let Some(depth) = self.depth {
+            args.push(format!("--depth={depth}"));
+        }
+        // `--mirror` implies `--bare`
+        if self.mirror {
+            args.push("--mirror".to_string());
+        } else if self.bare {
+            args.push("--bare".to_string());
+        }
+        args
+    }
+}
+
+impl FetchOptions {
+    /// Arguments passed to git
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec!["fetch".to_string()];
+        if self.prune {
+            args.push("-p".to_string());
+        }
+        if self.tags {
+            args.push("--tags".to_string());
+        }
+        if let Some(depth) = self.depth {
+            args.push(format!("--depth={depth}"));
+        }
+        if let Some(url) = self.url.as_ref() {
+            args.push(url.clone());
+            args.push("+refs/heads/*:refs/remotes/origin/*".to_string());
+        }
+        args
+    }
+}
+
+/// Wrapper around the `git` executable
+///
+/// Defaults to resolving `git` from `PATH`. Use [`Cli::new`] to point at a specific binary or
+/// [`Cli::builder`] to configure the rest of the options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    binary: PathBuf,
+    timeout: Option<Duration>,
+    retries: usize,
+    token: Option<String>,
+    fallback_branches: Vec<String>,
+    quiet: bool,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            binary: PathBuf::from("git"),
+            timeout: None,
+            retries: 0,
+            token: None,
+            fallback_branches: DEFAULT_BRANCH_FALLBACKS.iter().map(|b| b.to_string()).collect(),
+            quiet: false,
         }
     }
 }
 
-pub struct Cli;
 impl Cli {
-    pub fn checksum<P: AsRef<Path>>(dir: P, branch: Option<&str>) -> Result<String, Error> {
+    pub fn new(binary: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn builder() -> CliBuilder {
+        CliBuilder::default()
+    }
+
+    /// Path to the git executable that is invoked
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    /// Max amount of time a single git command may run before it is killed
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Number of times a network operation (clone, fetch, pull) is retried after failing
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Token sent as a bearer `Authorization` header for http remotes
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Whether `--quiet` is passed to the commands that support it
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// Arguments passed to git for `args`, with `--quiet` after the command name when quiet
+    fn args<I, S>(&self, args: I) -> Vec<std::ffi::OsString>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut args = args.into_iter().map(|arg| arg.as_ref().to_os_string()).collect::<Vec<_>>();
+        if self.quiet && args.first().is_some_and(|command| QUIET_COMMANDS.iter().any(|quiet| command == quiet)) {
+            args.insert(1, "--quiet".into());
+        }
+        args
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        if let Some(token) = self.token.as_deref() {
+            // Passed through the environment so the token never shows up in the process args
+            command
+                .env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+                .env("GIT_CONFIG_VALUE_0", format!("Authorization: Bearer {token}"));
+        }
+        command
+    }
+
+    /// Run a git command in `dir`, killing it if it exceeds the configured timeout
+    fn output<I, S>(&self, dir: impl AsRef<Path>, args: I) -> std::io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.run(dir, args, None)
+    }
+
+    /// Run a git command in `dir`, calling `on_stderr` with every line git writes to stderr as it
+    /// is written. Progress lines that are overwritten with `\r` are reported individually.
+    fn run<'f, I, S>(
+        &self,
+        dir: impl AsRef<Path>,
+        args: I,
+        mut on_stderr: Option<&mut (dyn FnMut(&str) + 'f)>,
+    ) -> std::io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut command = self.command();
+        command.args(self.args(args)).current_dir(dir);
+
+        if self.timeout.is_none() && on_stderr.is_none() {
+            return command.output();
+        }
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Drain the pipes on separate threads so a chatty command can't block on a full pipe
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let out = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        let (lines, received) = std::sync::mpsc::channel::<String>();
+        let err = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let mut chunk = [0; 1024];
+            let mut line_start = 0;
+            while let Ok(count) = stderr.read(&mut chunk) {
+                if count == 0 {
+                    break;
+                }
+                buf.extend_from_slice(&chunk[..count]);
+
+                while let Some(end) = buf[line_start..].iter().position(|b| matches!(b, b'\r' | b'\n')) {
+                    let line = String::from_utf8_lossy(&buf[line_start..line_start + end]);
+                    if !line.is_empty() {
+                        let _ = lines.send(line.to_string());
+                    }
+                    line_start += end + 1;
+                }
+            }
+            buf
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(on_stderr) = on_stderr.as_mut() {
+                for line in received.try_iter() {
+                    on_stderr(&line);
+                }
+            }
+
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if let Some(timeout) = self.timeout {
+                if start.elapsed() >= timeout {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("git command timed out after {}ms", timeout.as_millis()),
+                    ));
+                }
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        let stderr = err.join().unwrap_or_default();
+        if let Some(on_stderr) = on_stderr.as_mut() {
+            for line in received.try_iter() {
+                on_stderr(&line);
+            }
+        }
+
+        Ok(Output {
+            status,
+            stdout: out.join().unwrap_or_default(),
+            stderr,
+        })
+    }
+
+    /// Same as [`output`][Cli::output] but retries unsuccessful runs up to the configured number
+    /// of retries. Only used for commands that talk to a remote.
+    fn output_with_retry(&self, dir: impl AsRef<Path>, args: &[&str]) -> std::io::Result<Output> {
+        self.run_with_retry(dir, args, None)
+    }
+
+    fn run_with_retry<'f>(
+        &self,
+        dir: impl AsRef<Path>,
+        args: &[&str],
+        mut on_stderr: Option<&mut (dyn FnMut(&str) + 'f)>,
+    ) -> std::io::Result<Output> {
+        let dir = dir.as_ref();
+        let mut attempt = 0;
+        loop {
+            let result = self.run(dir, args, on_stderr.as_deref_mut());
+            match result.as_ref() {
+                Ok(output) if output.status.success() => return result,
+                _ if attempt >= self.retries => return result,
+                _ => attempt += 1,
+            }
+        }
+    }
+
+    pub fn checksum<P: AsRef<Path>>(&self, dir: P, branch: Option<&str>) -> Result<String, Error> {
         let result = if let Some(branch) = branch.as_ref() {
             //git log -n 1 origin/main --pretty=format:'%H'
-            std::process::Command::new("git")
-                .args([
+            self.output(
+                dir,
+                [
                     "log",
                     "-n",
                     "1",
                     format!("origin/{branch}").as_str(),
-                    "--pretty=format:'%H'",
-                ])
-                .current_dir(dir)
-                .output()?
+                    "--pretty=format:%H",
+                ],
+            )?
         } else {
-            std::process::Command::new("git")
-                .args(["rev-parse", "--verify", "HEAD"])
-                .current_dir(dir)
-                .output()?
+            self.output(dir, ["rev-parse", "--verify", "HEAD"])?
         };
 
         if !result.status.success() {
@@ -47,84 +354,476 @@ impl Cli {
         Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
     }
 
-    pub fn branch_name<P: AsRef<Path>>(dir: P) -> Result<String, Error> {
-        let result = std::process::Command::new("git")
-            .args(["rev-parse", "--abbrev-ref", "HEAD"])
-            .current_dir(dir)
-            .output()?;
+    /// Number of commits reachable from `to` but not from `from`
+    pub fn commit_count<P: AsRef<Path>>(&self, dir: P, from: &str, to: &str) -> Result<usize, Error> {
+        let result = self.output(dir, ["rev-list", "--count", &format!("{from}..{to}")])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to count commits:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+        String::from_utf8_lossy(&result.stdout)
+            .trim()
+            .parse()
+            .map_err(|err| Error::custom(format!("failed to count commits: {err}")))
+    }
+
+    /// Git directory of the repository `dir` belongs to, shared by all of its worktrees
+    pub fn common_dir<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf, Error> {
+        let dir = dir.as_ref();
+        let result = self.output(dir, ["rev-parse", "--git-common-dir"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to get the git directory:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        // Relative to `dir` unless it is outside of the worktree
+        let common = dir.join(String::from_utf8_lossy(&result.stdout).trim());
+        Ok(common.canonicalize().unwrap_or(common))
+    }
+
+    pub fn branch_name<P: AsRef<Path>>(&self, dir: P) -> Result<String, Error> {
+        let result = self.output(dir, ["rev-parse", "--abbrev-ref", "HEAD"])?;
+
+        Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
+    }
+
+    /// Default branch of the `origin` remote
+    ///
+    /// Uses `origin/HEAD`, then asks the remote. When neither is known the first fallback branch
+    /// that exists locally or on the remote is used, see [`CliBuilder::fallback_branches`].
+    pub fn default_branch_name<P: AsRef<Path>>(&self, dir: P) -> Result<String, Error> {
+        let dir = dir.as_ref();
+
+        let result = self.output(dir, ["symbolic-ref", "refs/remotes/origin/HEAD"])?;
+        if result.status.success() {
+            let head = String::from_utf8_lossy(&result.stdout);
+            if let Some(branch) = head.trim().strip_prefix("refs/remotes/origin/") {
+                if !branch.is_empty() {
+                    return Ok(branch.to_string());
+                }
+            }
+        }
+
+        // `origin/HEAD` isn't always set, e.g. after a shallow or mirror clone, so ask the remote
+        let result = self.output(dir, ["remote", "show", "origin"])?;
+        if result.status.success() {
+            let stdout = String::from_utf8_lossy(&result.stdout);
+            let branch = stdout
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("HEAD branch:"))
+                .map(str::trim)
+                .filter(|branch| !branch.is_empty() && *branch != "(unknown)");
+            if let Some(branch) = branch {
+                return Ok(branch.to_string());
+            }
+        }
+
+        for branch in self.fallback_branches.iter() {
+            let result = self.output(
+                dir,
+                ["rev-parse", "--verify", "--quiet", &format!("refs/remotes/origin/{branch}")],
+            )?;
+            if result.status.success() || self.remote_branch_exists(dir, branch).unwrap_or(false) {
+                return Ok(branch.to_string());
+            }
+        }
+
+        Err(Error::custom(format!(
+            "failed to determine the default branch of {}",
+            dir.display()
+        )))
+    }
+
+    /// Commit a branch or tag points to on a remote repository without cloning or fetching it,
+    /// the remote's `HEAD` when `reference` is `None`
+    ///
+    /// Branches take precedence over tags with the same name. Annotated tags resolve to the
+    /// commit they point to instead of the tag object.
+    pub fn remote_checksum(&self, url: &str, reference: Option<&str>) -> Result<String, Error> {
+        let patterns = match reference {
+            Some(name) => vec![
+                format!("refs/heads/{name}"),
+                format!("refs/tags/{name}"),
+                format!("refs/tags/{name}^{{}}"),
+            ],
+            None => vec!["HEAD".to_string()],
+        };
+        let mut args = vec!["ls-remote", url];
+        args.extend(patterns.iter().map(String::as_str));
+        let result = self.output_with_retry(".", &args)?;
+
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to get remote checksum:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let refs = parse_ls_remote(&stdout);
+        patterns
+            .iter()
+            .map(|pattern| pattern.trim_end_matches("^{}"))
+            .find_map(|wanted| {
+                let peeled = format!("{wanted}^{{}}");
+                refs.iter()
+                    .find(|(_, name)| *name == peeled)
+                    .or_else(|| refs.iter().find(|(_, name)| *name == wanted))
+                    .map(|(checksum, _)| checksum.to_string())
+            })
+            .ok_or_else(|| {
+                Error::custom(format!("`{}` does not exist on the remote", reference.unwrap_or("HEAD")))
+            })
+    }
+
+    /// Resolve a revision (checksum, tag, branch) to the commit it points to
+    pub fn resolve<P: AsRef<Path>>(&self, dir: P, revision: &str) -> Result<String, Error> {
+        let result = self.output(dir, ["rev-parse", "--verify", "--quiet", &format!("{revision}^{{commit}}")])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!("unknown revision `{revision}`")));
+        }
+        Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
+    }
+
+    /// Object hash git gives the contents of `file`, used as the checksum of files that aren't
+    /// in a repository
+    pub fn hash_object(&self, file: impl AsRef<Path>) -> Result<String, Error> {
+        let file = file.as_ref();
+        let dir = file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let name = file.file_name().unwrap_or(file.as_os_str());
+        let result = self.output(dir, [std::ffi::OsStr::new("hash-object"), "--no-filters".as_ref(), "--".as_ref(), name])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to hash {}:\n{}",
+                file.display(),
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+        Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
+    }
+
+    /// Whether the git-lfs extension is installed
+    pub fn lfs_installed(&self) -> bool {
+        self.output(".", ["lfs", "version"]).is_ok_and(|result| result.status.success())
+    }
+
+    /// Download the git-lfs files of the checked out commit, replacing their pointer files
+    pub fn lfs_pull<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let result = self.output_with_retry(dir, &["lfs", "pull"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to pull git-lfs files:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+        Ok(())
+    }
 
+    /// Url of the `origin` remote
+    pub fn remote_url<P: AsRef<Path>>(&self, dir: P) -> Result<String, Error> {
+        let result = self.output(dir, ["remote", "get-url", "origin"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to get the remote url:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
         Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
     }
 
-    pub fn default_branch_name<P: AsRef<Path>>(dir: P) -> Result<String, Error> {
-        let result = std::process::Command::new("git")
-            .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
-            .current_dir(dir)
-            .output()?;
+    /// Check if the `origin` remote has a branch with the given name
+    pub fn remote_branch_exists<P: AsRef<Path>>(&self, dir: P, branch: &str) -> Result<bool, Error> {
+        let result = self.output_with_retry(
+            dir,
+            &["ls-remote", "--exit-code", "--heads", "origin", branch],
+        )?;
 
-        let result = String::from_utf8_lossy(&result.stdout).trim().to_string();
-        Ok(result.rsplit_once('/').unwrap().1.to_string())
+        match result.status.code() {
+            Some(0) => Ok(true),
+            // `--exit-code` exits with 2 when no matching refs were found
+            Some(2) => Ok(false),
+            _ => Err(Error::custom(format!(
+                "failed to list remote branches:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            ))),
+        }
     }
 
-    pub fn fetch<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
-        std::process::Command::new("git")
-            .args(["fetch", "-p"])
-            .current_dir(dir)
-            .output()?;
+    /// Current default branch of the `origin` remote
+    ///
+    /// Unlike [`Cli::default_branch_name`] this asks the remote directly, so it reflects a
+    /// default branch that was renamed since the repository was cloned.
+    pub fn remote_default_branch<P: AsRef<Path>>(&self, dir: P) -> Result<String, Error> {
+        let result = self.output_with_retry(dir, &["ls-remote", "--symref", "origin", "HEAD"])?;
+
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to get the remote default branch:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        // ref: refs/heads/main	HEAD
+        String::from_utf8_lossy(&result.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("ref: refs/heads/"))
+            .and_then(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .ok_or_else(|| Error::custom("the remote does not have a default branch"))
+    }
+
+    pub fn fetch<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        self.fetch_with(dir, &FetchOptions::default())
+    }
+
+    /// Same as [`fetch`][Cli::fetch] with control over pruning, tags, and depth
+    pub fn fetch_with<P: AsRef<Path>>(&self, dir: P, options: &FetchOptions) -> Result<(), Error> {
+        let args = options.args();
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        let result = self.output_with_retry(dir, &args)?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to fetch:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
 
         Ok(())
     }
 
-    pub fn switch<P: AsRef<Path>>(dir: P, branch: impl AsRef<str>) -> Result<(), Error> {
-        std::process::Command::new("git")
-            .args(["switch", branch.as_ref()])
-            .current_dir(dir)
-            .output()?;
+    /// Remove unreachable objects and pack the repository
+    pub fn gc<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let result = self.output(dir, ["gc", "--quiet", "--prune=now"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to collect garbage:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+        Ok(())
+    }
 
+    /// Remove remote tracking branches of `origin` that no longer exist on the remote
+    pub fn remote_prune<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let result = self.output_with_retry(dir, &["remote", "prune", "origin"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to prune remote branches:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
         Ok(())
     }
 
-    pub fn pull<P: AsRef<Path>>(dir: P, force: bool) -> Result<(), Error> {
+    pub fn switch<P: AsRef<Path>>(&self, dir: P, branch: impl AsRef<str>) -> Result<(), Error> {
+        let result = self.output(dir, ["switch", branch.as_ref()])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to switch to branch `{}`:\n{}",
+                branch.as_ref(),
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Detach `HEAD` from its branch so the branch can be checked out by a worktree
+    pub fn detach<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let result = self.output(dir, ["switch", "--detach"])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to detach HEAD:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check out `repo` into `path` as a new worktree with a detached `HEAD`
+    ///
+    /// Checks out `commitish` when given, otherwise the `HEAD` of `repo`. Stale worktrees whose
+    /// directories were removed are pruned first so their paths can be reused.
+    pub fn worktree_add(
+        &self,
+        repo: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+        commitish: Option<&str>,
+    ) -> Result<(), Error> {
+        let repo = repo.as_ref();
+        self.output(repo, ["worktree", "prune"])?;
+
+        let path = path.as_ref().to_string_lossy();
+        let mut args = vec!["worktree", "add", "--detach", path.as_ref()];
+        args.extend(commitish);
+
+        let result = self.output(repo, args)?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to add worktree {path}:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn pull<P: AsRef<Path>>(&self, dir: P, force: bool) -> Result<(), Error> {
         let mut args = vec!["pull"];
         if force {
             args.push("--force");
         }
 
-        std::process::Command::new("git")
-            .args(args)
-            .current_dir(dir)
-            .output()?;
+        let result = self.output_with_retry(dir, &args)?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to pull:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
 
         Ok(())
     }
 
     pub fn reset<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
         dir: P,
         ty: ResetType,
         target: Option<S>,
     ) -> Result<(), Error> {
-        let mut args = vec!["pull", ty.as_ref()];
+        let mut args = vec!["reset", ty.as_ref()];
         if let Some(target) = target.as_ref() {
             args.push(target.as_ref());
         }
 
-        std::process::Command::new("git")
-            .args(args)
-            .current_dir(dir)
-            .output()?;
+        let result = self.output(dir, args)?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to reset:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
 
         Ok(())
     }
 
+    /// Stage and commit a single file in the repository at `dir`
+    ///
+    /// Returns `false` without committing when `dir` isn't inside a git repository or the file
+    /// has no changes to commit.
+    pub fn commit_file<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        file: impl AsRef<Path>,
+        message: impl AsRef<str>,
+    ) -> Result<bool, Error> {
+        let dir = dir.as_ref();
+        let file = file.as_ref().as_os_str();
+
+        let result = self.output(dir, ["rev-parse", "--is-inside-work-tree"])?;
+        if !result.status.success() {
+            return Ok(false);
+        }
+
+        let result = self.output(dir, [std::ffi::OsStr::new("add"), "--".as_ref(), file])?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to stage {}:\n{}",
+                file.to_string_lossy(),
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        let result = self.output(
+            dir,
+            [std::ffi::OsStr::new("diff"), "--cached".as_ref(), "--quiet".as_ref(), "--".as_ref(), file],
+        )?;
+        if result.status.success() {
+            return Ok(false);
+        }
+
+        let result = self.output(
+            dir,
+            [
+                std::ffi::OsStr::new("commit"),
+                "-m".as_ref(),
+                message.as_ref().as_ref(),
+                "--".as_ref(),
+                file,
+            ],
+        )?;
+        if !result.status.success() {
+            return Err(Error::custom(format!(
+                "failed to commit {}:\n{}",
+                file.to_string_lossy(),
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+
+        Ok(true)
+    }
+
     pub fn clone(
+        &self,
         dir: impl AsRef<Path>,
         url: impl AsRef<str>,
         name: impl AsRef<str>,
     ) -> Result<(), Error> {
-        let result = std::process::Command::new("git")
-            .args(["clone", url.as_ref(), name.as_ref()])
-            .current_dir(dir)
-            .output()?;
+        self.clone_with_progress(dir, url, name, |_| {})
+    }
+
+    /// Same as [`clone`][Cli::clone] but reports the percentage of objects received as the
+    /// clone progresses.
+    pub fn clone_with_progress(
+        &self,
+        dir: impl AsRef<Path>,
+        url: impl AsRef<str>,
+        name: impl AsRef<str>,
+        progress: impl FnMut(u8),
+    ) -> Result<(), Error> {
+        self.clone_with_depth(dir, url, name, None, progress)
+    }
+
+    /// Same as [`clone_with_progress`][Cli::clone_with_progress] but creates a shallow clone with
+    /// only the last `depth` commits when a depth is given.
+    pub fn clone_with_depth(
+        &self,
+        dir: impl AsRef<Path>,
+        url: impl AsRef<str>,
+        name: impl AsRef<str>,
+        depth: Option<u32>,
+        progress: impl FnMut(u8),
+    ) -> Result<(), Error> {
+        let options = CloneOptions { depth, ..Default::default() };
+        self.clone_with(dir, url, name, &options, progress)
+    }
+
+    /// Same as [`clone_with_progress`][Cli::clone_with_progress] with additional options, e.g. a
+    /// bare or mirror clone
+    pub fn clone_with(
+        &self,
+        dir: impl AsRef<Path>,
+        url: impl AsRef<str>,
+        name: impl AsRef<str>,
+        options: &CloneOptions,
+        mut progress: impl FnMut(u8),
+    ) -> Result<(), Error> {
+        let mut on_stderr = |line: &str| {
+            if let Some(percent) = parse_progress(line) {
+                progress(percent);
+            }
+        };
+
+        let mut args = options.args();
+        args.extend([url.as_ref().to_string(), name.as_ref().to_string()]);
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let result = self.run_with_retry(dir, &args, Some(&mut on_stderr))?;
 
         if result.status.success() {
             Ok(())
@@ -135,3 +834,415 @@ impl Cli {
         }
     }
 }
+
+/// Whether a checked out repository stores files with git-lfs according to its `.gitattributes`
+pub fn uses_lfs(dir: impl AsRef<Path>) -> bool {
+    std::fs::read_to_string(dir.as_ref().join(".gitattributes")).is_ok_and(|attributes| {
+        attributes
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .any(|line| line.split_whitespace().any(|attribute| attribute == "filter=lfs"))
+    })
+}
+
+/// Parse the percentage out of a git `Receiving objects:  45% (450/1000)` progress line
+pub fn parse_progress(line: &str) -> Option<u8> {
+    let (_, rest) = line.split_once("Receiving objects:")?;
+    let (percent, _) = rest.split_once('%')?;
+    percent.trim().parse().ok()
+}
+
+/// Builder for a configured [`Cli`]
+#[derive(Debug, Default, Clone)]
+pub struct CliBuilder {
+    cli: Cli,
+}
+
+impl CliBuilder {
+    pub fn binary(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.cli.binary = binary.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.cli.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.cli.retries = retries;
+        self
+    }
+
+    pub fn token(mut self, token: impl std::fmt::Display) -> Self {
+        self.cli.token = Some(token.to_string());
+        self
+    }
+
+    /// Pass `--quiet` to clone, fetch, pull, switch, and reset so failures only report git's
+    /// errors. Clones no longer report their progress.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.cli.quiet = quiet;
+        self
+    }
+
+    /// Branches tried in order when a repository's default branch can't be determined, see
+    /// [`Cli::default_branch_name`]
+    pub fn fallback_branches<S: Into<String>>(
+        mut self,
+        branches: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.cli.fallback_branches = branches.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn build(self) -> Cli {
+        self.cli
+    }
+}
+
+/// Shorthand for [`Cli::checksum`] using the default [`Cli`]
+pub fn checksum<P: AsRef<Path>>(dir: P, branch: Option<&str>) -> Result<String, Error> {
+    Cli::default().checksum(dir, branch)
+}
+
+/// Shorthand for [`Cli::branch_name`] using the default [`Cli`]
+pub fn branch_name<P: AsRef<Path>>(dir: P) -> Result<String, Error> {
+    Cli::default().branch_name(dir)
+}
+
+/// Shorthand for [`Cli::default_branch_name`] using the default [`Cli`]
+pub fn default_branch_name<P: AsRef<Path>>(dir: P) -> Result<String, Error> {
+    Cli::default().default_branch_name(dir)
+}
+
+/// Shorthand for [`Cli::fetch`] using the default [`Cli`]
+pub fn fetch<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
+    Cli::default().fetch(dir)
+}
+
+/// Shorthand for [`Cli::switch`] using the default [`Cli`]
+pub fn switch<P: AsRef<Path>>(dir: P, branch: impl AsRef<str>) -> Result<(), Error> {
+    Cli::default().switch(dir, branch)
+}
+
+/// Shorthand for [`Cli::pull`] using the default [`Cli`]
+pub fn pull<P: AsRef<Path>>(dir: P, force: bool) -> Result<(), Error> {
+    Cli::default().pull(dir, force)
+}
+
+/// Shorthand for [`Cli::reset`] using the default [`Cli`]
+pub fn reset<P: AsRef<Path>, S: AsRef<str>>(
+    dir: P,
+    ty: ResetType,
+    target: Option<S>,
+) -> Result<(), Error> {
+    Cli::default().reset(dir, ty, target)
+}
+
+/// Shorthand for [`Cli::clone`] using the default [`Cli`]
+pub fn clone(
+    dir: impl AsRef<Path>,
+    url: impl AsRef<str>,
+    name: impl AsRef<str>,
+) -> Result<(), Error> {
+    Cli::default().clone(dir, url, name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{git, init_remote, TempDir};
+
+    #[cfg(unix)]
+    use crate::test_util::FakeGit;
+
+    #[cfg(unix)]
+    #[test]
+    fn custom_binary_is_invoked() {
+        let fake = FakeGit::new("");
+        let cli = fake.cli();
+        assert_eq!(cli.binary(), fake.binary());
+
+        cli.fetch(fake.dir()).unwrap();
+        cli.switch(fake.dir(), "main").unwrap();
+
+        let calls = fake.log();
+        assert_eq!(calls, "fetch -p\nswitch main\n");
+    }
+
+    #[test]
+    fn quiet_args() {
+        let compose = |cli: &Cli, args: &[&str]| {
+            cli.args(args).into_iter().map(|arg| arg.into_string().unwrap()).collect::<Vec<_>>()
+        };
+
+        let quiet = Cli::builder().quiet(true).build();
+        assert!(quiet.quiet());
+        assert_eq!(compose(&quiet, &["pull", "--force"]), ["pull", "--quiet", "--force"]);
+        assert_eq!(compose(&quiet, &["reset", "--hard", "abc"]), ["reset", "--quiet", "--hard", "abc"]);
+        // Commands that don't report anything besides their output are left alone
+        assert_eq!(compose(&quiet, &["rev-parse", "HEAD"]), ["rev-parse", "HEAD"]);
+
+        let cli = Cli::default();
+        assert_eq!(compose(&cli, &["pull", "--force"]), ["pull", "--force"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quiet_commands_are_invoked_with_quiet() {
+        let fake = FakeGit::new("");
+        let cli = Cli::builder().binary(fake.binary()).quiet(true).build();
+
+        cli.fetch(fake.dir()).unwrap();
+        cli.pull(fake.dir(), false).unwrap();
+
+        let calls = fake.log();
+        assert_eq!(calls, "fetch --quiet -p\npull --quiet\n");
+    }
+
+    #[test]
+    fn default_branch_without_origin_head() {
+        let dir = TempDir::new();
+        git(&dir, &["init", "-q", "-b", "trunk"]);
+        git(&dir, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+
+        // No remote at all
+        assert!(Cli::default().default_branch_name(&dir).is_err());
+
+        // Remote tracking branch exists but `origin/HEAD` does not
+        git(&dir, &["update-ref", "refs/remotes/origin/master", "HEAD"]);
+        assert_eq!(Cli::default().default_branch_name(&dir).unwrap(), "master");
+
+        git(&dir, &["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/master"]);
+        assert_eq!(Cli::default().default_branch_name(&dir).unwrap(), "master");
+    }
+
+    #[test]
+    fn default_branch_fallbacks() {
+        let root = TempDir::new();
+        let remote = root.join("remote");
+        let dir = root.join("addon");
+        std::fs::create_dir_all(&remote).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A remote with `trunk` and `master` branches whose HEAD points at a missing branch
+        git(&remote, &["init", "-q", "-b", "trunk"]);
+        git(&remote, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+        git(&remote, &["branch", "master"]);
+        git(&remote, &["symbolic-ref", "HEAD", "refs/heads/missing"]);
+
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["remote", "add", "origin", remote.to_str().unwrap()]);
+
+        let cli = Cli::builder().fallback_branches(["develop", "trunk", "master"]).build();
+        assert_eq!(cli.default_branch_name(&dir).unwrap(), "trunk");
+        let cli = Cli::builder().fallback_branches(["master", "trunk"]).build();
+        assert_eq!(cli.default_branch_name(&dir).unwrap(), "master");
+        let cli = Cli::builder().fallback_branches(["develop"]).build();
+        assert!(cli.default_branch_name(&dir).is_err());
+    }
+
+    #[test]
+    fn clone_progress_lines() {
+        let stderr = "Cloning into 'love2d'...\nremote: Enumerating objects: 1000, done.\n\
+            remote: Counting objects: 100% (1000/1000), done.\n\
+            Receiving objects:   0% (1/1000)\rReceiving objects:  45% (450/1000), 1.2 MiB | 2.4 MiB/s\r\
+            Receiving objects: 100% (1000/1000), 2.5 MiB | 2.4 MiB/s, done.\n\
+            Resolving deltas: 100% (300/300), done.\n";
+
+        let percentages = stderr
+            .split(['\r', '\n'])
+            .filter_map(parse_progress)
+            .collect::<Vec<_>>();
+        assert_eq!(percentages, [0, 45, 100]);
+
+        assert_eq!(parse_progress("Receiving objects: abc%"), None);
+        assert_eq!(parse_progress("Resolving deltas:  50% (1/2)"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clone_reports_progress() {
+        let fake = FakeGit::new(r#"printf 'Receiving objects:  10%% (1/10)\rReceiving objects: 100%% (10/10), done.\n' >&2"#);
+
+        let mut percentages = Vec::new();
+        fake.cli()
+            .clone_with_progress(fake.dir(), "https://github.com/LuaCATS/love2d", "love2d", |p| {
+                percentages.push(p)
+            })
+            .unwrap();
+        assert_eq!(percentages, [10, 100]);
+    }
+
+    #[test]
+    fn commit_single_file() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join(".luarc.json"), "{}").unwrap();
+
+        let cli = Cli::default();
+        // Not a git repository
+        assert!(!cli.commit_file(&dir, ".luarc.json", "llam: add love2d").unwrap());
+
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["config", "user.name", "llam"]);
+        git(&dir, &["config", "user.email", "llam@example.com"]);
+        std::fs::write(dir.join("other.txt"), "untouched").unwrap();
+
+        assert!(cli.commit_file(&dir, ".luarc.json", "llam: add love2d").unwrap());
+        let output = Command::new("git")
+            .args(["log", "-1", "--pretty=%s", "--name-only"])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "llam: add love2d\n\n.luarc.json"
+        );
+
+        // Nothing changed so nothing is committed
+        assert!(!cli.commit_file(&dir, ".luarc.json", "llam: add love2d").unwrap());
+    }
+
+    #[test]
+    fn builder_options() {
+        let cli = Cli::builder()
+            .binary("/opt/git/bin/git")
+            .timeout(Duration::from_secs(5))
+            .retries(2)
+            .token("secret")
+            .build();
+
+        assert_eq!(cli.binary(), Path::new("/opt/git/bin/git"));
+        assert_eq!(cli.timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(cli.retries(), 2);
+        assert_eq!(cli.token(), Some("secret"));
+
+        let command = cli.command();
+        assert_eq!(command.get_program(), "/opt/git/bin/git");
+        let header = command
+            .get_envs()
+            .find(|(key, _)| *key == "GIT_CONFIG_VALUE_0")
+            .and_then(|(_, value)| value);
+        assert_eq!(header, Some("Authorization: Bearer secret".as_ref()));
+
+        assert_eq!(Cli::default().command().get_envs().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn network_commands_retry() {
+        let fake = FakeGit::new("exit 1");
+        let cli = Cli::builder().binary(fake.binary()).retries(2).build();
+
+        assert!(cli.clone(fake.dir(), "https://github.com/LuaCATS/love2d.git", "love2d").is_err());
+        // Local commands are not retried
+        let _ = cli.branch_name(fake.dir());
+
+        assert_eq!(fake.calls_to(&["clone"]).len(), 3);
+        assert_eq!(fake.calls_to(&["rev-parse"]).len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_timeout() {
+        let fake = FakeGit::new("sleep 5");
+        let cli = Cli::builder()
+            .binary(fake.binary())
+            .timeout(Duration::from_millis(100))
+            .build();
+
+        let start = Instant::now();
+        assert!(cli.fetch(fake.dir()).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn fetch_arguments() {
+        let args = |prune, tags, depth| FetchOptions { prune, tags, depth, url: None }.args();
+        assert_eq!(FetchOptions::default().args(), ["fetch", "-p"]);
+        assert_eq!(args(false, false, None), ["fetch"]);
+        assert_eq!(args(true, true, None), ["fetch", "-p", "--tags"]);
+        assert_eq!(args(false, true, None), ["fetch", "--tags"]);
+        assert_eq!(args(true, false, Some(1)), ["fetch", "-p", "--depth=1"]);
+        assert_eq!(args(false, true, Some(3)), ["fetch", "--tags", "--depth=3"]);
+
+        let mirror = FetchOptions { url: Some("https://mirror.example.com/love2d".into()), ..Default::default() };
+        assert_eq!(
+            mirror.args(),
+            ["fetch", "-p", "https://mirror.example.com/love2d", "+refs/heads/*:refs/remotes/origin/*"]
+        );
+    }
+
+    #[test]
+    fn lfs_attributes() {
+        let dir = TempDir::new();
+        assert!(!uses_lfs(&dir));
+
+        let attributes = dir.join(".gitattributes");
+        std::fs::write(&attributes, "*.lua text eol=lf\n# *.png filter=lfs diff=lfs\n").unwrap();
+        assert!(!uses_lfs(&dir));
+        std::fs::write(&attributes, "*.lua text eol=lf\n*.png filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+        assert!(uses_lfs(&dir));
+    }
+
+    #[test]
+    fn clone_arguments() {
+        assert_eq!(CloneOptions::default().args(), ["clone", "--progress"]);
+        let args = |bare, mirror| CloneOptions { depth: Some(1), bare, mirror }.args();
+        assert_eq!(args(true, false), ["clone", "--progress", "--depth=1", "--bare"]);
+        assert_eq!(args(false, true), ["clone", "--progress", "--depth=1", "--mirror"]);
+        assert_eq!(args(true, true), ["clone", "--progress", "--depth=1", "--mirror"]);
+    }
+
+    #[test]
+    fn remote_checksums() {
+        let root = TempDir::new();
+        let work = root.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+        git(&work, &["tag", "-a", "v1.0", "-m", "release"]);
+        git(&work, &["tag", "light"]);
+        let initial = Cli::default().checksum(&work, None).unwrap();
+        git(&work, &["switch", "-q", "-c", "feature"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "feature"]);
+        let feature = Cli::default().checksum(&work, None).unwrap();
+        git(&work, &["switch", "-q", "main"]);
+        git(&root, &["clone", "-q", "--bare", "work", "remote.git"]);
+        let url = format!("file://{}", root.join("remote.git").display());
+
+        let cli = Cli::default();
+        assert_eq!(cli.remote_checksum(&url, None).unwrap(), initial);
+        assert_eq!(cli.remote_checksum(&url, Some("main")).unwrap(), initial);
+        assert_eq!(cli.remote_checksum(&url, Some("feature")).unwrap(), feature);
+        // Annotated tags are peeled to their commit
+        assert_eq!(cli.remote_checksum(&url, Some("v1.0")).unwrap(), initial);
+        assert_eq!(cli.remote_checksum(&url, Some("light")).unwrap(), initial);
+        let err = cli.remote_checksum(&url, Some("missing")).unwrap_err();
+        assert!(err.to_string().contains("`missing` does not exist"), "{err}");
+
+        assert_eq!(
+            parse_ls_remote("abc\trefs/heads/main\n\nwarning: something\ndef\trefs/tags/v1^{}\n"),
+            [("abc", "refs/heads/main"), ("def", "refs/tags/v1^{}")]
+        );
+    }
+
+    #[test]
+    fn bare_clone_has_no_working_tree() {
+        let root = TempDir::new();
+        let remote = root.join("remote");
+        let url = init_remote(&remote);
+
+        for (name, options) in [
+            ("bare", CloneOptions { bare: true, ..Default::default() }),
+            ("mirror", CloneOptions { mirror: true, ..Default::default() }),
+        ] {
+            Cli::default().clone_with(&root, &url, name, &options, |_| {}).unwrap();
+            let clone = root.join(name);
+            assert!(!clone.join("config.json").exists());
+            assert!(!clone.join(".git").exists());
+            assert!(clone.join("HEAD").exists());
+        }
+    }
+}
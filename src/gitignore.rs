@@ -0,0 +1,80 @@
+//! Helpers for keeping addon clones out of the project's repository.
+
+use std::path::Path;
+
+use crate::Error;
+
+pub static GITIGNORE: &str = ".gitignore";
+
+/// Whether a `.gitignore` line ignores the directory `dir` at the root of the repository
+///
+/// `dir`, `dir/`, `/dir`, and `/dir/` are all considered a match.
+fn matches(line: &str, dir: &str) -> bool {
+    let line = line.trim();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let line = line.strip_suffix('/').unwrap_or(line);
+    line == dir.trim_matches('/')
+}
+
+/// Add `dir/` to the `.gitignore` in `project`, creating the file if it doesn't exist
+///
+/// Returns `false` if the directory was already ignored.
+pub fn ensure_ignored(project: impl AsRef<Path>, dir: &str) -> Result<bool, Error> {
+    let path = project.as_ref().join(GITIGNORE);
+    let mut contents = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+
+    if contents.lines().any(|line| matches(line, dir)) {
+        return Ok(false);
+    }
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("{}/\n", dir.trim_matches('/')));
+
+    std::fs::write(&path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn creates_gitignore() {
+        let dir = TempDir::new();
+
+        assert!(ensure_ignored(&dir, ".addons").unwrap());
+        assert_eq!(std::fs::read_to_string(dir.join(GITIGNORE)).unwrap(), ".addons/\n");
+
+        assert!(!ensure_ignored(&dir, ".addons").unwrap());
+        assert_eq!(std::fs::read_to_string(dir.join(GITIGNORE)).unwrap(), ".addons/\n");
+    }
+
+    #[test]
+    fn appends_to_existing_gitignore() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join(GITIGNORE), "target/\n*.log").unwrap();
+
+        assert!(ensure_ignored(&dir, ".addons").unwrap());
+        assert_eq!(
+            std::fs::read_to_string(dir.join(GITIGNORE)).unwrap(),
+            "target/\n*.log\n.addons/\n"
+        );
+        assert!(!ensure_ignored(&dir, ".addons").unwrap());
+    }
+
+    #[test]
+    fn existing_entry_forms() {
+        for entry in [".addons", ".addons/", "/.addons", "/.addons/", "  .addons  "] {
+            assert!(matches(entry, ".addons"), "{entry}");
+        }
+        assert!(!matches(".addons/love2d", ".addons"));
+        assert!(!matches("# .addons", ".addons"));
+    }
+}
@@ -1,21 +1,40 @@
 mod error;
 mod manager;
 
+pub mod addon_config;
+pub mod archive;
+pub mod cache;
+pub mod disk;
 pub mod lua_rc;
 
 // TODO: Don't expose this
 pub mod cli;
 
 pub mod logging;
+pub mod luals;
 pub mod git;
+pub mod gitignore;
+pub mod output;
+pub mod registry;
+pub mod report;
+pub mod shared;
+pub mod timings;
 
-use std::{borrow::Cow, str::FromStr};
+#[cfg(test)]
+mod test_util;
+
+use std::{borrow::Cow, collections::BTreeSet, str::FromStr};
 
 pub use error::Error;
-pub use manager::Manager;
+pub use manager::{temp_clone_root, AddOptions, CleanPlan, Manager, Outdated, UpdateCheck};
+pub use report::{OperationReport, Outcome};
 
 use reqwest::Url;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Serialize,
+};
 
 static ADDONS_DIR: &str = ".addons";
 
@@ -26,25 +45,45 @@ static LUA_LS: &str = "LuaLS";
 #[allow(unused)]
 static LLS_ADDONS: &str = "LLS-Addons";
 
+/// Url schemes that are accepted as an addon source
+static SCHEMES: [&str; 5] = ["https", "http", "ssh", "git", "file"];
+
+/// File extensions of the release archives that are downloaded instead of cloned
+static ARCHIVE_EXTENSIONS: [&str; 3] = [".tar.gz", ".tgz", ".tar"];
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum Target {
     #[default]
     LuaCats,
     Github,
+    /// Any other git remote
+    Git,
+    /// A tarball, such as a release asset, that is downloaded and extracted
+    Archive,
 }
 
 impl FromStr for Target {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.starts_with("https://") {
-            // TODO: Convert the error
-            let url = Url::parse(s).unwrap();
+        if s.contains("://") {
+            let url = Url::parse(s)
+                .map_err(|err| Error::custom(format!("invalid addon source `{s}`: {err}")))?;
+
+            if !SCHEMES.contains(&url.scheme()) {
+                return Err(Error::custom(format!(
+                    "unsupported addon source scheme `{}`, expected one of: {}",
+                    url.scheme(),
+                    SCHEMES.join(", ")
+                )));
+            }
+
+            let archive = ARCHIVE_EXTENSIONS.iter().any(|ext| url.path().ends_with(ext));
             match url.host_str() {
+                _ if archive && matches!(url.scheme(), "https" | "http" | "file") => Ok(Target::Archive),
                 Some("github.com") => Ok(Target::Github),
-                Some(other) => Err(Error::custom(format!("unsupported addon source: {other}"))),
-                _ => Err(Error::custom(format!("unsupported addon source: {s}"))),
+                _ => Ok(Target::Git),
             }
         } else {
             Ok(Target::LuaCats)
@@ -52,37 +91,328 @@ impl FromStr for Target {
     }
 }
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LuaCats => write!(f, "LuaCATS"),
+            Self::Github => write!(f, "github"),
+            Self::Git => write!(f, "git"),
+            Self::Archive => write!(f, "archive"),
+        }
+    }
+}
+
+/// What an installed addon is checked out to
+///
+/// Stored in `.luarc.json` as `"default"`, `{ "branch": "main" }`, `{ "tag": "v1" }`, or
+/// `{ "commit": "abc1234" }`, and parsed from `default`, `branch:main`, `tag:v1`, or
+/// `commit:abc1234`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AddonTarget {
+    #[default]
+    DefaultBranch,
+    Branch(String),
+    Tag(String),
+    Commit(String),
+}
+
+impl AddonTarget {
+    /// Whether the value looks like an abbreviated or full commit hash
+    pub fn is_commit(value: &str) -> bool {
+        (7..=40).contains(&value.len()) && value.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Tag or commit for a revision that doesn't say which it is, a commit when it looks like a
+    /// commit hash
+    pub fn revision(revision: impl Into<String>) -> Self {
+        let revision = revision.into();
+        if Self::is_commit(&revision) {
+            Self::Commit(revision)
+        } else {
+            Self::Tag(revision)
+        }
+    }
+
+    /// The form the target is parsed from, e.g. `branch:main`
+    pub fn reference(&self) -> String {
+        match self {
+            Self::DefaultBranch => "default".to_string(),
+            Self::Branch(branch) => format!("branch:{branch}"),
+            Self::Tag(tag) => format!("tag:{tag}"),
+            Self::Commit(commit) => format!("commit:{commit}"),
+        }
+    }
+
+    pub fn is_default_branch(&self) -> bool {
+        *self == Self::DefaultBranch
+    }
+
+    /// Target of a `branch`, `tag`, or `commit` kind, `None` for an unknown kind or invalid value
+    fn from_parts(kind: &str, value: &str) -> Option<Self> {
+        match kind {
+            "branch" if !value.is_empty() => Some(Self::Branch(value.to_string())),
+            "tag" if !value.is_empty() => Some(Self::Tag(value.to_string())),
+            "commit" if Self::is_commit(value) => Some(Self::Commit(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for AddonTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let target = match s.split_once(':') {
+            None if s == "default" => Some(Self::DefaultBranch),
+            Some((kind, value)) => Self::from_parts(kind, value),
+            None => None,
+        };
+        target.ok_or_else(|| {
+            Error::custom(format!(
+                "invalid target `{s}`, expected `default`, `branch:<name>`, `tag:<name>`, or `commit:<hash>`"
+            ))
+        })
+    }
+}
+
+impl std::fmt::Display for AddonTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DefaultBranch => write!(f, "default branch"),
+            Self::Branch(branch) => write!(f, "branch {branch}"),
+            Self::Tag(tag) => write!(f, "tag {tag}"),
+            Self::Commit(commit) => write!(f, "commit {}", &commit[..commit.len().min(7)]),
+        }
+    }
+}
+
+impl Serialize for AddonTarget {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let (kind, value) = match self {
+            Self::DefaultBranch => return serializer.serialize_str("default"),
+            Self::Branch(branch) => ("branch", branch),
+            Self::Tag(tag) => ("tag", tag),
+            Self::Commit(commit) => ("commit", commit),
+        };
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(kind, value)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for AddonTarget {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct AddonTargetVisitor;
+        impl<'de> Visitor<'de> for AddonTargetVisitor {
+            type Value = AddonTarget;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, r#""default", or an object with a single "branch", "tag", or "commit""#)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match v {
+                    "default" => Ok(AddonTarget::DefaultBranch),
+                    other => Err(E::custom(format!("unknown target `{other}`, expected `default`"))),
+                }
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let Some((kind, value)) = map.next_entry::<String, String>()? else {
+                    return Err(serde::de::Error::custom("empty target, expected a branch, tag, or commit"));
+                };
+                if let Some(other) = map.next_key::<String>()? {
+                    return Err(serde::de::Error::custom(format!(
+                        "target has both `{kind}` and `{other}`, expected only one"
+                    )));
+                }
+                AddonTarget::from_parts(&kind, &value)
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid target `{kind}: {value}`")))
+            }
+        }
+
+        deserializer.deserialize_any(AddonTargetVisitor)
+    }
+}
+
+#[inline]
+const fn default_true() -> bool {
+    true
+}
+
+const fn enabled(ctx: &bool) -> bool {
+    *ctx
+}
+
+const fn disabled(ctx: &bool) -> bool {
+    !*ctx
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(from = "StoredAddon")]
 pub struct Addon {
     pub src: String,
+    /// Branch, tag, or commit the addon is checked out to
+    ///
+    /// This is not the same as [`target`][Addon::target], which is where the addon is sourced from.
+    #[serde(skip_serializing_if = "AddonTarget::is_default_branch")]
+    pub checkout: AddonTarget,
+    pub target: Target,
+    /// Name the addon is installed and recorded as instead of the name derived from `src`
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub checksum: Option<String>,
+    pub alias: Option<String>,
+    /// Only clone and fetch this many commits of history
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub branch: Option<String>,
-    pub target: Target,
+    pub depth: Option<u32>,
+    /// Urls of mirrors of the same repository, tried in order when the primary url fails
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// Disabled addons stay installed but are not exposed to luals
+    #[serde(skip_serializing_if = "enabled")]
+    pub enabled: bool,
+    /// Whether the addon was only installed because another addon depends on it
+    #[serde(skip_serializing_if = "disabled")]
+    pub dependency: bool,
+    /// Pinned addons are skipped by `update` unless pinned addons are explicitly allowed
+    #[serde(skip_serializing_if = "disabled")]
+    pub pinned: bool,
+    /// Names of the installed addons that depend on this addon
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub required_by: BTreeSet<String>,
+    /// Entries this addon added to `workspace.ignoreDir`, removed again with the addon
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignore_dirs: Vec<String>,
+}
+
+/// An [`Addon`] as it is read from the config
+///
+/// Configs written before the checkout was stored have a `branch` and `checksum` instead, a
+/// checksum that looks like a commit hash is read as a commit and any other as a tag.
+#[derive(Deserialize)]
+struct StoredAddon {
+    src: String,
+    checkout: Option<AddonTarget>,
+    branch: Option<String>,
+    checksum: Option<String>,
+    target: Target,
+    #[serde(default)]
+    alias: Option<String>,
+    #[serde(default)]
+    depth: Option<u32>,
+    #[serde(default)]
+    mirrors: Vec<String>,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    dependency: bool,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    required_by: BTreeSet<String>,
+    #[serde(default)]
+    ignore_dirs: Vec<String>,
+}
+
+impl From<StoredAddon> for Addon {
+    fn from(stored: StoredAddon) -> Self {
+        let checkout = match (stored.checkout, stored.checksum, stored.branch) {
+            (Some(checkout), _, _) => checkout,
+            (None, Some(checksum), _) => AddonTarget::revision(checksum),
+            (None, None, Some(branch)) => AddonTarget::Branch(branch),
+            (None, None, None) => AddonTarget::DefaultBranch,
+        };
+
+        Self {
+            src: stored.src,
+            checkout,
+            target: stored.target,
+            alias: stored.alias,
+            depth: stored.depth,
+            mirrors: stored.mirrors,
+            enabled: stored.enabled,
+            dependency: stored.dependency,
+            pinned: stored.pinned,
+            required_by: stored.required_by,
+            ignore_dirs: stored.ignore_dirs,
+        }
+    }
+}
+
+impl Default for Addon {
+    fn default() -> Self {
+        Self {
+            src: String::new(),
+            checkout: AddonTarget::DefaultBranch,
+            target: Target::default(),
+            alias: None,
+            depth: None,
+            mirrors: Vec::new(),
+            enabled: true,
+            dependency: false,
+            pinned: false,
+            required_by: BTreeSet::new(),
+            ignore_dirs: Vec::new(),
+        }
+    }
 }
 
 impl Addon {
-    pub fn cats(name: String, checksum: Option<String>, branch: Option<String>) -> Self {
+    pub fn cats(name: String, checkout: AddonTarget) -> Self {
         Self {
             src: name,
-            checksum,
-            branch,
+            checkout,
             target: Target::LuaCats,
+            ..Default::default()
         }
     }
 
+    /// Branch the addon follows, `None` when it follows the default branch or is at a tag or commit
+    pub fn branch(&self) -> Option<&str> {
+        match &self.checkout {
+            AddonTarget::Branch(branch) => Some(branch),
+            _ => None,
+        }
+    }
+
+    /// Tag or commit the addon is checked out to
+    pub fn checksum(&self) -> Option<&str> {
+        match &self.checkout {
+            AddonTarget::Tag(revision) | AddonTarget::Commit(revision) => Some(revision),
+            _ => None,
+        }
+    }
+
+    /// Build an addon from its parts, see [`AddonBuilder`]
+    pub fn builder() -> AddonBuilder {
+        AddonBuilder::default()
+    }
+
+    /// Addon sourced from a LuaCATS name or git url, without a checksum or branch
+    pub fn from_url(url: impl AsRef<str>) -> Result<Self, Error> {
+        Self::builder().url(url.as_ref()).build()
+    }
+
     pub fn name(&self) -> Cow<'static, str> {
+        if let Some(alias) = self.alias.as_ref() {
+            return alias.clone().into();
+        }
+
         match self.target {
             Target::LuaCats => self.src.clone().into(),
-            Target::Github => {
-                let url = Url::parse(self.src.as_str()).unwrap();
-                url.path_segments()
-                    .unwrap()
-                    .nth(1)
-                    .unwrap()
-                    .to_string()
-                    .into()
+            Target::Github | Target::Git | Target::Archive => {
+                url_name(&self.src, self.target).unwrap_or_default().into()
             }
         }
     }
@@ -90,8 +420,35 @@ impl Addon {
     pub fn clone_url(&self) -> String {
         match self.target {
             Target::LuaCats => format!("https://github.com/LuaCATS/{}.git", self.src),
-            Target::Github => self.src.to_string(),
+            Target::Github | Target::Git | Target::Archive => self.src.to_string(),
+        }
+    }
+
+    /// Web page comparing two commits of the addon, only known for repositories on GitHub
+    pub fn compare_url(&self, old: &str, new: &str) -> Option<String> {
+        let url = self.clone_url();
+        let url = match url.strip_prefix("git@github.com:") {
+            Some(path) => format!("https://github.com/{path}"),
+            None => url,
+        };
+
+        let url = Url::parse(&url).ok()?;
+        if url.host_str() != Some("github.com") {
+            return None;
         }
+        let repo = url.path().trim_matches('/').trim_end_matches(".git");
+        (repo.split('/').count() == 2).then(|| format!("https://github.com/{repo}/compare/{old}...{new}"))
+    }
+
+    /// Whether both addons clone the same repository, ignoring a `.git` suffix and case
+    pub fn same_repository(&self, other: &Self) -> bool {
+        let normalize = |url: String| url.trim_end_matches('/').trim_end_matches(".git").to_lowercase();
+        normalize(self.clone_url()) == normalize(other.clone_url())
+    }
+
+    /// The clone url followed by the mirrors, in the order they are tried
+    pub fn clone_urls(&self) -> Vec<String> {
+        std::iter::once(self.clone_url()).chain(self.mirrors.iter().cloned()).collect()
     }
 
     pub fn merge(&mut self, other: &Self) -> bool {
@@ -100,20 +457,144 @@ impl Addon {
         self.src = other.src.clone();
         self.target = other.target;
 
-        if let Some(branch) = other.branch.as_ref() {
-            self.branch = Some(branch.to_string());
+        if !other.checkout.is_default_branch() {
+            self.checkout = other.checkout.clone();
             diff = true;
         }
 
-        if let Some(checksum) = other.checksum.as_ref() {
-            self.checksum = Some(checksum.to_string());
-            diff = true;
+        if let Some(alias) = other.alias.as_ref() {
+            self.alias = Some(alias.to_string());
+        }
+
+        if other.depth.is_some() {
+            diff |= self.depth != other.depth;
+            self.depth = other.depth;
+        }
+
+        if !other.mirrors.is_empty() {
+            diff |= self.mirrors != other.mirrors;
+            self.mirrors = other.mirrors.clone();
         }
 
         diff
     }
 }
 
+/// Builder for an [`Addon`], used when driving a [`Manager`] from code
+///
+/// ```
+/// use llam::Addon;
+///
+/// let addon = Addon::builder()
+///     .url("https://github.com/someone/love2d")
+///     .tag("v11.5")
+///     .name("love")
+///     .build()
+///     .unwrap();
+/// assert_eq!(addon.name(), "love");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AddonBuilder {
+    url: Option<String>,
+    checkout: AddonTarget,
+    alias: Option<String>,
+    depth: Option<u32>,
+    mirrors: Vec<String>,
+}
+
+impl AddonBuilder {
+    /// LuaCATS name, git url, or scp-like `user@host:path` the addon is cloned from
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Branch to follow
+    pub fn branch(mut self, branch: impl Into<String>) -> Self {
+        self.checkout = AddonTarget::Branch(branch.into());
+        self
+    }
+
+    /// Tag to check out
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.checkout = AddonTarget::Tag(tag.into());
+        self
+    }
+
+    /// Commit to check out
+    pub fn checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.checkout = AddonTarget::Commit(checksum.into());
+        self
+    }
+
+    /// Name to install the addon as instead of the name derived from the url
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.alias = Some(name.into());
+        self
+    }
+
+    /// Keep the addon a shallow clone with this many commits of history
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Url of a mirror of the same repository, used when the primary url fails
+    pub fn mirror(mut self, url: impl Into<String>) -> Self {
+        self.mirrors.push(url.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Addon, Error> {
+        let url = self
+            .url
+            .ok_or_else(|| Error::custom("an addon requires a url or LuaCATS name"))?;
+        let src = normalize_source(&url)?;
+        let target = Target::from_str(&src)?;
+
+        if self.alias.is_none() && target != Target::LuaCats && url_name(&src, target).is_none() {
+            return Err(Error::custom(format!(
+                "unable to derive an addon name from `{url}`, set a name"
+            )));
+        }
+        match &self.checkout {
+            AddonTarget::Branch(name) | AddonTarget::Tag(name) if name.is_empty() => {
+                return Err(Error::custom(format!("empty branch or tag for addon `{url}`")));
+            }
+            AddonTarget::Commit(checksum) if !AddonTarget::is_commit(checksum) => {
+                return Err(Error::custom(format!("invalid checksum `{checksum}` for addon `{url}`")));
+            }
+            _ => {}
+        }
+        if self.depth == Some(0) {
+            return Err(Error::custom("addon clone depth must be at least 1"));
+        }
+        if let Some(alias) = self.alias.as_deref() {
+            if !is_valid_name(alias) {
+                return Err(Error::custom(format!("invalid addon name `{alias}`")));
+            }
+        }
+        let mirrors = self
+            .mirrors
+            .iter()
+            .map(|mirror| match normalize_source(mirror)? {
+                url if url.contains("://") => Ok(url),
+                _ => Err(Error::custom(format!("addon mirror `{mirror}` must be a url"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Addon {
+            target,
+            src,
+            checkout: self.checkout,
+            alias: self.alias,
+            depth: self.depth,
+            mirrors,
+            ..Default::default()
+        })
+    }
+}
+
 impl From<String> for Addon {
     fn from(value: String) -> Self {
         value.as_str().into()
@@ -122,29 +603,114 @@ impl From<String> for Addon {
 
 impl From<&str> for Addon {
     fn from(s: &str) -> Self {
-        let mut target = s;
-        let mut checksum = None;
+        Self::from_str(s).unwrap()
+    }
+}
 
-        if target.contains('@') {
-            let (f, s) = target.split_once('@').unwrap();
-            target = f;
-            checksum = Some(s.to_string());
-        }
+impl FromStr for Addon {
+    type Err = Error;
 
-        Self {
-            target: Target::from_str(target).unwrap(),
-            src: target.to_string(),
-            checksum,
-            branch: None,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `@branch:`, `@tag:`, and `@commit:` name the target, the first one is used since a
+        // branch or tag may contain an `@`. Otherwise the last `@` marks a tag or commit unless
+        // it is the user in a url or scp-like source.
+        let explicit = ["@branch:", "@tag:", "@commit:"].iter().filter_map(|marker| s.find(marker)).min();
+        let (source, checkout) = match (explicit, s.rsplit_once('@')) {
+            (Some(at), _) if at > 0 => (&s[..at], AddonTarget::from_str(&s[at + 1..])?),
+            (None, Some((source, revision))) if !source.is_empty() && !revision.contains([':', '/']) => {
+                if revision.is_empty() {
+                    return Err(Error::custom(format!("missing checksum after `@` in `{s}`")));
+                }
+                (source, AddonTarget::revision(revision))
+            }
+            _ => (s, AddonTarget::DefaultBranch),
+        };
+
+        let src = normalize_source(source)?;
+        let target = Target::from_str(&src)?;
+        if target != Target::LuaCats && url_name(&src, target).is_none() {
+            return Err(Error::custom(format!("unable to derive an addon name from `{source}`")));
         }
+
+        Ok(Self {
+            target,
+            src,
+            checkout,
+            ..Default::default()
+        })
     }
 }
 
+/// Validate an addon source and convert the scp-like `user@host:path` form into an
+/// `ssh://user@host/path` url so it is stored in a single canonical form.
+fn normalize_source(source: &str) -> Result<String, Error> {
+    if source.contains("://") {
+        return Ok(source.to_string());
+    }
+
+    if let Some((remote, path)) = source.split_once(':') {
+        return match remote.split_once('@') {
+            Some((user, host))
+                if !user.is_empty() && !host.is_empty() && !remote.contains('/') && !path.is_empty() =>
+            {
+                Ok(format!("ssh://{user}@{host}/{}", path.trim_start_matches('/')))
+            }
+            _ => Err(Error::custom(format!(
+                "invalid addon source `{source}`, expected a LuaCATS name, a url, or `user@host:path`"
+            ))),
+        };
+    }
+
+    if !is_valid_name(source) {
+        return Err(Error::custom(format!("invalid LuaCATS addon name `{source}`")));
+    }
+
+    Ok(source.to_string())
+}
+
+/// Name derived from an addon url, ignoring empty segments from trailing slashes and a `.git`
+/// suffix
+///
+/// Github urls are named after the repository, `github.com/<owner>/<repo>`, any other url after
+/// its last path segment. Archives are named after the file without its extension. Returns
+/// `None` when the url doesn't have a usable name.
+fn url_name(src: &str, target: Target) -> Option<String> {
+    let url = Url::parse(src).ok()?;
+    let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+    let segment = match target {
+        Target::Github => segments.nth(1)?,
+        _ => segments.next_back()?,
+    };
+
+    let name = match target {
+        Target::Archive => ARCHIVE_EXTENSIONS.iter().find_map(|ext| segment.strip_suffix(ext))?,
+        _ => segment.strip_suffix(".git").unwrap_or(segment),
+    };
+    is_valid_name(name).then(|| name.to_string())
+}
+
+/// Whether the value can be used as an addon's directory name
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !matches!(name, "." | "..")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 impl std::fmt::Display for Addon {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.src)?;
-        if let Some(checksum) = self.checksum.as_deref() {
-            write!(f, "@{checksum}")?;
+        match &self.checkout {
+            AddonTarget::DefaultBranch => {}
+            // A plain suffix is read back as a tag or commit depending on whether it looks like
+            // a commit hash
+            AddonTarget::Tag(revision) | AddonTarget::Commit(revision)
+                if !revision.contains([':', '/', '@']) && AddonTarget::revision(revision.as_str()) == self.checkout =>
+            {
+                write!(f, "@{revision}")?
+            }
+            target => write!(f, "@{}", target.reference())?,
         }
 
         Ok(())
@@ -180,7 +746,266 @@ mod test {
     #[test]
     #[should_panic]
     fn parse_fail() {
-        let _ = Addon::from("https://example.com/LuaCATS/love2d@1234");
+        let _ = Addon::from("ftp://example.com/LuaCATS/love2d@1234");
+    }
+
+    #[test]
+    fn checkout_target() {
+        let addon = Addon::from("love2d");
+        assert_eq!(addon.checkout, AddonTarget::DefaultBranch);
+        assert_eq!(addon.checkout.to_string(), "default branch");
+
+        let addon = Addon {
+            checkout: AddonTarget::Branch("develop".to_string()),
+            ..Addon::from("love2d")
+        };
+        assert_eq!(addon.checkout, AddonTarget::Branch("develop".to_string()));
+        assert_eq!(addon.checkout.to_string(), "branch develop");
+
+        let addon = Addon::from("love2d@v11.5");
+        assert_eq!(addon.checkout, AddonTarget::Tag("v11.5".to_string()));
+        assert_eq!(addon.checkout.to_string(), "tag v11.5");
+
+        let addon = Addon::from("love2d@4f2e8d1c9b7a6e5d4c3b2a1f0e9d8c7b6a5f4e3d");
+        assert_eq!(
+            addon.checkout,
+            AddonTarget::Commit("4f2e8d1c9b7a6e5d4c3b2a1f0e9d8c7b6a5f4e3d".to_string())
+        );
+        assert_eq!(addon.checkout.to_string(), "commit 4f2e8d1");
+    }
+
+    /// Parse `reference` and `json` into `target`, and write `target` back into both
+    fn assert_round_trips(target: AddonTarget, reference: &str, json: serde_json::Value) {
+        let parsed = AddonTarget::from_str(reference).unwrap();
+        let deserialized = serde_json::from_value::<AddonTarget>(json.clone()).unwrap();
+        assert_eq!(parsed, target, "{reference}");
+        assert_eq!(deserialized, parsed, "{json}");
+
+        assert_eq!(target.reference(), reference);
+        assert_eq!(serde_json::to_value(&target).unwrap(), json);
+
+        // Stored on an addon
+        let addon = Addon { checkout: target.clone(), ..Addon::from("love2d") };
+        let stored = serde_json::to_string(&addon).unwrap();
+        assert_eq!(serde_json::from_str::<Addon>(&stored).unwrap().checkout, target, "{stored}");
+    }
+
+    #[test]
+    fn default_branch_round_trips() {
+        assert_round_trips(AddonTarget::DefaultBranch, "default", serde_json::json!("default"));
+        // The default branch isn't written
+        assert!(!serde_json::to_string(&Addon::from("love2d")).unwrap().contains("checkout"));
+    }
+
+    #[test]
+    fn branch_round_trips() {
+        assert_round_trips(
+            AddonTarget::Branch("feature/lsp".into()),
+            "branch:feature/lsp",
+            serde_json::json!({ "branch": "feature/lsp" }),
+        );
+    }
+
+    #[test]
+    fn tag_round_trips() {
+        assert_round_trips(AddonTarget::Tag("v1.0".into()), "tag:v1.0", serde_json::json!({ "tag": "v1.0" }));
+        // A tag that looks like a commit hash stays a tag
+        assert_round_trips(AddonTarget::Tag("deadbeef".into()), "tag:deadbeef", serde_json::json!({ "tag": "deadbeef" }));
+    }
+
+    #[test]
+    fn commit_round_trips() {
+        assert_round_trips(
+            AddonTarget::Commit("abc1234".into()),
+            "commit:abc1234",
+            serde_json::json!({ "commit": "abc1234" }),
+        );
+    }
+
+    #[test]
+    fn invalid_targets() {
+        for invalid in ["", "main", "branch:", "tag:", "commit:main", "remote:origin"] {
+            assert!(AddonTarget::from_str(invalid).is_err(), "{invalid}");
+        }
+
+        for invalid in [
+            serde_json::json!("main"),
+            serde_json::json!({}),
+            serde_json::json!({ "branch": "" }),
+            serde_json::json!({ "commit": "main" }),
+            serde_json::json!({ "remote": "origin" }),
+            serde_json::json!({ "branch": "main", "tag": "v1.0" }),
+        ] {
+            assert!(serde_json::from_value::<AddonTarget>(invalid.clone()).is_err(), "{invalid}");
+        }
+
+        // Too short to be a commit hash
+        let addon = Addon::from("love2d@1234");
+        assert_eq!(addon.checkout, AddonTarget::Tag("1234".to_string()));
+    }
+
+    #[test]
+    fn legacy_branch_and_checksum() {
+        let read = |json: serde_json::Value| serde_json::from_value::<Addon>(json).unwrap().checkout;
+
+        assert_eq!(
+            read(serde_json::json!({ "src": "love2d", "target": "lua_cats", "branch": "main" })),
+            AddonTarget::Branch("main".to_string())
+        );
+        assert_eq!(
+            read(serde_json::json!({ "src": "love2d", "target": "lua_cats", "checksum": "v11.5" })),
+            AddonTarget::Tag("v11.5".to_string())
+        );
+        // The checksum was checked out after switching to the branch
+        assert_eq!(
+            read(serde_json::json!({ "src": "love2d", "target": "lua_cats", "branch": "main", "checksum": "1234567" })),
+            AddonTarget::Commit("1234567".to_string())
+        );
+        assert_eq!(
+            read(serde_json::json!({
+                "src": "love2d",
+                "target": "lua_cats",
+                "checksum": "1234567",
+                "checkout": { "tag": "v11.5" }
+            })),
+            AddonTarget::Tag("v11.5".to_string())
+        );
+
+        // Written back with the checkout instead
+        let addon = serde_json::from_value::<Addon>(serde_json::json!({
+            "src": "love2d",
+            "target": "lua_cats",
+            "branch": "main"
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&addon).unwrap(),
+            serde_json::json!({ "src": "love2d", "target": "lua_cats", "checkout": { "branch": "main" } })
+        );
+    }
+
+    #[test]
+    fn parse_explicit_target() {
+        for (source, target) in [
+            ("love2d@tag:deadbeef", AddonTarget::Tag("deadbeef".into())),
+            ("love2d@tag:1234567", AddonTarget::Tag("1234567".into())),
+            ("love2d@tag:release/1.0", AddonTarget::Tag("release/1.0".into())),
+            ("love2d@branch:feature@2", AddonTarget::Branch("feature@2".into())),
+            ("love2d@commit:1234567", AddonTarget::Commit("1234567".into())),
+            ("git@example.com:tools/luassert@tag:v1", AddonTarget::Tag("v1".into())),
+        ] {
+            let addon = Addon::from_str(source).unwrap();
+            assert_eq!(addon.checkout, target, "{source}");
+            assert_eq!(Addon::from_str(&addon.to_string()).unwrap(), addon, "{source}");
+        }
+        assert_eq!(Addon::from("git@example.com:tools/luassert@tag:v1").src, "ssh://git@example.com/tools/luassert");
+
+        assert_eq!(Addon::from("love2d@deadbeef").to_string(), "love2d@deadbeef");
+        assert_eq!(Addon::from("love2d@v11.5").to_string(), "love2d@v11.5");
+        assert_eq!(Addon::from("love2d@tag:deadbeef").to_string(), "love2d@tag:deadbeef");
+        assert_eq!(Addon::from("love2d@branch:main").to_string(), "love2d@branch:main");
+
+        for source in ["love2d@commit:main", "love2d@tag:", "love2d@branch:", "@tag:v1"] {
+            assert!(Addon::from_str(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn parse_schemes() {
+        for source in [
+            "https://github.com/LuaCATS/love2d",
+            "http://github.com/LuaCATS/love2d",
+            "ssh://git@github.com/LuaCATS/love2d",
+            "git://github.com/LuaCATS/love2d",
+        ] {
+            let addon = Addon::from_str(source).unwrap();
+            assert_eq!(addon.target, Target::Github, "{source}");
+            assert_eq!(addon.name(), "love2d", "{source}");
+        }
+
+        let addon = Addon::from_str("https://gitlab.com/someone/lovr-addon.git@abc").unwrap();
+        assert_eq!(addon.target, Target::Git);
+        assert_eq!(addon.name(), "lovr-addon");
+        assert_eq!(addon.checksum(), Some("abc"));
+
+        let addon = Addon::from_str("file:///home/user/addons/busted/").unwrap();
+        assert_eq!(addon.target, Target::Git);
+        assert_eq!(addon.name(), "busted");
+
+        for (source, name) in [
+            ("https://example.com/releases/download/v1.0/penlight.tar.gz", "penlight"),
+            ("http://example.com/luassert-1.9.tgz", "luassert-1.9"),
+            ("file:///tmp/addons/busted.tar", "busted"),
+        ] {
+            let addon = Addon::from_str(source).unwrap();
+            assert_eq!(addon.target, Target::Archive, "{source}");
+            assert_eq!(addon.name(), name, "{source}");
+            assert_eq!(addon.clone_url(), source);
+        }
+        let addon = Addon::from_str("https://example.com/penlight.tar.gz@abc1234").unwrap();
+        assert_eq!(addon.target, Target::Archive);
+        assert_eq!(addon.checksum(), Some("abc1234"));
+        assert_eq!(Addon::from_str("ssh://git@example.com/addon.tar.gz").unwrap().target, Target::Git);
+    }
+
+    #[test]
+    fn names_from_urls() {
+        for (source, name) in [
+            ("https://github.com/owner/repo.git", "repo"),
+            ("https://github.com/owner/repo.git/", "repo"),
+            ("https://github.com/owner/repo/", "repo"),
+            ("https://github.com//owner//repo//", "repo"),
+            ("git@github.com:owner/repo.git", "repo"),
+            ("git@gitlab.com:group/sub/repo.git/", "repo"),
+            ("https://gitlab.com/group/repo.git//", "repo"),
+            ("ssh://git@example.com/repo.git", "repo"),
+        ] {
+            assert_eq!(Addon::from_str(source).unwrap().name(), name, "{source}");
+        }
+
+        for source in [
+            "https://github.com/owner",
+            "https://github.com/owner/.git",
+            "https://example.com/",
+            "https://example.com/.git/",
+            "git@example.com:/",
+        ] {
+            assert!(Addon::from_str(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn parse_rejected() {
+        for source in [
+            "ftp://github.com/LuaCATS/love2d",
+            "https://",
+            "github.com:LuaCATS/love2d",
+            "git@github.com:",
+            "love 2d",
+            "love2d@",
+            "",
+        ] {
+            assert!(Addon::from_str(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn parse_scp_form() {
+        let addon = Addon::from_str("git@github.com:LuaCATS/love2d.git").unwrap();
+        assert_eq!(
+            addon,
+            Addon {
+                src: "ssh://git@github.com/LuaCATS/love2d.git".to_string(),
+                target: Target::Github,
+                ..Default::default()
+            }
+        );
+        assert_eq!(addon.clone_url(), "ssh://git@github.com/LuaCATS/love2d.git");
+
+        let addon = Addon::from_str("git@example.com:tools/luassert@1234").unwrap();
+        assert_eq!(addon.src, "ssh://git@example.com/tools/luassert");
+        assert_eq!(addon.target, Target::Git);
+        assert_eq!(addon.checksum(), Some("1234"));
     }
 
     #[test]
@@ -190,7 +1015,7 @@ mod test {
             source,
             Addon {
                 src: "love2d".to_string(),
-                checksum: Some("1234".to_string()),
+                checkout: AddonTarget::Tag("1234".to_string()),
                 ..Default::default()
             }
         );
@@ -200,10 +1025,108 @@ mod test {
             source,
             Addon {
                 src: "https://github.com/LuaCATS/love2d".to_string(),
-                checksum: Some("1234567678".to_string()),
+                checkout: AddonTarget::Commit("1234567678".to_string()),
                 target: Target::Github,
                 ..Default::default()
             }
         );
     }
+
+    #[test]
+    fn builder() {
+        let addon = Addon::builder()
+            .url("https://github.com/someone/love2d")
+            .branch("develop")
+            .build()
+            .unwrap();
+        assert_eq!(addon.target, Target::Github);
+        assert_eq!(addon.name(), "love2d");
+        assert_eq!(addon.checkout, AddonTarget::Branch("develop".to_string()));
+
+        let addon = Addon::builder().url("love2d").tag("v11.5").name("love").build().unwrap();
+        assert_eq!(addon.name(), "love");
+        assert_eq!(addon.clone_url(), "https://github.com/LuaCATS/love2d.git");
+        assert_eq!(addon.checkout, AddonTarget::Tag("v11.5".to_string()));
+
+        let addon = Addon::builder().url("git@example.com:me/utils.git").checksum("1a2b3c4d").build().unwrap();
+        assert_eq!(addon.src, "ssh://git@example.com/me/utils.git");
+        assert_eq!(addon.checkout, AddonTarget::Commit("1a2b3c4d".to_string()));
+
+        assert_eq!(Addon::from_url("love2d").unwrap(), Addon::from("love2d"));
+    }
+
+    #[test]
+    fn builder_validation() {
+        assert!(Addon::builder().branch("main").build().is_err());
+        assert!(Addon::builder().url("").build().is_err());
+        assert!(Addon::builder().url("ftp://example.com/addon").build().is_err());
+        assert!(Addon::builder().url("love2d").checksum("").build().is_err());
+        assert!(Addon::builder().url("love2d").name("../love").build().is_err());
+        assert!(Addon::from_url("not a name").is_err());
+        assert!(Addon::builder().url("https://example.com/.git").build().is_err());
+        assert!(Addon::builder().url("https://example.com/.git").name("lib").build().is_ok());
+    }
+
+    #[test]
+    fn mirrors() {
+        let addon = Addon::builder()
+            .url("love2d")
+            .mirror("https://mirror.example.com/love2d.git")
+            .mirror("git@backup.example.com:love2d.git")
+            .build()
+            .unwrap();
+        assert_eq!(
+            addon.clone_urls(),
+            [
+                "https://github.com/LuaCATS/love2d.git",
+                "https://mirror.example.com/love2d.git",
+                "ssh://git@backup.example.com/love2d.git",
+            ]
+        );
+
+        let json = serde_json::to_string(&addon).unwrap();
+        assert_eq!(serde_json::from_str::<Addon>(&json).unwrap(), addon);
+        assert!(!serde_json::to_string(&Addon::from("love2d")).unwrap().contains("mirrors"));
+        assert!(Addon::builder().url("love2d").mirror("love2d").build().is_err());
+    }
+
+    #[test]
+    fn compare_urls() {
+        let compare = |url: &str| Addon::from(url).compare_url("abc123", "def456");
+
+        assert_eq!(
+            compare("love2d").as_deref(),
+            Some("https://github.com/LuaCATS/love2d/compare/abc123...def456")
+        );
+        for url in ["https://github.com/someone/love2d", "git@github.com:someone/love2d.git"] {
+            assert_eq!(
+                compare(url).as_deref(),
+                Some("https://github.com/someone/love2d/compare/abc123...def456"),
+                "{url}"
+            );
+        }
+        assert_eq!(compare("https://gitlab.com/someone/love2d.git"), None);
+        assert_eq!(compare("git@example.com:love2d.git"), None);
+    }
+
+    #[test]
+    fn same_repository() {
+        let cats = Addon::from("love2d");
+        assert!(cats.same_repository(&Addon::from("https://github.com/LuaCATS/love2d")));
+        assert!(cats.same_repository(&Addon::from("https://github.com/luacats/love2d.git")));
+        assert!(cats.same_repository(&Addon::builder().url("love2d").name("love").build().unwrap()));
+        assert!(!cats.same_repository(&Addon::from("https://github.com/someone/love2d")));
+    }
+
+    #[test]
+    fn depth_round_trip() {
+        let addon = Addon::builder().url("love2d").depth(1).build().unwrap();
+        let json = serde_json::to_string(&addon).unwrap();
+        assert!(json.contains(r#""depth":1"#), "{json}");
+        assert_eq!(serde_json::from_str::<Addon>(&json).unwrap(), addon);
+
+        let json = serde_json::to_string(&Addon::from("love2d")).unwrap();
+        assert!(!json.contains("depth"), "{json}");
+        assert!(Addon::builder().url("love2d").depth(0).build().is_err());
+    }
 }
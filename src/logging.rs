@@ -1,4 +1,4 @@
-use std::{io::{stderr, stdout, Write}, sync::{atomic::{AtomicBool, Ordering}, mpsc::Sender, Arc}, thread::JoinHandle, time::Duration};
+use std::{io::{stderr, stdin, stdout, BufRead, IsTerminal, Write}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{RecvTimeoutError, Sender}, Arc, Mutex}, thread::JoinHandle, time::{Duration, Instant}};
 
 pub mod colors {
     pub use owo_colors::*;
@@ -12,9 +12,36 @@ pub trait Logger {
     fn error(&mut self, log: impl std::fmt::Display);
     fn success(&mut self, log: impl std::fmt::Display);
     fn warning(&mut self, log: impl std::fmt::Display);
+    /// Ask `question` and read the answer, a line from stdin
+    ///
+    /// Nothing else is rendered while waiting for the answer. Loggers that can't ask anything
+    /// return an error.
+    fn prompt(&mut self, question: impl std::fmt::Display) -> Result<String, crate::Error>;
+    /// Stop any animation and flush pending output
+    ///
+    /// Everything logged before `finish` is fully written when it returns, so output printed
+    /// afterwards, including by other means than the logger, always comes after it.
     fn finish(&mut self);
 }
 
+/// Logger that discards everything
+///
+/// The recommended logger when embedding a [`Manager`][crate::Manager] in another program. It
+/// doesn't spawn a render thread or write to stdout like a [`Spinner`] does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NullLogger;
+
+impl Logger for NullLogger {
+    fn update(&mut self, _: impl std::fmt::Display) {}
+    fn error(&mut self, _: impl std::fmt::Display) {}
+    fn success(&mut self, _: impl std::fmt::Display) {}
+    fn warning(&mut self, _: impl std::fmt::Display) {}
+    fn prompt(&mut self, question: impl std::fmt::Display) -> Result<String, crate::Error> {
+        Err(crate::Error::custom(format!("unable to ask `{question}` without a terminal")))
+    }
+    fn finish(&mut self) {}
+}
+
 pub trait OrLog<L: Logger, O = ()> {
     /// Consume the value and log
     fn log(self, logger: &mut L);
@@ -63,6 +90,12 @@ impl<O, E: std::fmt::Display, L: Logger> OrLog<L, O> for Result<O, E> {
     }
 }
 
+/// Log an error that ends the program and stop the logger, so the error is the last line written
+pub fn fatal<L: Logger>(logger: &mut L, err: impl std::fmt::Display) {
+    logger.error(err);
+    logger.finish();
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Stream {
     Stdout,
@@ -92,6 +125,34 @@ impl Write for Stream {
     }
 }
 
+/// When to use colors in the output
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Use colors when writing to a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Whether output written to `stream` should be colored
+    pub fn enabled(&self, stream: Stream) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                let terminal = match stream {
+                    Stream::Stdout => stdout().is_terminal(),
+                    Stream::Stderr => stderr().is_terminal(),
+                };
+                terminal && !no_color
+            }
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! frames {
     ([ $($frame: expr),* $(,)? ], $interval: expr) => {
@@ -109,32 +170,65 @@ macro_rules! frames {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Frame {
     text: String,
+    /// Colored version of the text
+    styled: Option<String>,
     interval: Duration
 }
 
 impl Frame {
     pub fn new(text: impl std::fmt::Display, interval: Duration) -> Self {
-        Self { text: text.to_string(), interval }
+        Self { text: text.to_string(), styled: None, interval }
     }
 
     pub fn new_with_color<C: colors::Color>(text: impl std::fmt::Display, interval: Duration) -> Self {
-        Self { text: text.to_string().fg::<C>().to_string(), interval }
+        let text = text.to_string();
+        Self { styled: Some(text.fg::<C>().to_string()), text, interval }
+    }
+
+    /// The frame's text without any colors
+    pub fn plain(&self) -> &str {
+        &self.text
     }
 }
 
 impl std::fmt::Display for Frame {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.text)
+        write!(f, "{}", self.styled.as_deref().unwrap_or(&self.text))
     }
 }
 
-#[derive(Debug)]
+/// Message sent to the spinner's render thread
+enum Message {
+    /// Replace the animated status line
+    Status(Option<String>),
+    /// Print a line above the animated status line
+    Line(String),
+    /// Clear the status line and stop rendering until [`Resume`][Message::Resume], the sender is
+    /// notified once the line is cleared
+    Pause(Sender<()>),
+    /// Start rendering again after a [`Pause`][Message::Pause]
+    Resume,
+    /// Stop rendering and exit the thread
+    Stop,
+}
+
+type Target = Arc<Mutex<Box<dyn Write + Send>>>;
+
 pub struct Spinner {
-    channel: Sender<Option<String>>,
+    channel: Sender<Message>,
     handle: Option<JoinHandle<()>>,
     spinning: Arc<AtomicBool>,
+    color: bool,
+
+    target: Target,
+}
 
-    stream: Stream,
+impl std::fmt::Debug for Spinner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Spinner")
+            .field("spinning", &self.is_spinning())
+            .finish_non_exhaustive()
+    }
 }
 
 impl Spinner {
@@ -142,33 +236,131 @@ impl Spinner {
     ///
     /// The spinner creates a thread and start immediatly. However, it will not render until it is
     /// updated with a message to display.
-    pub fn new(mut target: Stream, frames: Vec<Frame>) -> Self {
-        let (s, r) = std::sync::mpsc::channel::<Option<String>>();
+    pub fn new(target: Stream, frames: Vec<Frame>) -> Self {
+        Self::new_with_color(target, frames, ColorMode::Auto)
+    }
+
+    /// Same as [`new`][Spinner::new] but with control over when colors are used
+    pub fn new_with_color(target: Stream, frames: Vec<Frame>, color: ColorMode) -> Self {
+        Self::spawn(target.get(), frames, color.enabled(target))
+    }
+
+    /// Same as [`new`][Spinner::new] but rendering to any writer, e.g. a buffer in tests
+    ///
+    /// The writer is never a terminal, so colors are only used with [`ColorMode::Always`].
+    pub fn new_with_writer(target: Box<dyn Write + Send>, frames: Vec<Frame>, color: ColorMode) -> Self {
+        Self::spawn(target, frames, color == ColorMode::Always)
+    }
+
+    /// Start the render thread writing to `target`
+    ///
+    /// All output goes through the render thread so status updates are coalesced to at most one
+    /// redraw per frame and printed lines never interleave with a partially drawn status line.
+    fn spawn(target: Box<dyn Write + Send>, frames: Vec<Frame>, color: bool) -> Self {
+        let (s, r) = std::sync::mpsc::channel::<Message>();
+        let target: Target = Arc::new(Mutex::new(target));
 
         let sp = Arc::new(AtomicBool::new(true));
 
         let spinning = sp.clone();
+        let output = target.clone();
         let handle = std::thread::spawn(move || {
             let mut message: Option<String> = None;
-            let frames = frames.iter().cycle().take_while(|_| spinning.load(Ordering::Relaxed));
+            let mut last = String::new();
 
-            for frame in frames {
-                if let Ok(msg) = r.try_recv() {
-                    message = msg;
+            let write_line = |line: &str| {
+                let mut output = output.lock().unwrap();
+                let _ = output.write_all(format!("\r\x1b[0K{line}\n").as_bytes());
+                let _ = output.flush();
+            };
+
+            'frames: for frame in frames.iter().cycle() {
+                if !spinning.load(Ordering::Relaxed) {
+                    break;
                 }
 
                 let fout = match message.as_deref() {
-                    Some(msg) => format!("{frame} {msg}"),
+                    Some(msg) if color => format!("{frame} {msg}"),
+                    Some(msg) => format!("{} {msg}", frame.plain()),
                     None => String::new(),
                 };
 
-                let _ = write!(target, "\r\x1b[0K{fout}");
-                let _ = target.flush();
+                // An empty line only needs to be cleared once
+                if !(fout.is_empty() && last.is_empty()) {
+                    let mut output = output.lock().unwrap();
+                    let _ = output.write_all(format!("\r{fout}\x1b[0K").as_bytes());
+                    let _ = output.flush();
+                }
+                last = fout;
+
+                // Wait out the frame while handling messages. Status updates only take effect on
+                // the next frame, printed lines are written right away. Without a status there is
+                // nothing to animate so the thread sleeps until the next message.
+                let deadline = Instant::now() + frame.interval;
+                loop {
+                    let received = if message.is_none() && last.is_empty() {
+                        r.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                    } else {
+                        r.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    };
+
+                    match received {
+                        Ok(Message::Status(msg)) => message = msg,
+                        Ok(Message::Line(line)) => {
+                            write_line(&line);
+                            last.clear();
+                        }
+                        Ok(Message::Pause(paused)) => {
+                            if !last.is_empty() {
+                                let mut output = output.lock().unwrap();
+                                let _ = output.write_all(b"\r\x1b[0K");
+                                let _ = output.flush();
+                                last.clear();
+                            }
+                            let _ = paused.send(());
+
+                            // Lines logged while paused are held back so they don't end up in
+                            // the middle of the question
+                            let mut held = Vec::new();
+                            let stopped = loop {
+                                match r.recv() {
+                                    Ok(Message::Status(msg)) => message = msg,
+                                    Ok(Message::Line(line)) => held.push(line),
+                                    Ok(Message::Pause(paused)) => {
+                                        let _ = paused.send(());
+                                    }
+                                    Ok(Message::Resume) => break false,
+                                    Ok(Message::Stop) | Err(_) => break true,
+                                }
+                            };
+                            for line in held {
+                                write_line(&line);
+                            }
+                            if stopped {
+                                break 'frames;
+                            }
+                            break;
+                        }
+                        Ok(Message::Resume) => {}
+                        Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => break 'frames,
+                        Err(RecvTimeoutError::Timeout) => break,
+                    }
+                }
+            }
 
-                std::thread::sleep(frame.interval);
+            // Make sure lines sent right before stopping are still printed
+            for msg in r.try_iter() {
+                if let Message::Line(line) = msg {
+                    write_line(&line);
+                    last.clear();
+                }
             }
 
-            let _ = write!(target, "\r\x1b[0K");
+            if !last.is_empty() {
+                let mut output = output.lock().unwrap();
+                let _ = output.write_all(b"\r\x1b[0K");
+                let _ = output.flush();
+            }
             spinning.store(false, Ordering::Relaxed);
         });
 
@@ -176,9 +368,10 @@ impl Spinner {
             channel: s,
             handle: Some(handle),
             spinning: sp,
+            color,
 
-            stream: target
-        } 
+            target,
+        }
     }
 
     /// Check if the spinner is running
@@ -188,7 +381,7 @@ impl Spinner {
 
     /// Update the message of the spinner line
     pub fn update_message(&self, msg: impl std::fmt::Display) {
-        let _ = self.channel.send(Some(msg.to_string()));
+        let _ = self.channel.send(Message::Status(Some(msg.to_string())));
     }
 
     /// Clear the spinner line
@@ -196,17 +389,53 @@ impl Spinner {
     /// The spinner will keep running, it will just not display anything since there
     /// is no message to display.
     pub fn clear(&self) {
-        let _ = self.channel.send(None);
+        let _ = self.channel.send(Message::Status(None));
+    }
+
+    /// Print a line above the spinner line
+    ///
+    /// Falls back to writing directly when the spinner has been stopped.
+    pub fn println(&self, line: impl std::fmt::Display) {
+        let line = line.to_string();
+        if self.handle.is_some() {
+            if let Err(err) = self.channel.send(Message::Line(line)) {
+                if let Message::Line(line) = err.0 {
+                    self.write_line(&line);
+                }
+            }
+        } else {
+            self.write_line(&line);
+        }
+    }
+
+    fn write_line(&self, line: &str) {
+        let mut target = self.target.lock().unwrap();
+        let _ = writeln!(target, "\r\x1b[0K{line}");
+        let _ = target.flush();
+    }
+
+    /// Handle that logs to this spinner from other threads
+    ///
+    /// The spinner still owns the render thread, handles only send messages to it and write
+    /// directly once it was stopped.
+    pub fn handle(&self) -> SpinnerHandle {
+        SpinnerHandle {
+            channel: self.channel.clone(),
+            spinning: self.spinning.clone(),
+            color: self.color,
+            target: self.target.clone(),
+        }
     }
 
     /// Stop the spinner and wait for it to exit
+    ///
+    /// Lines printed before stopping are written and the status line is cleared before this
+    /// returns. Lines printed afterwards are written directly.
     pub fn stop(&mut self) {
-        let _ = self.channel.send(None);
         self.spinning.store(false, Ordering::Relaxed);
+        let _ = self.channel.send(Message::Stop);
         if let Some(handle) = self.handle.take() {
-            if !handle.is_finished() {
-                let _ = handle.join();
-            }
+            let _ = handle.join();
         }
     }
 }
@@ -217,21 +446,64 @@ impl Drop for Spinner {
     }
 }
 
+/// Pause the render thread while `question` is written to `target` and the answer is read
+///
+/// When the render thread isn't `running` anymore the question is written directly.
+fn ask(
+    channel: &Sender<Message>,
+    running: bool,
+    target: &Target,
+    question: impl std::fmt::Display,
+    input: &mut impl BufRead,
+) -> Result<String, crate::Error> {
+    let (paused, wait) = std::sync::mpsc::channel();
+    let paused = running && channel.send(Message::Pause(paused)).is_ok() && wait.recv().is_ok();
+
+    let answer = (|| {
+        {
+            let mut target = target.lock().unwrap();
+            write!(target, "{question}")?;
+            target.flush()?;
+        }
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(answer)
+    })();
+
+    if paused {
+        let _ = channel.send(Message::Resume);
+    }
+    answer
+}
+
+/// Prefix a log line with its glyph, colored when enabled
+fn mark(color: bool, glyph: &str, colored: impl std::fmt::Display, log: impl std::fmt::Display) -> String {
+    if color {
+        format!("{colored} {log}")
+    } else {
+        format!("{glyph} {log}")
+    }
+}
+
 impl Logger for Spinner {
     fn update(&mut self, log: impl std::fmt::Display) {
         self.update_message(log.to_string());
     }
 
     fn error(&mut self, log: impl std::fmt::Display) {
-        let _ = writeln!(self.stream, "\r\x1b[0K{} {}", "✕".red().bold(), log);
+        self.println(mark(self.color, "✕", "✕".red().bold(), log));
     }
 
     fn success(&mut self, log: impl std::fmt::Display) {
-        let _ = writeln!(self.stream, "\r\x1b[0K{} {}", "✓".green().bold(), log);
+        self.println(mark(self.color, "✓", "✓".green().bold(), log));
     }
 
     fn warning(&mut self, log: impl std::fmt::Display) {
-        let _ = writeln!(self.stream, "\r\x1b[0K{} {}", "⚠".yellow().bold(), log);
+        self.println(mark(self.color, "⚠", "⚠".yellow().bold(), log));
+    }
+
+    fn prompt(&mut self, question: impl std::fmt::Display) -> Result<String, crate::Error> {
+        ask(&self.channel, self.handle.is_some(), &self.target, question, &mut stdin().lock())
     }
 
     fn finish(&mut self) {
@@ -239,6 +511,90 @@ impl Logger for Spinner {
     }
 }
 
+/// Cloneable handle to a [`Spinner`] that can be shared with worker threads
+///
+/// Logging only needs `&self`. The [`Logger`] implementation is provided so a handle can be used
+/// where a logger is expected, its `finish` does nothing since the spinner owns the render thread.
+#[derive(Clone)]
+pub struct SpinnerHandle {
+    channel: Sender<Message>,
+    spinning: Arc<AtomicBool>,
+    color: bool,
+    target: Target,
+}
+
+impl std::fmt::Debug for SpinnerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpinnerHandle")
+            .field("spinning", &self.spinning.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpinnerHandle {
+    /// Update the message of the spinner line
+    pub fn update(&self, log: impl std::fmt::Display) {
+        let _ = self.channel.send(Message::Status(Some(log.to_string())));
+    }
+
+    pub fn error(&self, log: impl std::fmt::Display) {
+        self.println(mark(self.color, "✕", "✕".red().bold(), log));
+    }
+
+    pub fn success(&self, log: impl std::fmt::Display) {
+        self.println(mark(self.color, "✓", "✓".green().bold(), log));
+    }
+
+    pub fn warning(&self, log: impl std::fmt::Display) {
+        self.println(mark(self.color, "⚠", "⚠".yellow().bold(), log));
+    }
+
+    /// Print a line above the spinner line, or directly once the spinner was stopped
+    pub fn println(&self, line: impl std::fmt::Display) {
+        let line = line.to_string();
+        let line = if self.spinning.load(Ordering::Relaxed) {
+            match self.channel.send(Message::Line(line)) {
+                Ok(()) => return,
+                Err(err) => match err.0 {
+                    Message::Line(line) => line,
+                    _ => return,
+                },
+            }
+        } else {
+            line
+        };
+
+        let mut target = self.target.lock().unwrap();
+        let _ = writeln!(target, "\r\x1b[0K{line}");
+        let _ = target.flush();
+    }
+}
+
+impl Logger for SpinnerHandle {
+    fn update(&mut self, log: impl std::fmt::Display) {
+        SpinnerHandle::update(self, log);
+    }
+
+    fn error(&mut self, log: impl std::fmt::Display) {
+        SpinnerHandle::error(self, log);
+    }
+
+    fn success(&mut self, log: impl std::fmt::Display) {
+        SpinnerHandle::success(self, log);
+    }
+
+    fn warning(&mut self, log: impl std::fmt::Display) {
+        SpinnerHandle::warning(self, log);
+    }
+
+    fn prompt(&mut self, question: impl std::fmt::Display) -> Result<String, crate::Error> {
+        let running = self.spinning.load(Ordering::Relaxed);
+        ask(&self.channel, running, &self.target, question, &mut stdin().lock())
+    }
+
+    fn finish(&mut self) {}
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -269,4 +625,242 @@ mod test {
 
         assert!(!spinner.is_spinning());
     }
+
+    /// Records how many times the output was flushed, one flush per render
+    #[derive(Clone, Default)]
+    struct Renders(Arc<Mutex<Vec<String>>>);
+    impl Write for Renders {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let mut renders = self.0.lock().unwrap();
+            if renders.is_empty() {
+                renders.push(String::new());
+            }
+            renders.last_mut().unwrap().push_str(&String::from_utf8_lossy(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(String::new());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spinner_renders_to_writer() {
+        let renders = Renders::default();
+        let mut spinner = Spinner::new_with_writer(
+            Box::new(renders.clone()),
+            frames!(["-", "|"], Duration::from_millis(10)),
+            ColorMode::Auto,
+        );
+
+        spinner.update("working");
+        std::thread::sleep(Duration::from_millis(100));
+        Logger::success(&mut spinner, "done");
+        spinner.stop();
+        assert!(!spinner.is_spinning());
+
+        let output = renders.0.lock().unwrap().concat();
+        assert!(output.contains("\r| working\x1b[0K") && output.contains("\r- working\x1b[0K"), "{output:?}");
+        assert!(output.ends_with("\r\x1b[0K✓ done\n"), "{output:?}");
+        assert!(!has_sgr(&output), "{output:?}");
+    }
+
+    #[test]
+    fn fatal_errors_use_the_logger() {
+        for (color, colored) in [(ColorMode::Always, true), (ColorMode::Never, false)] {
+            let renders = Renders::default();
+            let mut spinner = Spinner::new_with_writer(
+                Box::new(renders.clone()),
+                frames!(["-", "|"], Duration::from_millis(10)),
+                color,
+            );
+            spinner.update("working");
+            fatal(&mut spinner, crate::Error::custom("the project path does not exist"));
+            assert!(!spinner.is_spinning());
+
+            let output = renders.0.lock().unwrap().concat();
+            assert!(output.ends_with("the project path does not exist\n"), "{output:?}");
+            assert_eq!(has_sgr(&output), colored, "{output:?}");
+            if !colored {
+                assert!(output.ends_with("\r\x1b[0K✕ the project path does not exist\n"), "{output:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn spinner_coalesces_updates() {
+        let interval = Duration::from_millis(20);
+        let renders = Renders::default();
+
+        let start = Instant::now();
+        let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames!(["-", "|"], interval), true);
+        for i in 0..1000 {
+            spinner.update(format!("update {i}"));
+        }
+        Logger::success(&mut spinner, "done");
+        std::thread::sleep(Duration::from_millis(200));
+        spinner.stop();
+        let elapsed = start.elapsed();
+
+        let renders = renders.0.lock().unwrap();
+        let frames = (elapsed.as_millis() / interval.as_millis()) as usize + 1;
+        // One render per frame, the printed line, and the final clear
+        assert!(renders.len() <= frames + 2, "{} renders in {frames} frames", renders.len());
+        assert!(renders.iter().any(|r| r.contains("update 999")));
+        assert!(renders.iter().any(|r| r.contains("done\n")));
+    }
+
+    #[test]
+    fn spinner_sleeps_while_idle() {
+        let interval = Duration::from_millis(10);
+        let renders = Renders::default();
+
+        let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames!(["-", "|"], interval), true);
+        std::thread::sleep(interval * 10);
+        assert_eq!(renders.0.lock().unwrap().len(), 0);
+
+        spinner.update("working");
+        std::thread::sleep(interval * 5);
+        spinner.clear();
+        std::thread::sleep(interval * 5);
+
+        let idle = renders.0.lock().unwrap().len();
+        assert!(idle > 0);
+        assert!(renders.0.lock().unwrap().iter().rev().any(|r| r == "\r\x1b[0K"));
+        std::thread::sleep(interval * 20);
+        assert_eq!(renders.0.lock().unwrap().len(), idle);
+
+        // Printed lines still wake the thread
+        Logger::success(&mut spinner, "done");
+        std::thread::sleep(interval * 5);
+        assert!(renders.0.lock().unwrap().len() > idle);
+
+        spinner.stop();
+        assert!(!spinner.is_spinning());
+    }
+
+    #[test]
+    fn finish_writes_summary_last() {
+        let interval = Duration::from_millis(1);
+        for _ in 0..50 {
+            let renders = Renders::default();
+            let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames!(["-", "|"], interval), false);
+            spinner.update("working");
+            std::thread::sleep(interval * 2);
+            spinner.update("still working");
+            Logger::success(&mut spinner, "1 added, 2 up-to-date");
+            spinner.finish();
+            assert!(!spinner.is_spinning());
+            Logger::success(&mut spinner, "after finish");
+
+            let output = renders.0.lock().unwrap().concat();
+            assert!(
+                output.ends_with("\r\x1b[0K✓ 1 added, 2 up-to-date\n\r\x1b[0K✓ after finish\n"),
+                "{output:?}"
+            );
+        }
+    }
+
+    /// Input that logs through the spinner and waits a few frames before answering
+    struct Answer(SpinnerHandle, Duration, &'static [u8]);
+    impl std::io::Read for Answer {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.warning("logged while asking");
+            std::thread::sleep(self.1);
+            self.2.read(buf)
+        }
+    }
+
+    #[test]
+    fn prompt_pauses_rendering() {
+        let interval = Duration::from_millis(5);
+        let renders = Renders::default();
+        let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames!(["-", "|"], interval), false);
+
+        spinner.update("working");
+        std::thread::sleep(interval * 4);
+        let mut input = std::io::BufReader::new(Answer(spinner.handle(), interval * 10, b"love2d\n"));
+        let answer = ask(&spinner.channel, true, &spinner.target, "Addon to add: ", &mut input).unwrap();
+        assert_eq!(answer, "love2d\n");
+        std::thread::sleep(interval * 4);
+        spinner.stop();
+
+        let output = renders.0.lock().unwrap().concat();
+        let (before, after) = output.split_once("Addon to add: ").unwrap();
+        assert!(before.ends_with("working\x1b[0K\r\x1b[0K"), "{output:?}");
+        assert!(after.starts_with("\r\x1b[0K⚠ logged while asking\n"), "{output:?}");
+        assert!(after.contains("working"), "{output:?}");
+    }
+
+    #[test]
+    fn null_logger_cannot_prompt() {
+        assert!(NullLogger.prompt("Addon to add: ").is_err());
+    }
+
+    #[test]
+    fn handle_logs_from_many_threads() {
+        let renders = Renders::default();
+        let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames!(["-", "|"], Duration::from_millis(1)), false);
+
+        let handle = spinner.handle();
+        std::thread::scope(|scope| {
+            for worker in 0..8 {
+                let handle = handle.clone();
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        handle.update(format!("worker {worker} step {i}"));
+                        handle.success(format!("worker {worker} line {i}"));
+                    }
+                });
+            }
+        });
+        spinner.finish();
+        handle.warning("after finish");
+
+        let output = renders.0.lock().unwrap().concat();
+        for worker in 0..8 {
+            for i in 0..50 {
+                let line = format!("\r\x1b[0K✓ worker {worker} line {i}\n");
+                assert_eq!(output.matches(&line).count(), 1, "{line:?}");
+            }
+        }
+        assert!(output.ends_with("\r\x1b[0K⚠ after finish\n"), "{output:?}");
+
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<SpinnerHandle>();
+    }
+
+    /// Whether the output contains a color (SGR) escape sequence
+    fn has_sgr(output: &str) -> bool {
+        output.split("\x1b[").skip(1).any(|seq| {
+            seq.chars()
+                .find(|c| !c.is_ascii_digit() && *c != ';')
+                .is_some_and(|c| c == 'm')
+        })
+    }
+
+    #[test]
+    fn never_color_has_no_sgr() {
+        let interval = Duration::from_millis(10);
+        let frames = frames!(["-", "|"], interval, colors::xterm::AeroBlue);
+
+        for (color, expected) in [(true, true), (false, false)] {
+            let renders = Renders::default();
+            let mut spinner = Spinner::spawn(Box::new(renders.clone()), frames.clone(), color);
+            spinner.update("working");
+            std::thread::sleep(interval * 3);
+            Logger::success(&mut spinner, "done");
+            Logger::warning(&mut spinner, "careful");
+            Logger::error(&mut spinner, "failed");
+            spinner.stop();
+
+            let output = renders.0.lock().unwrap().concat();
+            assert!(output.contains("working") && output.contains("failed"));
+            assert_eq!(has_sgr(&output), expected, "{output:?}");
+        }
+
+        assert!(!ColorMode::Never.enabled(Stream::Stdout));
+        assert!(ColorMode::Always.enabled(Stream::Stdout));
+    }
 }
@@ -146,6 +146,27 @@ pub enum Diagnostic {
     Unused(Unused),
 }
 
+impl Diagnostic {
+    /// Group the diagnostic belongs to, e.g. `global` for `global:undefined-global`
+    pub fn group(&self) -> DiagnosticGroup {
+        match self {
+            Self::Ambiguity(_) => DiagnosticGroup::Ambiguity,
+            Self::Await(_) => DiagnosticGroup::Await,
+            Self::Codestyle(_) => DiagnosticGroup::Codestyle,
+            Self::Conventions(_) => DiagnosticGroup::Conventions,
+            Self::Duplicate(_) => DiagnosticGroup::Duplicate,
+            Self::Global(_) => DiagnosticGroup::Global,
+            Self::Luadoc(_) => DiagnosticGroup::Luadoc,
+            Self::Redefined(_) => DiagnosticGroup::Redefined,
+            Self::Strict(_) => DiagnosticGroup::Strict,
+            Self::Strong(_) => DiagnosticGroup::Strong,
+            Self::TypeCheck(_) => DiagnosticGroup::TypeCheck,
+            Self::Unbalanced(_) => DiagnosticGroup::Unbalanced,
+            Self::Unused(_) => DiagnosticGroup::Unused,
+        }
+    }
+}
+
 impl FromStr for Diagnostic {
     type Err = String;
 
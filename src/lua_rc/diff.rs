@@ -0,0 +1,128 @@
+//! Line level diff between two versions of a config file.
+
+/// Number of unchanged lines shown around each change
+const CONTEXT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Compute the line changes needed to go from `old` to `new`
+pub fn changes<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // Longest common subsequence table, lcs[i][j] is the length for old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(Change::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(Change::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|line| Change::Removed(line)));
+    changes.extend(new[j..].iter().map(|line| Change::Added(line)));
+    changes
+}
+
+/// Render the changes between `old` and `new` with `-`/`+` markers, only keeping a few lines of
+/// context around each change. Returns `None` when there are no changes.
+pub fn diff(old: &str, new: &str) -> Option<String> {
+    let changes = changes(old, new);
+    if changes.iter().all(|c| matches!(c, Change::Same(_))) {
+        return None;
+    }
+
+    let near_change = |index: usize| {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(changes.len());
+        changes[start..end]
+            .iter()
+            .any(|c| !matches!(c, Change::Same(_)))
+    };
+
+    let mut out = Vec::new();
+    let mut skipped = false;
+    for (index, change) in changes.iter().enumerate() {
+        if !near_change(index) {
+            skipped = true;
+            continue;
+        }
+
+        if skipped && !out.is_empty() {
+            out.push("  ...".to_string());
+        }
+        skipped = false;
+
+        out.push(match change {
+            Change::Same(line) => format!("  {line}"),
+            Change::Removed(line) => format!("- {line}"),
+            Change::Added(line) => format!("+ {line}"),
+        });
+    }
+
+    Some(out.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lua_rc::LuaRc, Addon};
+
+    #[test]
+    fn no_changes() {
+        assert_eq!(diff("{\n}", "{\n}"), None);
+    }
+
+    #[test]
+    fn added_addon() {
+        let mut rc = LuaRc::default();
+        rc.add_or_update_addon(&Addon::from("love2d"));
+        let old = serde_json::to_string_pretty(&rc).unwrap();
+
+        rc.add_or_update_addon(&Addon::from("lovr"));
+        let new = serde_json::to_string_pretty(&rc).unwrap();
+
+        let diff = diff(&old, &new).unwrap();
+        assert!(diff.contains(r#"+       "lovr": {"#), "{diff}");
+        assert!(diff.contains(r#"+         "src": "lovr","#), "{diff}");
+        assert!(!diff.lines().any(|l| l.starts_with('-')), "{diff}");
+    }
+
+    #[test]
+    fn changed_checksum() {
+        let mut rc = LuaRc::default();
+        rc.add_or_update_addon(&Addon::from("love2d@1234567"));
+        let old = serde_json::to_string_pretty(&rc).unwrap();
+
+        rc.add_or_update_addon(&Addon::from("love2d@89abcde"));
+        let new = serde_json::to_string_pretty(&rc).unwrap();
+
+        let diff = diff(&old, &new).unwrap();
+        assert!(diff.contains(r#"-           "commit": "1234567""#), "{diff}");
+        assert!(diff.contains(r#"+           "commit": "89abcde""#), "{diff}");
+        assert_eq!(diff.lines().filter(|l| l.starts_with(['-', '+'])).count(), 2);
+    }
+}
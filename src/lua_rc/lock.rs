@@ -0,0 +1,30 @@
+//! Addon pins stored outside of `.luarc.json`.
+//!
+//! When a lockfile exists next to the config, the resolved addons are kept in it instead of the
+//! custom `workspace.addons` field so the config only carries the luals facing settings.
+
+use std::{borrow::Cow, collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Addon, Error};
+
+/// Name of the lockfile, stored next to `.luarc.json`
+pub static LOCKFILE: &str = ".llam.lock";
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub addons: BTreeMap<Cow<'static, str>, Addon>,
+}
+
+impl Lockfile {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let bytes = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn contents(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
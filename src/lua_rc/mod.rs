@@ -8,12 +8,13 @@
 //!
 //! # Added Fields:
 //! - `workspace.addons`: An object of where the key is the addon name and the value is a json
-//! representation of [`Addon`][crate::Addon]. This information is used to know what addons are
-//! currently installed. Similar to the `"dependencies"` entry in a `npm` project's `package.json`
+//!   representation of [`Addon`][crate::Addon]. This information is used to know what addons are
+//!   currently installed. Similar to the `"dependencies"` entry in a `npm` project's `package.json`.
+//!   When a [`LOCKFILE`] exists the addons are stored there instead.
 
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -22,9 +23,18 @@ use serde::{de::Visitor, Deserialize, Serialize};
 use serde_json::Value;
 
 pub mod diagnostics;
+pub mod diff;
+pub mod lock;
+pub mod schema;
+pub use lock::{Lockfile, LOCKFILE};
+pub mod vars;
+pub use vars::expand_vars;
 use diagnostics::{Diagnostic, DiagnosticGroup};
 use crate::{Addon, Error, LUARC};
 
+/// Editor folders that are searched for the config when the project root doesn't have one
+pub static CONFIG_DIRS: [&str; 2] = [".vscode", ".nvim"];
+
 
 const fn enabled(ctx: &bool) -> bool {
     *ctx
@@ -43,6 +53,18 @@ const fn default_true() -> bool {
     true
 }
 
+/// Whether a settings block would be written as an empty object, e.g. when it was created with
+/// [`LuaRc::runtime_mut`] but every setting was left at its default.
+///
+/// Unknown keys are part of the serialized block, so a block that has any is always written
+/// along with them.
+fn is_empty_block<T: Serialize>(block: &Option<T>) -> bool {
+    match block {
+        None => true,
+        Some(block) => matches!(serde_json::to_value(block), Ok(Value::Object(map)) if map.is_empty()),
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct AddonManager {
     #[serde(default = "default_true", skip_serializing_if = "enabled")]
@@ -266,6 +288,47 @@ impl Diagnostics {
     const fn full_percent_validate(ctx: &usize) -> bool {
         *ctx == 100
     }
+
+    /// Declare globals, skipping ones that are already declared
+    pub fn add_globals(&mut self, globals: impl IntoIterator<Item = String>) {
+        for global in globals {
+            if !self.globals.contains(&global) {
+                self.globals.push(global);
+            }
+        }
+    }
+
+    /// Merge a severity policy of `diagnostic=severity` lines into the severities
+    ///
+    /// Blank lines and `#` comments are ignored. Nothing is merged when a line is invalid, the
+    /// error lists every invalid line with its line number. Returns the number of severities set.
+    pub fn import_severities(&mut self, policy: &str) -> Result<usize, String> {
+        let mut severities = Vec::new();
+        let mut invalid = Vec::new();
+        for (number, line) in policy.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parsed = match line.split_once('=') {
+                Some((diagnostic, severity)) => Diagnostic::from_str(diagnostic.trim())
+                    .and_then(|diagnostic| Ok((diagnostic, Severity::from_str(severity.trim())?))),
+                None => Err("expected <diagnostic>=<severity>".to_string()),
+            };
+            match parsed {
+                Ok(severity) => severities.push(severity),
+                Err(err) => invalid.push(format!("line {}: `{line}`: {err}", number + 1)),
+            }
+        }
+
+        if !invalid.is_empty() {
+            return Err(invalid.join("\n"));
+        }
+        let count = severities.len();
+        self.severity.extend(severities);
+        Ok(count)
+    }
 }
 
 impl Default for Diagnostics {
@@ -294,14 +357,14 @@ impl Default for Diagnostics {
 #[derive(Default, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Doc {
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
-    pub package_name: HashSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub package_name: BTreeSet<String>,
 
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
-    pub private_name: HashSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub private_name: BTreeSet<String>,
 
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
-    pub protected_name: HashSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub protected_name: BTreeSet<String>,
 
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub other: Option<BTreeMap<String, Value>>,
@@ -395,17 +458,17 @@ impl Default for Hint {
 pub struct Hover {
     #[serde(default = "default_true", skip_serializing_if = "enabled")]
     pub enable: bool,
-    #[serde(default, skip_serializing_if = "Self::enum_limit_validate")]
+    #[serde(default = "Hover::enums_limit", skip_serializing_if = "Self::enum_limit_validate")]
     pub enums_limit: usize,
     #[serde(default = "default_true", skip_serializing_if = "enabled")]
     pub expand_alias: bool,
-    #[serde(default, skip_serializing_if = "Self::preview_fields_validate")]
+    #[serde(default = "Hover::preview_fields", skip_serializing_if = "Self::preview_fields_validate")]
     pub preview_fields: usize,
     #[serde(default = "default_true", skip_serializing_if = "enabled")]
     pub view_number: bool,
     #[serde(default = "default_true", skip_serializing_if = "enabled")]
     pub view_string: bool,
-    #[serde(default, skip_serializing_if = "Self::view_string_max_validate")]
+    #[serde(default = "Hover::view_string_max", skip_serializing_if = "Self::view_string_max_validate")]
     pub view_string_max: usize,
 
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
@@ -429,6 +492,21 @@ impl Default for Hover {
 }
 
 impl Hover {
+    #[inline]
+    fn enums_limit() -> usize {
+        5
+    }
+
+    #[inline]
+    fn preview_fields() -> usize {
+        50
+    }
+
+    #[inline]
+    fn view_string_max() -> usize {
+        1000
+    }
+
     const fn enum_limit_validate(ctx: &usize) -> bool {
         *ctx == 5
     }
@@ -701,11 +779,26 @@ pub struct Workspace {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub addons: BTreeMap<Cow<'static, str>, Addon>,
 
+    /// Named addon sets that are used instead of `addons` when selected with `--profile`
+    ///
+    /// Also custom to llam, addons share one clone in the addons directory between profiles.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, BTreeMap<Cow<'static, str>, Addon>>,
+
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub other: Option<BTreeMap<String, Value>>,
 }
 
 impl Workspace {
+    /// Ignore directories, skipping ones that are already ignored
+    pub fn add_ignore_dirs(&mut self, dirs: impl IntoIterator<Item = String>) {
+        for dir in dirs {
+            if !self.ignore_dir.contains(&dir) {
+                self.ignore_dir.push(dir);
+            }
+        }
+    }
+
     pub const fn max_preload() -> usize {
         5000
     }
@@ -736,6 +829,7 @@ impl Default for Workspace {
             user_third_party: Vec::default(),
 
             addons: BTreeMap::default(),
+            profiles: BTreeMap::default(),
 
             other: None,
         }
@@ -747,37 +841,49 @@ impl Default for Workspace {
 pub struct LuaRc {
     #[serde(skip)]
     path: PathBuf,
+    /// Whether the config file did not exist and was created when it was detected
+    #[serde(skip)]
+    created: bool,
+    /// When set the config file is never written, see [`LuaRc::set_frozen`]
+    #[serde(skip)]
+    frozen: bool,
+    /// Addons stored in a separate lockfile instead of `workspace.addons`
+    #[serde(skip)]
+    lock: Option<Lockfile>,
+    /// Profile in `workspace.profiles` whose addons are used, see [`LuaRc::set_profile`]
+    #[serde(skip)]
+    profile: Option<String>,
 
     #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
     pub schema: Option<String>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub addon_manager: Option<AddonManager>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub completion: Option<Completion>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub diagnostics: Option<Diagnostics>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub doc: Option<Doc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub format: Option<Format>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub hint: Option<Hint>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub hover: Option<Hover>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub misc: Option<Misc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub runtime: Option<Runtime>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub semantic: Option<Semantic>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub signature_help: Option<SignatureHelp>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub spell: Option<Spell>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub r#type: Option<Type>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_empty_block")]
     pub workspace: Option<Workspace>,
 
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
@@ -785,17 +891,117 @@ pub struct LuaRc {
 }
 
 impl LuaRc {
+    /// Read a config file without any other side effects
+    ///
+    /// Unlike [`detect`][LuaRc::detect] nothing is created when the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(path.as_ref())
+    }
+
+    /// Write the config to `path`, which becomes the path used by [`write`][LuaRc::write]
+    pub fn save(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.path = path.as_ref().to_path_buf();
+        self.write()
+    }
+
+    /// Path of the config file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Find the config in `dir`, or one of the editor folders in [`CONFIG_DIRS`], creating an
+    /// empty one in `dir` if it doesn't exist
     pub fn detect(dir: impl AsRef<Path>) -> Result<Self, Error> {
         let dir = dir.as_ref();
+        match Self::find(dir) {
+            Some(path) => Self::read(&path),
+            None => Self::new(dir),
+        }
+    }
 
-        if dir.join(LUARC).exists() {
-            Self::read(&dir.join(LUARC))
-        } else {
-            Self::new(dir)
+    /// Same as [`detect`][LuaRc::detect] but returns an empty config instead of creating one in
+    /// `dir` when it doesn't exist, for commands that only read the config
+    pub fn detect_readonly(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        match Self::find(dir) {
+            Some(path) => Self::read(&path),
+            None => {
+                let mut rc = Self {
+                    path: dir.join(LUARC),
+                    ..Default::default()
+                };
+                rc.read_lockfile()?;
+                Ok(rc)
+            }
         }
     }
 
+    /// Existing config in `dir` or one of the editor folders in [`CONFIG_DIRS`]
+    fn find(dir: &Path) -> Option<PathBuf> {
+        std::iter::once(dir.join(LUARC))
+            .chain(CONFIG_DIRS.iter().map(|sub| dir.join(sub).join(LUARC)))
+            .find(|path| path.exists())
+    }
+
+    /// Move a config that can't be parsed out of the way, returning where it was moved to
+    ///
+    /// The next [`detect`][LuaRc::detect] creates a new config.
+    pub fn backup(path: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        let path = path.as_ref();
+        let mut backup = path.as_os_str().to_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        std::fs::rename(path, &backup)?;
+        Ok(backup)
+    }
+
+    /// Use the addons of a profile in `workspace.profiles` instead of `workspace.addons`, the
+    /// profile is created when addons are added to it
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// Profile whose addons are used
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Whether an addon is in the default addons or a profile other than the one in use, its
+    /// clone is shared with them
+    pub fn in_other_addon_set(&self, name: &str) -> bool {
+        let default = match (self.lock.as_ref(), self.workspace.as_ref()) {
+            (Some(lock), _) => Some(&lock.addons),
+            (None, Some(workspace)) => Some(&workspace.addons),
+            (None, None) => None,
+        };
+        let profiles = self
+            .workspace
+            .iter()
+            .flat_map(|w| w.profiles.iter())
+            .filter(|(profile, _)| Some(profile.as_str()) != self.profile.as_deref())
+            .map(|(_, addons)| addons);
+
+        default
+            .filter(|_| self.profile.is_some())
+            .into_iter()
+            .chain(profiles)
+            .any(|addons| addons.contains_key(name))
+    }
+
+    /// Whether the config was freshly created instead of being read from an existing file
+    pub fn is_new(&self) -> bool {
+        self.created
+    }
+
     pub fn get_addons_mut(&mut self) -> &mut BTreeMap<Cow<'static, str>, Addon> {
+        if let Some(profile) = self.profile.clone() {
+            return self.workspace_mut().profiles.entry(profile).or_default();
+        }
+
+        if let Some(lock) = self.lock.as_mut() {
+            return &mut lock.addons;
+        }
+
         if self.workspace.is_none() {
             self.workspace = Some(Workspace {
                 addons: BTreeMap::default(),
@@ -807,6 +1013,14 @@ impl LuaRc {
     }
 
     pub fn get_addons(&mut self) -> &BTreeMap<Cow<'static, str>, Addon> {
+        if self.profile.is_some() {
+            return self.get_addons_mut();
+        }
+
+        if let Some(lock) = self.lock.as_ref() {
+            return &lock.addons;
+        }
+
         if self.workspace.is_none() {
             self.workspace = Some(Workspace {
                 addons: BTreeMap::default(),
@@ -828,7 +1042,114 @@ impl LuaRc {
         }
     }
 
+    /// `workspace.library` entries with environment variables expanded
+    pub fn library_paths(&self) -> Vec<String> {
+        self.workspace
+            .iter()
+            .flat_map(|w| w.library.iter())
+            .map(|entry| expand_vars(entry).into_owned())
+            .collect()
+    }
+
+    /// `runtime.path` entries with environment variables expanded
+    pub fn runtime_paths(&self) -> Vec<String> {
+        self.runtime
+            .iter()
+            .flat_map(|r| r.path.iter())
+            .map(|entry| expand_vars(entry).into_owned())
+            .collect()
+    }
+
+    /// `misc.executablePath` with environment variables expanded
+    pub fn executable_path(&self) -> Option<String> {
+        self.misc
+            .as_ref()
+            .and_then(|m| m.executable_path.as_deref())
+            .map(|path| expand_vars(path).into_owned())
+    }
+
+    /// Path of the lockfile next to the config
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.path.with_file_name(LOCKFILE)
+    }
+
+    /// Whether addons are stored in a separate lockfile
+    pub fn uses_lockfile(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Store addons in a separate lockfile, or move them back into `workspace.addons`
+    ///
+    /// Takes effect the next time the config is written.
+    pub fn set_use_lockfile(&mut self, split: bool) {
+        match (split, self.lock.take()) {
+            (true, None) => {
+                let addons = self
+                    .workspace
+                    .as_mut()
+                    .map(|w| std::mem::take(&mut w.addons))
+                    .unwrap_or_default();
+                self.lock = Some(Lockfile { addons });
+            }
+            (false, Some(lock)) => self.workspace_mut().addons.extend(lock.addons),
+            (_, lock) => self.lock = lock,
+        }
+    }
+
+    /// Prevent the config file from changing
+    ///
+    /// [`write`][LuaRc::write] will return an error containing the diff of what would have
+    /// changed instead of writing the file.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Line diff between the config file on disk and what would be written
+    ///
+    /// Returns `None` if writing the config would not change the file.
+    pub fn pending_diff(&self) -> Result<Option<String>, Error> {
+        let current = if self.path.exists() {
+            std::fs::read_to_string(&self.path)?
+        } else {
+            String::new()
+        };
+
+        Ok(diff::diff(&current, &serde_json::to_string_pretty(self)?))
+    }
+
     pub fn write(&self) -> Result<(), Error> {
+        if self.frozen {
+            let lock_diff = match self.lock.as_ref() {
+                Some(lock) => {
+                    let path = self.lockfile_path();
+                    let current = match path.exists() {
+                        true => std::fs::read_to_string(&path)?,
+                        false => String::new(),
+                    };
+                    diff::diff(&current, &lock.contents()?).map(|diff| (path, diff))
+                }
+                None => None,
+            };
+
+            let changes = self
+                .pending_diff()?
+                .map(|diff| (self.path.clone(), diff))
+                .into_iter()
+                .chain(lock_diff)
+                .map(|(path, diff)| {
+                    format!("{} is frozen but would change:\n{diff}", path.display())
+                })
+                .collect::<Vec<_>>();
+            return match changes.is_empty() {
+                true => Ok(()),
+                false => Err(Error::custom(changes.join("\n"))),
+            };
+        }
+
+        if let Some(lock) = self.lock.as_ref() {
+            std::fs::write(self.lockfile_path(), lock.contents()?)?;
+        }
+
         Ok(std::fs::write(
             &self.path,
             serde_json::to_string_pretty(self)?,
@@ -839,19 +1160,48 @@ impl LuaRc {
 impl LuaRc {
     fn read(file: &Path) -> Result<Self, Error> {
         let bytes = std::fs::read(file)?;
-        let mut lock: Self = serde_json::from_slice(&bytes)?;
+        // Report which key is invalid, e.g. a hand edited `workspace.addons` that isn't an object,
+        // instead of only the line and column
+        let mut de = serde_json::Deserializer::from_slice(&bytes);
+        let mut lock: Self = serde_path_to_error::deserialize(&mut de).map_err(|err| {
+            Error::InvalidConfig(
+                file.to_path_buf(),
+                format!("invalid `{}` in {}: {}", err.path(), file.display(), err.inner()),
+            )
+        })?;
 
         lock.path = file.to_path_buf();
+        lock.read_lockfile()?;
 
         Ok(lock)
     }
 
+    /// Use the lockfile next to the config if it exists. Addons still in the config are merged
+    /// into it so they are moved over on the next write.
+    fn read_lockfile(&mut self) -> Result<(), Error> {
+        let path = self.lockfile_path();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let mut lockfile = Lockfile::read(&path)?;
+        if let Some(workspace) = self.workspace.as_mut() {
+            for (name, addon) in std::mem::take(&mut workspace.addons) {
+                lockfile.addons.entry(name).or_insert(addon);
+            }
+        }
+        self.lock = Some(lockfile);
+        Ok(())
+    }
+
     fn new(dir: &Path) -> Result<Self, Error> {
         // Attempt to read sha1 from cloned addon repositories
-        let lock = Self {
+        let mut lock = Self {
             path: dir.join(LUARC),
+            created: true,
             ..Default::default()
         };
+        lock.read_lockfile()?;
 
         // TODO: Create error instead
         if !dir.exists() {
@@ -993,3 +1343,277 @@ impl LuaRc {
         self.diagnostics.as_mut().unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn default_blocks_are_not_written() {
+        let dir = TempDir::new();
+
+        let minimal = "{\n  \"hover\": {\n    \"enable\": false\n  },\n  \"runtime\": {\n    \"version\": \"LuaJIT\"\n  }\n}";
+        std::fs::write(dir.join(LUARC), minimal).unwrap();
+
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        rc.runtime_mut();
+        rc.type_mut();
+        rc.misc_mut();
+        rc.diagnostics_mut();
+        rc.completion_mut();
+        rc.get_addons();
+        rc.write().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join(LUARC)).unwrap(), minimal);
+
+        // Blocks that end up at their defaults are dropped
+        rc.runtime_mut().version = None;
+        rc.hover_mut().enable = true;
+        rc.write().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join(LUARC)).unwrap(), "{}");
+    }
+
+    #[test]
+    fn import_severity_policy() {
+        let policy = indoc::indoc! {"
+            # Shared severity policy
+            global:lowercase-global = Error
+            global:undefined-global=warning!  # too noisy as an error
+
+        "};
+
+        let mut diagnostics = Diagnostics::default();
+        assert_eq!(diagnostics.import_severities(policy), Ok(2));
+        assert_eq!(
+            serde_json::to_value(&diagnostics).unwrap(),
+            serde_json::json!({
+                "severity": {
+                    "lowercase-global": "Error",
+                    "undefined-global": "Warning!",
+                }
+            })
+        );
+
+        let invalid = format!("{policy}global:undefined-global=Loud\nunused-local\n");
+        let mut other = Diagnostics::default();
+        let err = other.import_severities(&invalid).unwrap_err();
+        assert_eq!(err.lines().count(), 2, "{err}");
+        assert!(err.starts_with("line 5: `global:undefined-global=Loud`"), "{err}");
+        assert!(err.contains("line 6: `unused-local`"), "{err}");
+        assert!(other.severity.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_survive_pruning() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join(LUARC), indoc::indoc! {r#"
+            {
+              "x-team": { "owner": "tools", "tags": [] },
+              "emptyCustom": {},
+              "runtime": { "version": "LuaJIT" },
+              "workspace": { "customKey": {}, "customList": [] },
+              "hover": { "enable": true, "customFlag": false }
+            }
+        "#}).unwrap();
+
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        // Every block llam manages ends up at its defaults
+        rc.runtime_mut().version = None;
+        rc.diagnostics_mut();
+        rc.get_addons();
+        rc.write().unwrap();
+
+        let written: Value = serde_json::from_str(&std::fs::read_to_string(dir.join(LUARC)).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({
+                "x-team": { "owner": "tools", "tags": [] },
+                "emptyCustom": {},
+                "workspace": { "customKey": {}, "customList": [] },
+                "hover": { "customFlag": false }
+            })
+        );
+    }
+
+    #[test]
+    fn config_in_editor_folder() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(".vscode")).unwrap();
+        let path = dir.join(".vscode").join(LUARC);
+        std::fs::write(&path, r#"{ "runtime": { "version": "LuaJIT" } }"#).unwrap();
+
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert_eq!(rc.path(), path);
+        assert!(!rc.is_new());
+        rc.hover_mut().enable = false;
+        rc.write().unwrap();
+        assert!(!dir.join(LUARC).exists());
+        let config = std::fs::read_to_string(&path).unwrap();
+        assert!(config.contains("LuaJIT") && config.contains("hover"), "{config}");
+
+        // The root config is preferred
+        std::fs::write(dir.join(LUARC), "{}").unwrap();
+        assert_eq!(LuaRc::detect(&dir).unwrap().path(), dir.join(LUARC));
+    }
+
+    #[test]
+    fn rewriting_is_byte_identical() {
+        let dir = TempDir::new();
+        std::fs::write(
+            dir.join(LUARC),
+            r#"{
+                "doc": { "privateName": ["_*", "m_*", "__*"] },
+                "workspace": {
+                    "addons": {
+                        "lovr": { "src": "lovr", "target": "lua_cats", "required_by": ["zeta", "alpha"] },
+                        "love2d": {
+                            "src": "https://example.com/love2d.git",
+                            "target": "git",
+                            "mirrors": ["https://z.example.com/love2d.git", "https://a.example.com/love2d.git"]
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rewrite = || {
+            LuaRc::detect(&dir).unwrap().write().unwrap();
+            std::fs::read_to_string(dir.join(LUARC)).unwrap()
+        };
+        let first = rewrite();
+        assert_eq!(rewrite(), first);
+
+        // Sets and maps are sorted, mirrors keep the order they are tried in
+        let value: Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(value["doc"]["privateName"], serde_json::json!(["_*", "__*", "m_*"]));
+        let addons = &value["workspace"]["addons"];
+        assert_eq!(addons["lovr"]["required_by"], serde_json::json!(["alpha", "zeta"]));
+        assert_eq!(addons["love2d"]["mirrors"][0], "https://z.example.com/love2d.git");
+        assert!(first.find("\"love2d\"").unwrap() < first.find("\"lovr\"").unwrap());
+    }
+
+    #[test]
+    fn combined_and_split_addons() {
+        let dir = TempDir::new();
+
+        // Combined: addons are stored in `workspace.addons`
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        rc.add_or_update_addon(&Addon::from("love2d@1234567"));
+        rc.workspace_mut().library.push(".addons/love2d".to_string());
+        rc.write().unwrap();
+        assert!(!dir.join(LOCKFILE).exists());
+        let config = std::fs::read_to_string(dir.join(LUARC)).unwrap();
+        assert!(config.contains("\"addons\""), "{config}");
+
+        // Split: addons move into the lockfile
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        rc.set_use_lockfile(true);
+        rc.write().unwrap();
+        let config = std::fs::read_to_string(dir.join(LUARC)).unwrap();
+        assert!(!config.contains("\"addons\""), "{config}");
+        assert!(config.contains(".addons/love2d"), "{config}");
+        let lockfile = Lockfile::read(dir.join(LOCKFILE)).unwrap();
+        assert_eq!(lockfile.addons["love2d"], Addon::from("love2d@1234567"));
+
+        // The lockfile is picked up automatically
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert!(rc.uses_lockfile());
+        assert_eq!(rc.get_addons()["love2d"].checksum(), Some("1234567"));
+        rc.add_or_update_addon(&Addon::from("lovr"));
+        rc.write().unwrap();
+        assert!(Lockfile::read(dir.join(LOCKFILE)).unwrap().addons.contains_key("lovr"));
+        assert!(!std::fs::read_to_string(dir.join(LUARC)).unwrap().contains("lovr"));
+    }
+
+    #[test]
+    fn malformed_addons_names_the_key() {
+        let dir = TempDir::new();
+
+        for addons in [r#"["love2d"]"#, r#""love2d""#] {
+            std::fs::write(
+                dir.join(LUARC),
+                format!(r#"{{ "workspace": {{ "library": [], "addons": {addons} }} }}"#),
+            )
+            .unwrap();
+
+            let err = LuaRc::detect(&dir).unwrap_err();
+            assert!(matches!(&err, Error::InvalidConfig(path, _) if path == &dir.join(LUARC)));
+            let err = err.to_string();
+            assert!(err.contains("`workspace.addons`"), "{err}");
+            assert!(err.contains(LUARC), "{err}");
+        }
+    }
+
+    #[test]
+    fn reset_settings_are_not_written() {
+        let dir = TempDir::new();
+        std::fs::write(
+            dir.join(LUARC),
+            r#"{ "diagnostics": { "disable": ["lowercase-global"], "globals": ["love"] }, "doc": { "privateName": ["_*"] } }"#,
+        )
+        .unwrap();
+
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert!(rc.diagnostics.is_some() && rc.doc.is_some());
+        rc.diagnostics = None;
+        rc.doc = None;
+        rc.write().unwrap();
+
+        let config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(LUARC)).unwrap()).unwrap();
+        assert_eq!(config, serde_json::json!({}));
+    }
+
+    #[test]
+    fn load_and_save() {
+        let dir = TempDir::new();
+        let path = dir.join("config.json");
+        std::fs::write(&path, indoc::indoc! {r#"
+            {
+              "runtime": { "version": "LuaJIT" },
+              "diagnostics": { "globals": ["love"] },
+              "custom": true
+            }
+        "#}).unwrap();
+
+        let mut rc = LuaRc::load(&path).unwrap();
+        assert_eq!(rc.runtime.as_ref().and_then(|r| r.version.as_deref()), Some("LuaJIT"));
+        assert_eq!(rc.path(), path);
+
+        rc.diagnostics_mut().globals.push("lovr".to_string());
+        let copy = dir.join("copy.json");
+        rc.save(&copy).unwrap();
+
+        let reloaded = LuaRc::load(&copy).unwrap();
+        assert_eq!(reloaded.diagnostics.unwrap().globals, ["love", "lovr"]);
+        assert_eq!(reloaded.other.unwrap()["custom"], Value::Bool(true));
+
+        // Loading never creates files
+        assert!(LuaRc::load(dir.join("missing.json")).is_err());
+        assert!(!dir.join("missing.json").exists());
+        assert!(!dir.join(LUARC).exists());
+
+        let rc = LuaRc::detect_readonly(&dir).unwrap();
+        assert!(!rc.is_new());
+        assert_eq!(rc.path(), dir.join(LUARC));
+        assert!(!dir.join(LUARC).exists());
+    }
+
+    #[test]
+    fn paths_expand_but_serialize_literally() {
+        std::env::set_var("LLAM_TEST_LUA_HOME", "/opt/lua");
+        let mut rc = LuaRc::default();
+        rc.workspace_mut().library = vec!["$LLAM_TEST_LUA_HOME/library".to_string(), ".addons/love2d".to_string()];
+        rc.runtime_mut().path = vec!["${LLAM_TEST_LUA_HOME}/?.lua".to_string()];
+        rc.misc_mut().executable_path = Some("$LLAM_TEST_MISSING/lua-language-server".to_string());
+
+        assert_eq!(rc.library_paths(), ["/opt/lua/library", ".addons/love2d"]);
+        assert_eq!(rc.runtime_paths(), ["/opt/lua/?.lua"]);
+        assert_eq!(rc.executable_path().as_deref(), Some("$LLAM_TEST_MISSING/lua-language-server"));
+
+        let json = serde_json::to_string(&rc).unwrap();
+        assert!(json.contains("$LLAM_TEST_LUA_HOME/library"), "{json}");
+        assert!(json.contains("${LLAM_TEST_LUA_HOME}/?.lua"), "{json}");
+    }
+}
@@ -0,0 +1,259 @@
+//! JSON Schema for the parts of `.luarc.json` that llam adds on top of luals.
+//!
+//! The schema extends the luals schema, so it can be used as the `$schema` of a config that
+//! contains `workspace.addons` without editors flagging the addons.
+
+use serde_json::{json, Value};
+
+/// Schema luals publishes for the standard `.luarc.json` fields
+pub static LUALS_SCHEMA: &str =
+    "https://raw.githubusercontent.com/LuaLS/vscode-lua/master/setting/schema.json";
+
+/// Schema of a single entry in `workspace.addons`, see [`Addon`][crate::Addon]
+pub fn addon() -> Value {
+    json!({
+        "type": "object",
+        "description": "Addon installed by llam",
+        "required": ["src", "target"],
+        "properties": {
+            "src": {
+                "type": "string",
+                "description": "Name of a LuaCATS addon, `owner/repo` for github, a git url, or the url of a tarball"
+            },
+            "checkout": {
+                "description": "Branch, tag, or commit the addon is checked out to, a tarball's content hash is its commit",
+                "default": "default",
+                "oneOf": [
+                    { "type": "string", "enum": ["default"] },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "branch": { "type": "string", "minLength": 1 },
+                            "tag": { "type": "string", "minLength": 1 },
+                            "commit": { "type": "string", "pattern": "^[0-9a-fA-F]{7,40}$" }
+                        },
+                        "minProperties": 1,
+                        "maxProperties": 1,
+                        "additionalProperties": false
+                    }
+                ]
+            },
+            "checksum": {
+                "type": "string",
+                "deprecated": true,
+                "description": "Commit or tag the addon is pinned to, read from configs written before `checkout`"
+            },
+            "branch": {
+                "type": "string",
+                "deprecated": true,
+                "description": "Branch the addon is checked out to, read from configs written before `checkout`"
+            },
+            "target": {
+                "type": "string",
+                "enum": ["lua_cats", "github", "git", "archive"],
+                "description": "Where the addon is sourced from"
+            },
+            "alias": {
+                "type": "string",
+                "description": "Name the addon is installed and recorded as"
+            },
+            "depth": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Only clone and fetch this many commits of history"
+            },
+            "mirrors": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Urls of mirrors of the same repository, tried in order when the primary url fails"
+            },
+            "enabled": {
+                "type": "boolean",
+                "default": true,
+                "description": "Disabled addons stay installed but are not exposed to luals"
+            },
+            "dependency": {
+                "type": "boolean",
+                "default": false,
+                "description": "Whether the addon was only installed because another addon depends on it"
+            },
+            "pinned": {
+                "type": "boolean",
+                "default": false,
+                "description": "Pinned addons are skipped by `update` unless pinned addons are explicitly allowed"
+            },
+            "required_by": {
+                "type": "array",
+                "items": { "type": "string" },
+                "uniqueItems": true,
+                "description": "Names of the installed addons that depend on this addon"
+            },
+            "ignore_dirs": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Entries this addon added to `workspace.ignoreDir`, removed again with the addon"
+            }
+        },
+        "additionalProperties": false
+    })
+}
+
+/// Schema for `.luarc.json` including llam's extensions
+pub fn schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "llam .luarc.json",
+        "allOf": [
+            { "$ref": LUALS_SCHEMA },
+            {
+                "type": "object",
+                "properties": {
+                    "workspace": {
+                        "type": "object",
+                        "properties": {
+                            "addons": {
+                                "type": "object",
+                                "description": "Addons installed by llam",
+                                "additionalProperties": addon()
+                            },
+                            "profiles": {
+                                "type": "object",
+                                "description": "Named sets of addons installed by llam, selected with `--profile`",
+                                "additionalProperties": {
+                                    "type": "object",
+                                    "additionalProperties": addon()
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    /// Minimal validator for the keywords used by [`super::schema`]. References to external
+    /// schemas are not resolved.
+    fn validate(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in all {
+                validate(schema, value, path)?;
+            }
+        }
+
+        if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+            let matches = one.iter().filter(|schema| validate(schema, value, path).is_ok()).count();
+            if matches != 1 {
+                return Err(format!("{path}: matches {matches} of the `oneOf` schemas instead of one"));
+            }
+        }
+
+        if let Some(ty) = schema.get("type").and_then(Value::as_str) {
+            let valid = match ty {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "boolean" => value.is_boolean(),
+                _ => true,
+            };
+            if !valid {
+                return Err(format!("{path}: expected {ty}"));
+            }
+        }
+
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                return Err(format!("{path}: {value} is not one of {options:?}"));
+            }
+        }
+
+        let minimum = schema.get("minimum").and_then(Value::as_i64);
+        if let (Some(minimum), Some(number)) = (minimum, value.as_i64()) {
+            if number < minimum {
+                return Err(format!("{path}: {number} is less than {minimum}"));
+            }
+        }
+
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (i, item) in array.iter().enumerate() {
+                validate(items, item, &format!("{path}[{i}]"))?;
+            }
+        }
+
+        if let Some(object) = value.as_object() {
+            let min = schema.get("minProperties").and_then(Value::as_u64).unwrap_or(0);
+            let max = schema.get("maxProperties").and_then(Value::as_u64).unwrap_or(u64::MAX);
+            if !(min..=max).contains(&(object.len() as u64)) {
+                return Err(format!("{path}: expected {min} to {max} properties"));
+            }
+
+            for key in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if !object.contains_key(key.as_str().unwrap()) {
+                    return Err(format!("{path}: missing {key}"));
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, item) in object {
+                let path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property) => validate(property, item, &path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => return Err(format!("{path}: unknown property")),
+                        Some(additional) => validate(additional, item, &path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn schema_validates_addons() {
+        let schema = super::schema();
+        let config: Value = serde_json::from_str(indoc::indoc! {r#"
+            {
+                "runtime": { "version": "LuaJIT" },
+                "workspace": {
+                    "library": [".addons/love2d/library"],
+                    "addons": {
+                        "love2d": { "src": "love2d", "target": "lua_cats", "checkout": { "commit": "1234567" } },
+                        "legacy": { "src": "legacy", "target": "lua_cats", "checksum": "v1.0" },
+                        "lib": {
+                            "src": "https://example.com/lib.git",
+                            "target": "git",
+                            "checkout": { "branch": "main" },
+                            "depth": 1,
+                            "mirrors": ["https://mirror.example.com/lib.git"],
+                            "enabled": false,
+                            "required_by": ["love2d"]
+                        }
+                    }
+                }
+            }
+        "#})
+        .unwrap();
+        validate(&schema, &config, "$").unwrap();
+
+        // Every addon llam writes matches the schema
+        let addon = serde_json::to_value(crate::Addon::from("love2d@1234567")).unwrap();
+        validate(&super::addon(), &addon, "$").unwrap();
+
+        let invalid = |addon: Value| serde_json::json!({ "workspace": { "addons": { "love2d": addon } } });
+        let unknown_target = invalid(serde_json::json!({ "src": "love2d", "target": "svn" }));
+        assert!(validate(&schema, &unknown_target, "$").is_err());
+        let missing_src = invalid(serde_json::json!({ "target": "lua_cats" }));
+        assert!(validate(&schema, &missing_src, "$").is_err());
+        for checkout in [serde_json::json!("main"), serde_json::json!({}), serde_json::json!({ "branch": "main", "tag": "v1" })] {
+            let invalid_checkout = invalid(serde_json::json!({ "src": "love2d", "target": "lua_cats", "checkout": checkout }));
+            assert!(validate(&schema, &invalid_checkout, "$").is_err(), "{checkout}");
+        }
+    }
+}
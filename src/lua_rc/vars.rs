@@ -0,0 +1,93 @@
+//! Environment variable expansion for path settings.
+//!
+//! Paths like `workspace.library` can reference `$HOME`, `${HOME}`, or `%USERPROFILE%` so a
+//! config can be shared between machines. The literal value is what is stored in the config,
+//! expansion only happens when a path is used.
+
+use std::borrow::Cow;
+
+fn is_name(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Expand `$VAR`, `${VAR}`, and `%VAR%` using the current environment
+///
+/// References to variables that are not set are left as is.
+pub fn expand_vars(value: &str) -> Cow<'_, str> {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+/// Same as [`expand_vars`] but looks variables up with `lookup`
+pub fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Cow<'_, str> {
+    if !value.contains(['$', '%']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find(['$', '%']) {
+        result.push_str(&rest[..start]);
+        let marker = &rest[start..];
+
+        // (variable name, length of the whole reference)
+        let reference = if let Some(braced) = marker.strip_prefix("${") {
+            braced
+                .find('}')
+                .map(|end| (&braced[..end], end + 3))
+                .filter(|(name, _)| !name.is_empty() && name.chars().all(is_name))
+        } else if let Some(plain) = marker.strip_prefix('$') {
+            let end = plain.find(|c| !is_name(c)).unwrap_or(plain.len());
+            Some((&plain[..end], end + 1)).filter(|(name, _)| !name.is_empty())
+        } else {
+            let windows = &marker[1..];
+            windows
+                .find('%')
+                .map(|end| (&windows[..end], end + 2))
+                .filter(|(name, _)| !name.is_empty() && name.chars().all(is_name))
+        };
+
+        match reference.and_then(|(name, len)| lookup(name).map(|value| (value, len))) {
+            Some((value, len)) => {
+                result.push_str(&value);
+                rest = &marker[len..];
+            }
+            None => {
+                result.push_str(&marker[..1]);
+                rest = &marker[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/user".to_string()),
+            "USERPROFILE" => Some("C:\\Users\\user".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expansion() {
+        assert_eq!(expand_with("$HOME/lua", lookup), "/home/user/lua");
+        assert_eq!(expand_with("${HOME}lua", lookup), "/home/userlua");
+        assert_eq!(expand_with("%USERPROFILE%\\lua", lookup), "C:\\Users\\user\\lua");
+        assert_eq!(expand_with("$HOME-$HOME", lookup), "/home/user-/home/user");
+        assert!(matches!(expand_with(".addons/love2d", lookup), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn missing_vars_are_kept() {
+        assert_eq!(expand_with("$MISSING/lua", lookup), "$MISSING/lua");
+        assert_eq!(expand_with("${MISSING}/lua", lookup), "${MISSING}/lua");
+        assert_eq!(expand_with("%MISSING%/lua", lookup), "%MISSING%/lua");
+        assert_eq!(expand_with("100% $ {HOME}", lookup), "100% $ {HOME}");
+    }
+}
@@ -0,0 +1,88 @@
+//! The lua language server (luals) that loads the installed addons.
+
+use std::{ffi::OsStr, process::Command, str::FromStr};
+
+/// Executable used when the config doesn't set `misc.executablePath`
+pub static LUALS_BINARY: &str = "lua-language-server";
+
+/// Dotted numeric version such as `3.7.4`
+///
+/// Trailing zero components are ignored when comparing, so `3.7` and `3.7.0` are equal.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(Vec<u64>);
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim().trim_start_matches('v');
+        // Ignore pre-release and build suffixes, e.g. `3.7.4-dev`
+        let numeric = trimmed.split(['-', '+']).next().unwrap_or_default();
+
+        let mut parts = numeric
+            .split('.')
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid version `{s}`, expected e.g. `3.7.4`"))?;
+        while parts.len() > 1 && parts.last() == Some(&0) {
+            parts.pop();
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = self.0.iter().map(|part| part.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+/// Version reported by `<binary> --version`, `None` when it can't be run or the output isn't
+/// understood
+pub fn detect(binary: impl AsRef<OsStr>) -> Option<Version> {
+    let output = Command::new(binary).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find_map(|word| Version::from_str(word).ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn compare_versions() {
+        let version = |s: &str| Version::from_str(s).unwrap();
+
+        assert!(version("3.7.4") < version("3.8.0"));
+        assert!(version("3.10") > version("3.9.9"));
+        assert!(version("v3.7.4") > version("3.7"));
+        assert_eq!(version("3.7"), version("3.7.0"));
+        assert_eq!(version("3.7.4-dev"), version("3.7.4"));
+        assert_eq!(version("3.7.0").to_string(), "3.7");
+
+        for invalid in ["", "three", "3..4", "3.x"] {
+            assert!(Version::from_str(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detect_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let binary = dir.join(LUALS_BINARY);
+        std::fs::write(&binary, "#!/bin/sh\necho \"lua-language-server 3.7.4\"\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(detect(&binary), Some(Version(vec![3, 7, 4])));
+        assert_eq!(detect(dir.join("missing")), None);
+    }
+}
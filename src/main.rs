@@ -1,44 +1,232 @@
-use std::time::Duration;
+use std::{io::{stderr, stdin, stdout, IsTerminal, Write}, time::{Duration, SystemTime}};
 
 use clap::Parser;
 
 use llam::{
-    cli::{Config, DiagnosticSetting, DocSetting, Subcommand, LLAM}, frames, logging::{colors, Spinner, Stream}, Error, Manager
+    cache::{RemoteCache, CACHE_FILE}, cli::{Config, DiagnosticSetting, DocSetting, Subcommand, LLAM}, frames, git::Cli, lua_rc::schema, shared, timings::Timings, logging::{self, colors, Logger, Spinner, Stream}, output::{self, AddonList, AddonSummary, Format}, lua_rc::LuaRc, registry, temp_clone_root, AddOptions, Addon, Error, Manager, LUARC
 };
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() {
     let llam = LLAM::parse();
+    let json_errors = llam.json_errors;
+    let color = llam.color;
+    // `update --check` reserves 1 for "updates available", so any failure exits with 2
+    let check = matches!(llam.command, Subcommand::Update { check: true, .. });
 
+    match run(llam).await {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            if json_errors {
+                eprintln!("{}", err.to_json());
+            } else {
+                // The spinner of the command has stopped, this one only prints the error
+                let mut logger = Spinner::new_with_color(Stream::Stderr, frames!(["✕"], Duration::from_secs(1)), color);
+                logging::fatal(&mut logger, err);
+            }
+            std::process::exit(if check { 2 } else { 1 });
+        }
+    }
+}
+
+/// Run the command, returning the code the process should exit with
+async fn run(llam: LLAM) -> Result<i32, Error> {
     let path = llam.path.unwrap_or(std::env::current_dir()?);
-    if !path.exists() {
-        return Err(Error::custom(format!(
-            "the project path does not exist: {path:?}"
-        )));
+
+    // Doesn't need a project, and shouldn't create a config in one
+    if let Subcommand::Schema { output } = &llam.command {
+        let schema = serde_json::to_string_pretty(&schema::schema())?;
+        match output {
+            Some(path) => std::fs::write(path, schema)?,
+            None => println!("{schema}"),
+        }
+        return Ok(0);
     }
 
-    let mut manager = Manager::new(
-        path,
-        Spinner::new(
+    let spinner = || {
+        Spinner::new_with_color(
             Stream::Stdout,
             frames!(
                 ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
                 Duration::from_millis(80),
                 colors::xterm::PaleGoldenrod
-            )
+            ),
+            llam.color,
         )
-    )?;
+    };
+    // Read-only commands shouldn't create a config in a project that doesn't have one
+    let readonly = matches!(
+        llam.command,
+        Subcommand::List { .. }
+            | Subcommand::Info { .. }
+            | Subcommand::Update { check: true, .. }
+            | Subcommand::Config { subcommand: Config::Diagnostic { setting: DiagnosticSetting::List } }
+    );
+    let new = |spinner| match readonly {
+        true => Manager::new_readonly(&path, spinner),
+        false => Manager::new(&path, spinner),
+    };
+    let (manager, recover) = match new(spinner()) {
+        Ok(manager) => (manager, false),
+        Err(Error::InvalidConfig(config, message)) if confirm_reinitialize(&message) => {
+            let backup = LuaRc::backup(&config)?;
+            eprintln!("moved {} to {}", config.display(), backup.display());
+            (Manager::new(&path, spinner())?, true)
+        }
+        Err(err) => return Err(err),
+    };
+    let mut manager = manager.with_git(
+        Cli::builder()
+            .binary(llam.git_binary)
+            .fallback_branches(llam.fallback_branches)
+            .quiet(llam.quiet_git)
+            .build(),
+    );
+    manager.timings = Timings::new(llam.timings);
+    manager.prefer_branch = llam.prefer_branch;
+    manager.rc.set_frozen(llam.frozen);
+    if llam.lockfile {
+        manager.rc.set_use_lockfile(true);
+    }
+    manager.rc.set_profile(llam.profile);
+    manager.commit = llam.commit;
+    manager.depth = llam.depth;
+    manager.remove_threads = llam.parallel_remove;
+    manager.addons_dir = llam.addons_dir;
+    manager.lfs = llam.lfs;
+    if recover {
+        manager.recover_addons()?;
+    }
+    if llam.shared {
+        manager.shared_clones = Some(llam.shared_dir.or_else(shared::default_dir).ok_or_else(|| {
+            Error::custom("unable to determine the shared clone directory, set --shared-dir")
+        })?);
+    }
+    // Only checking for updates and cleaning stale entries use the cache
+    let cached = matches!(
+        llam.command,
+        Subcommand::Update { check: true, .. } | Subcommand::Clean { older_than: Some(_), .. }
+    );
+    if cached && !llam.no_cache {
+        manager.cache = Some(RemoteCache::load(
+            manager.base.join(CACHE_FILE),
+            Duration::from_secs(llam.cache_ttl),
+        ));
+    }
 
+    let mut report = None;
+    let mut code = 0;
     match llam.command {
-        Subcommand::Add { addons } => manager.add(addons)?,
-        Subcommand::Remove(addons) => manager.remove(addons)?,
-        Subcommand::Update(addons) => manager.update(addons)?,
-        Subcommand::Clean => manager.clean()?,
-        Subcommand::List => for (name, addon) in manager.rc.get_addons() {
-            println!("  {name}: {:?}", addon.target);
-        },
+        Subcommand::Add {
+            mut addons,
+            no_update_luarc,
+            gitignore,
+            no_schema,
+            strict,
+            update,
+            force,
+            target,
+            name,
+            interactive,
+        } => {
+            if interactive {
+                addons.push(pick_addon(&mut manager.logger).await?);
+            }
+            if let Some(name) = name {
+                let [addon] = addons.as_mut_slice() else {
+                    return Err(Error::custom("--name can only be used when adding a single addon"));
+                };
+                addon.alias = Some(name);
+            }
+            manager.luarc_schema = !no_schema;
+            let target = target.target();
+            for addon in addons.iter_mut() {
+                addon.depth = addon.depth.or(llam.depth);
+                if let Some(target) = target.clone() {
+                    addon.checkout = target;
+                }
+            }
+            report = Some(manager.add(
+                addons,
+                AddOptions {
+                    update_luarc: !no_update_luarc,
+                    gitignore,
+                    strict,
+                    update,
+                    force,
+                },
+            )?)
+        }
+        Subcommand::Remove(addons) => report = Some(manager.remove(addons)?),
+        Subcommand::Update { addons, check: true, .. } => {
+            code = manager.check_updates(addons).exit_code();
+        }
+        Subcommand::Update { addons, force_pinned, to: Some(reference), .. } => {
+            manager.force_pinned = force_pinned;
+            report = Some(manager.update_to(addons, &reference)?)
+        }
+        Subcommand::Update { addons, keep_local_branch, force_pinned, .. } => {
+            manager.keep_local_branch = keep_local_branch;
+            manager.force_pinned = force_pinned;
+            report = Some(manager.update(addons)?)
+        }
+        Subcommand::Reinstall(addons) => report = Some(manager.reinstall(addons)?),
+        Subcommand::Gc(addons) => {
+            manager.gc(addons)?;
+        }
+        Subcommand::Doctor { repair } => report = Some(manager.doctor(repair)?),
+        Subcommand::Clean { older_than: Some(age), .. } => {
+            manager.clean_stale(&temp_clone_root(), age, SystemTime::now())?;
+        }
+        Subcommand::Clean { exclude, dry_run: true, .. } => {
+            manager.clean_dry_run(&exclude)?;
+        }
+        Subcommand::Clean { yes, exclude, .. } => manager.clean(yes, &exclude)?,
+        Subcommand::Enable { names } => manager.set_enabled(names, true)?,
+        Subcommand::Disable { names } => manager.set_enabled(names, false)?,
+        Subcommand::Pin { names } => manager.set_pinned(names, true)?,
+        Subcommand::Unpin { names } => manager.set_pinned(names, false)?,
+        Subcommand::Schema { .. } => unreachable!("handled before the manager is created"),
+        Subcommand::Info { name, raw, format } => {
+            manager.logger.finish();
+            let addon = manager
+                .rc
+                .get_addons()
+                .get(name.as_str())
+                .ok_or_else(|| Error::custom(format!("`{name}` is not installed")))?;
+            print!("{}", output::info(&name, addon, raw, format)?);
+        }
+        Subcommand::List { format: Format::Human, tree: true } => {
+            manager.logger.finish();
+            print!("{}", output::tree(manager.rc.get_addons()));
+        }
+        Subcommand::List { format, .. } => {
+            manager.logger.finish();
+            let list = AddonList {
+                addons: manager
+                    .rc
+                    .get_addons()
+                    .iter()
+                    .map(|(name, addon)| AddonSummary::new(name, addon))
+                    .collect(),
+            };
+            match format.render(&list)? {
+                Some(output) => println!("{output}"),
+                None => print!("{}", list.human()),
+            }
+        }
         Subcommand::Config { subcommand } => match subcommand {
             Config::Doc { setting } => match setting {
+                DocSetting::Reset { yes } => {
+                    if !yes {
+                        return Err(Error::custom(format!(
+                            "this removes every doc setting from {LUARC}; re-run with `--yes` to confirm"
+                        )));
+                    }
+                    manager.rc.doc = None;
+                    manager.rc.write()?;
+                }
                 DocSetting::Package { patterns } => {
                     match manager.rc.doc.as_mut() {
                         Some(d) => d.package_name.extend(patterns),
@@ -76,7 +264,35 @@ async fn main() -> Result<(), Error> {
                     manager.rc.write()?;
                 }
             },
+            Config::Hover { setting } => {
+                setting.apply(manager.rc.hover_mut());
+                manager.rc.write()?;
+            }
+            Config::Semantic { setting } => {
+                setting.apply(manager.rc.semantic_mut());
+                manager.rc.write()?;
+            }
+            Config::Workspace { setting } => {
+                setting.apply(manager.rc.workspace_mut());
+                manager.rc.write()?;
+            }
+            Config::Misc { setting } => {
+                setting.apply(manager.rc.misc_mut());
+                manager.rc.write()?;
+            }
+            Config::Spell { setting } => {
+                setting.apply(manager.rc.spell_mut());
+                manager.rc.write()?;
+            }
+            Config::Format { setting } => {
+                setting.apply(manager.rc.format_mut());
+                manager.rc.write()?;
+            }
             Config::Diagnostic { setting } => match setting {
+                DiagnosticSetting::List => {
+                    manager.logger.finish();
+                    print!("{}", output::diagnostics(manager.rc.diagnostics.as_ref().unwrap_or(&Default::default())));
+                }
                 DiagnosticSetting::Disable { diagnostics } => {
                     match manager.rc.diagnostics.as_mut() {
                         Some(d) => d.disable.extend(diagnostics),
@@ -96,15 +312,7 @@ async fn main() -> Result<(), Error> {
                     }
                 }
                 DiagnosticSetting::AddGlobal { globals } => {
-                    match manager.rc.diagnostics.as_mut() {
-                        Some(d) => d.globals.extend(globals),
-                        None => {
-                            manager.rc.diagnostics = Some(llam::lua_rc::Diagnostics {
-                                globals,
-                                ..Default::default()
-                            })
-                        }
-                    }
+                    manager.rc.diagnostics_mut().add_globals(globals);
                     manager.rc.write()?;
                 }
                 DiagnosticSetting::RemoveGlobal { globals } => {
@@ -113,7 +321,30 @@ async fn main() -> Result<(), Error> {
                         manager.rc.write()?;
                     }
                 }
-                DiagnosticSetting::Severity { severity } => {
+                DiagnosticSetting::Reset { yes } => {
+                    if !yes {
+                        return Err(Error::custom(format!(
+                            "this removes every diagnostic setting from {LUARC}; re-run with `--yes` to confirm"
+                        )));
+                    }
+                    manager.rc.diagnostics = None;
+                    manager.rc.write()?;
+                }
+                DiagnosticSetting::WorkspaceDelay { ms } => {
+                    manager.rc.diagnostics_mut().workspace_delay = ms;
+                    manager.rc.write()?;
+                }
+                DiagnosticSetting::WorkspaceRate { percent } => {
+                    manager.rc.diagnostics_mut().workspace_rate = percent;
+                    manager.rc.write()?;
+                }
+                DiagnosticSetting::Severity { severity, from } => {
+                    if let Some(path) = from {
+                        let policy = std::fs::read_to_string(&path)?;
+                        manager.rc.diagnostics_mut().import_severities(&policy).map_err(|err| {
+                            Error::custom(format!("invalid severity policy {}:\n{err}", path.display()))
+                        })?;
+                    }
                     match manager.rc.diagnostics.as_mut() {
                         Some(d) => d
                             .severity
@@ -131,5 +362,45 @@ async fn main() -> Result<(), Error> {
         },
     }
 
-    Ok(())
+    // Nothing printed from here on can interleave with the logger's output
+    manager.logger.finish();
+
+    if let (Some(path), Some(report)) = (llam.report, report) {
+        report.write(path)?;
+    }
+
+    if manager.timings.is_enabled() {
+        println!("{}", manager.timings);
+    }
+
+    Ok(code)
+}
+
+/// Ask for a search query, then for one of the matching LuaCATS addons and what to check out
+async fn pick_addon(logger: &mut impl Logger) -> Result<Addon, Error> {
+    if !stdin().is_terminal() || !stdout().is_terminal() {
+        return Err(Error::custom("--interactive can only be used in a terminal"));
+    }
+
+    let query = logger.prompt("Search LuaCATS addons: ")?;
+    let repositories = registry::search(query.trim()).await?;
+    if repositories.is_empty() {
+        return Err(Error::custom(format!("no LuaCATS addons match `{}`", query.trim())));
+    }
+    let choice = logger.prompt(format!("{}Addon to add: ", registry::numbered(&repositories)))?;
+    let reference = logger.prompt("Branch, tag, or commit [default branch]: ")?;
+    registry::select(&repositories, &choice, &reference)
+}
+
+/// Ask whether a config that can't be parsed should be backed up and created again, never when
+/// running non-interactively
+fn confirm_reinitialize(message: &str) -> bool {
+    if !stdin().is_terminal() || !stderr().is_terminal() {
+        return false;
+    }
+
+    eprint!("{message}\nBack up the config and create a new one from the installed addons? [y/N] ");
+    let _ = stderr().flush();
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
 }
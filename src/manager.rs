@@ -1,12 +1,25 @@
 use std::{
-    borrow::Cow,
+    cell::OnceCell,
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::{
-    git::{Cli, ResetType}, logging::{Logger, OrLog, Spinner}, lua_rc::{LuaRc, Workspace}, Addon, Error, ADDONS_DIR
+    addon_config::{AddonConfig, ADDON_CONFIG, LIBRARY_DIR}, archive, cache::RemoteCache, disk, git::{self, Cli, FetchOptions, ResetType}, gitignore::{self, GITIGNORE}, logging::{Logger, NullLogger, OrLog}, lua_rc::{expand_vars, schema::LUALS_SCHEMA, LuaRc, Workspace}, luals::{self, Version, LUALS_BINARY}, report::{OperationReport, Outcome}, shared, timings::Timings, Addon, AddonTarget, Error, Target, ADDONS_DIR, LUARC
 };
 
+/// State of an addon before an update, restored when the update fails
+struct Snapshot {
+    branch: String,
+    checksum: String,
+    addon: Option<Addon>,
+}
+
+/// Number of unknown addon directories `clean` will remove without explicit confirmation
+pub const CLEAN_CONFIRM_THRESHOLD: usize = 5;
+
 pub enum SomeOrAll<S> {
     Some(Vec<S>),
     All
@@ -26,108 +39,725 @@ impl<S> From<Vec<S>> for SomeOrAll<S> {
     }
 }
 
+/// Options that change how [`Manager::add`] installs addons
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddOptions {
+    /// Record the addons in `.luarc.json`. When disabled the addons are only cloned.
+    pub update_luarc: bool,
+    /// Add the addons directory to the project's `.gitignore`
+    pub gitignore: bool,
+    /// Fail addons that don't look like a luals addon instead of warning about them
+    pub strict: bool,
+    /// Update requested addons that are already installed instead of only reporting that an
+    /// update is available
+    pub update: bool,
+    /// Install addons again when the same repository is already installed under another name
+    pub force: bool,
+}
+
+impl Default for AddOptions {
+    fn default() -> Self {
+        Self {
+            update_luarc: true,
+            gitignore: false,
+            strict: false,
+            update: false,
+            force: false,
+        }
+    }
+}
+
+/// What [`Manager::clean`] removes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CleanPlan {
+    /// Addon directories that are not in the config
+    pub dirs: Vec<PathBuf>,
+    /// Config entries that point at missing or removed addon directories
+    pub entries: Vec<String>,
+}
+
+/// An addon that is not at the revision it should be
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outdated {
+    pub name: String,
+    /// Checked out commit, `None` when the addon isn't installed
+    pub current: Option<String>,
+    /// Commit the addon would be updated to, `None` when it isn't known locally
+    pub latest: Option<String>,
+}
+
+impl std::fmt::Display for Outdated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let short = |value: &Option<String>| match value.as_deref() {
+            Some(value) => value[..value.len().min(7)].to_string(),
+            None => "?".to_string(),
+        };
+
+        match self.current {
+            None => write!(f, "{}: not installed", self.name),
+            Some(_) => write!(f, "{}: {} -> {}", self.name, short(&self.current), short(&self.latest)),
+        }
+    }
+}
+
+/// Result of [`Manager::check_updates`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UpdateCheck {
+    pub outdated: Vec<Outdated>,
+    pub errors: Vec<(String, String)>,
+}
+
+impl UpdateCheck {
+    /// `0` when every addon is current, `1` when updates are available, and `2` when an
+    /// addon could not be checked
+    pub fn exit_code(&self) -> i32 {
+        if !self.errors.is_empty() {
+            2
+        } else if !self.outdated.is_empty() {
+            1
+        } else {
+            0
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Manager<L: Logger = Spinner> {
+pub struct Manager<L: Logger = NullLogger> {
     pub base: PathBuf,
     pub rc: LuaRc,
+    pub git: Cli,
+    pub timings: Timings,
+    /// Cache of remote checksums used when checking for updates, disabled when `None`
+    pub cache: Option<RemoteCache>,
+    /// Branch to check out for addons that don't specify one, if it exists on the remote
+    pub prefer_branch: Option<String>,
+    /// Commit `.luarc.json` to the project's repository after it is written
+    pub commit: bool,
+    /// Leave addons without a target on a locally checked out branch instead of switching them
+    /// back to the default branch during `update`
+    pub keep_local_branch: bool,
+    /// Update pinned addons that are named explicitly instead of skipping them
+    pub force_pinned: bool,
+    /// Clone and fetch depth used for every addon, overriding each addon's own depth
+    pub depth: Option<u32>,
+    /// Number of addon directories removed at the same time by `remove` and `clean`
+    pub remove_threads: usize,
+    /// Set the luals settings schema as the `$schema` of a config created by `add`
+    pub luarc_schema: bool,
+    /// Download the git-lfs files of addons whose `.gitattributes` use git-lfs after cloning them
+    pub lfs: bool,
+    /// Directory addons are installed in, relative to `base` unless it is absolute
+    pub addons_dir: PathBuf,
+    /// Clone addons once into this directory and check them out into projects as worktrees,
+    /// see [`shared`]
+    pub shared_clones: Option<PathBuf>,
+    /// Installed luals version, detected the first time an addon declares a minimum version
+    luals_version: OnceCell<Option<Version>>,
 
     pub logger: L
 }
 
+/// Error for a project directory that doesn't exist, showing the path as it would be typed
+fn project_exists(path: &Path) -> Result<&Path, Error> {
+    if path.exists() {
+        return Ok(path);
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    Err(Error::custom(format!("the project path does not exist: {}", absolute.display())))
+}
+
 impl<L: Logger> Manager<L> {
+    /// Manage the addons of the project in `dir`, creating its `.luarc.json` when it doesn't
+    /// have one
+    ///
+    /// Use [`NullLogger`] when embedding the manager and the progress shouldn't be printed.
     pub fn new(dir: impl AsRef<Path>, logger: L) -> Result<Self, Error> {
-        let path = dir.as_ref();
-        Ok(Self {
-            rc: LuaRc::detect(path)?,
+        let path = project_exists(dir.as_ref())?;
+        Ok(Self::with_rc(LuaRc::detect(path)?, path, logger))
+    }
+
+    /// Same as [`new`][Manager::new] without creating a `.luarc.json` when the project doesn't
+    /// have one, for commands that only read the config, see [`LuaRc::detect_readonly`]
+    pub fn new_readonly(dir: impl AsRef<Path>, logger: L) -> Result<Self, Error> {
+        let path = project_exists(dir.as_ref())?;
+        Ok(Self::with_rc(LuaRc::detect_readonly(path)?, path, logger))
+    }
+
+    fn with_rc(rc: LuaRc, path: &Path, logger: L) -> Self {
+        Self {
+            rc,
             base: path.to_path_buf(),
+            git: Cli::default(),
+            timings: Timings::default(),
+            cache: None,
+            prefer_branch: None,
+            commit: false,
+            keep_local_branch: false,
+            force_pinned: false,
+            depth: None,
+            remove_threads: disk::REMOVE_THREADS,
+            luarc_schema: true,
+            lfs: false,
+            addons_dir: PathBuf::from(ADDONS_DIR),
+            shared_clones: None,
+            luals_version: OnceCell::new(),
 
             logger,
-        })
+        }
+    }
+
+    /// Directory addons are installed in
+    pub fn addons_path(&self) -> PathBuf {
+        self.base.join(&self.addons_dir)
     }
 
-    pub fn clone_addon(&mut self, name: Cow<'static, str>) -> Result<(), Error> {
-        // PERF: Return error or log when addon is not in lock file
-        if let Some(addon) = self.rc.get_addons().get(&name) {
-            let temp_name = addon
-                .checksum
-                .clone()
-                .unwrap_or(uuid::Uuid::now_v7().to_string());
-            let from = std::env::temp_dir().join(&temp_name);
-            let to = self.base.join(ADDONS_DIR).join(addon.name().as_ref());
+    /// How the addons directory is referenced in the config, relative to the project when it is
+    /// inside of it and absolute otherwise
+    fn addons_entry(&self) -> String {
+        let dir = self.addons_path();
+        let dir = dir.strip_prefix(&self.base).unwrap_or(&dir);
+        dir.to_string_lossy().replace('\\', "/")
+    }
 
-            if let Err(err) = Cli::clone(std::env::temp_dir(), addon.clone_url(), &temp_name) {
-                if from.exists() {
-                    std::fs::remove_dir_all(&from)?;
-                }
+    /// Use a specific git executable for all git operations
+    pub fn with_git(mut self, git: Cli) -> Self {
+        self.git = git;
+        self
+    }
+
+    /// Use a known luals version instead of detecting it, `None` skips the version checks
+    pub fn with_luals_version(self, version: Option<Version>) -> Self {
+        let _ = self.luals_version.set(version);
+        self
+    }
+
+    /// Version of the configured `misc.executablePath` or the luals on the `PATH`
+    fn luals_version(&self) -> Option<&Version> {
+        self.luals_version
+            .get_or_init(|| luals::detect(self.rc.executable_path().unwrap_or_else(|| LUALS_BINARY.to_string())))
+            .as_ref()
+    }
+
+    pub fn clone_addon(&mut self, addon: &Addon) -> Result<(), Error> {
+        if addon.target == Target::Archive {
+            let hash = self.install_archive(addon).inspect_err(|err| self.logger.error(err))?;
+            if let Some(entry) = self.rc.get_addons_mut().get_mut(addon.name().as_ref()) {
+                entry.checkout = AddonTarget::Commit(hash);
+            }
+            return Ok(());
+        }
+
+        if let Some(root) = self.shared_clones.clone() {
+            self.checkout_shared(addon, &root)?;
+            self.pull_lfs(addon);
+            return Ok(());
+        }
+
+        let from = temp_clone_dir(addon);
+        let to = self.addons_path().join(addon.name().as_ref());
+
+        let temp_root = from.parent().unwrap();
+        if !temp_root.exists() {
+            std::fs::create_dir_all(temp_root)?;
+        }
+
+        for dir in [temp_root, to.parent().unwrap()] {
+            if let Err(err) = disk::check_space(dir, disk::available_space(dir), disk::MIN_FREE_SPACE) {
+                self.logger.error(&err);
                 return Err(err);
             }
+        }
+
+        let temp_name = from.file_name().unwrap().to_string_lossy();
+        let name = addon.name();
+        let depth = self.depth.or(addon.depth);
+        let urls = addon.clone_urls();
+        let mut result = Err(Error::custom(format!("no url to clone {name} from")));
+        for (i, url) in urls.iter().enumerate() {
+            result = self.timings.time(&name, "clone", || {
+                self.git.clone_with_depth(temp_root, url, &temp_name, depth, |percent| {
+                    self.logger.update(format!("Cloning {name} {percent}%"))
+                })
+            });
+            // A mirror has to be the same repository, so it must have the recorded commit
+            if let (Ok(()), Some(checksum), true) = (&result, addon.checksum(), i > 0) {
+                if self.git.resolve(&from, checksum).is_err() {
+                    result = Err(Error::custom(format!("mirror {url} doesn't have `{checksum}`")));
+                }
+            }
+
+            match result.as_ref() {
+                Ok(()) => {
+                    if i > 0 {
+                        self.logger.warning(format!("[{name}] cloned from mirror {url}"));
+                    }
+                    break;
+                }
+                Err(err) => {
+                    if from.exists() {
+                        std::fs::remove_dir_all(&from)?;
+                    }
+                    if i + 1 < urls.len() {
+                        self.logger.warning(format!(
+                            "[{name}] failed to clone {url}, trying the next mirror: {err}"
+                        ));
+                    }
+                }
+            }
+        }
+        result?;
+
+        if to.exists() {
+            std::fs::remove_dir_all(&to)?;
+        }
 
-            if to.exists() {
-                std::fs::remove_dir_all(&to)?;
+        if let Some(parent) = to.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
             }
+        }
+        std::fs::rename(from, to)?;
+        self.pull_lfs(addon);
+
+        Ok(())
+    }
 
-            if let Some(parent) = to.parent() {
-                if !parent.exists() {
+    /// Download and extract an addon distributed as a tarball into the addons directory,
+    /// returning the content hash of the tarball
+    ///
+    /// The hash is verified against the addon's checksum when it has one. An installed copy is
+    /// only replaced once the new tarball is verified and extracted.
+    fn install_archive(&mut self, addon: &Addon) -> Result<String, Error> {
+        let name = addon.name();
+        let temp = temp_clone_dir(addon);
+        std::fs::create_dir_all(&temp)?;
+
+        let tarball = temp.join(format!("{name}.tar"));
+        self.logger.update(format!("[{name}] Downloading {}", addon.src));
+        let result = self
+            .timings
+            .time(&name, "download", || archive::download(&addon.src, &tarball))
+            .and_then(|_| self.git.hash_object(&tarball))
+            .and_then(|hash| match addon.checksum() {
+                Some(checksum) if checksum != hash => Err(Error::custom(format!(
+                    "[{name}] archive hash `{hash}` doesn't match `{checksum}`"
+                ))),
+                _ => Ok(hash),
+            })
+            .and_then(|hash| {
+                self.logger.update(format!("[{name}] Extracting archive"));
+                let from = archive::extract(&tarball, &temp.join("files"))?;
+                std::fs::write(from.join(archive::HASH_FILE), format!("{hash}\n"))?;
+
+                let to = self.addons_path().join(name.as_ref());
+                if to.exists() {
+                    std::fs::remove_dir_all(&to)?;
+                }
+                if let Some(parent) = to.parent() {
                     std::fs::create_dir_all(parent)?;
                 }
+                std::fs::rename(from, to)?;
+                Ok(hash)
+            });
+
+        std::fs::remove_dir_all(&temp).ok();
+        result
+    }
+
+    /// Replace the git-lfs pointer files of a cloned addon with their contents when enabled
+    ///
+    /// Failures only warn, the addon is still usable apart from the files stored in git-lfs.
+    fn pull_lfs(&mut self, addon: &Addon) {
+        let name = addon.name();
+        let path = self.addons_path().join(name.as_ref());
+        if !self.lfs || !git::uses_lfs(&path) {
+            return;
+        }
+
+        if !self.git.lfs_installed() {
+            self.logger.warning(format!("[{name}] uses git-lfs but git-lfs isn't installed"));
+            return;
+        }
+
+        self.logger.update(format!("[{name}] Pulling git-lfs files"));
+        if let Err(err) = self.timings.time(&name, "lfs", || self.git.lfs_pull(&path)) {
+            self.logger.warning(format!("[{name}] {err}"));
+        }
+    }
+
+    /// Fetch an installed addon from its remote, falling back to its mirrors when that fails
+    fn fetch_addon(
+        &mut self,
+        name: &str,
+        path: &Path,
+        options: &FetchOptions,
+        mirrors: &[String],
+    ) -> Result<(), Error> {
+        let mut result = self.timings.time(name, "fetch", || self.git.fetch_with(path, options));
+        for mirror in mirrors {
+            let Err(err) = result.as_ref() else {
+                break;
+            };
+            self.logger.warning(format!("[{name}] failed to fetch, trying mirror {mirror}: {err}"));
+
+            let options = FetchOptions { url: Some(mirror.clone()), ..options.clone() };
+            result = self.timings.time(name, "fetch", || self.git.fetch_with(path, &options));
+            if result.is_ok() {
+                self.logger.warning(format!("[{name}] fetched from mirror {mirror}"));
+            }
+        }
+        result
+    }
+
+    /// Fetch an addon unless its repository was already fetched with the same options, e.g. when
+    /// addons are worktrees of the same shared clone
+    fn fetch_once(
+        &mut self,
+        fetched: &mut HashSet<(PathBuf, bool, Option<u32>)>,
+        name: &str,
+        path: &Path,
+        options: &FetchOptions,
+        mirrors: &[String],
+    ) -> Result<(), Error> {
+        let repository = self.git.common_dir(path).ok();
+        if let Some(repository) = repository.as_ref() {
+            if fetched.contains(&(repository.clone(), options.tags, options.depth)) {
+                return Ok(());
             }
-            std::fs::rename(from, to)?;
         }
 
+        self.fetch_addon(name, path, options, mirrors)?;
+        if let Some(repository) = repository {
+            fetched.insert((repository, options.tags, options.depth));
+        }
         Ok(())
     }
 
-    pub fn add(&mut self, addons: impl IntoIterator<Item=Addon>) -> Result<(), Error> {
-        let addons = addons.into_iter().collect::<Vec<_>>();
-        let total = addons.len().to_string();
+    /// Check out an addon from its clone in `root` as a worktree, cloning it first when no project
+    /// has used it yet
+    fn checkout_shared(&mut self, addon: &Addon, root: &Path) -> Result<(), Error> {
+        let url = addon.clone_url();
+        let repo = shared::clone_dir(root, &url);
+        let to = self.addons_path().join(addon.name().as_ref());
+        let name = addon.name();
+
+        if repo.exists() {
+            self.logger.update(format!("[{name}] Fetching shared clone"));
+            self.timings.time(&name, "fetch", || self.git.fetch(&repo))?;
+        } else {
+            std::fs::create_dir_all(root)?;
+            let available = disk::available_space(root);
+            if let Err(err) = disk::check_space(root, available, disk::MIN_FREE_SPACE) {
+                self.logger.error(&err);
+                return Err(err);
+            }
+
+            let repo_name = repo.file_name().unwrap().to_string_lossy();
+            let depth = self.depth.or(addon.depth);
+            let result = self.timings.time(&name, "clone", || {
+                self.git.clone_with_depth(root, &url, &repo_name, depth, |percent| {
+                    self.logger.update(format!("Cloning {name} {percent}%"))
+                })
+            });
+            // Worktrees can't check out a branch that is checked out in the shared clone
+            if let Err(err) = result.and_then(|_| self.git.detach(&repo)) {
+                if repo.exists() {
+                    std::fs::remove_dir_all(&repo)?;
+                }
+                return Err(err);
+            }
+        }
+
+        if to.exists() {
+            std::fs::remove_dir_all(&to)?;
+        }
+        if let Some(parent) = to.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        self.logger.update(format!("[{name}] Adding worktree"));
+        self.git.worktree_add(&repo, &to, None)
+    }
+
+    /// The preferred branch if the addon follows the default branch and the remote has it
+    fn preferred_branch(&mut self, addon: &Addon, path: &Path) -> Option<String> {
+        if !addon.checkout.is_default_branch() {
+            return None;
+        }
+
+        if addon.target == Target::Archive {
+            return None;
+        }
+        let preferred = self.prefer_branch.clone()?;
+        let name = addon.name();
+        self.logger.update(format!("[{name}] Checking for branch `{preferred}`"));
+        self.git
+            .remote_branch_exists(path, &preferred)
+            .log_or(&mut self.logger, false)
+            .then_some(preferred)
+    }
+
+    /// Switch a freshly cloned addon to the preferred branch, returning the branch if it was
+    /// checked out.
+    fn checkout_preferred_branch(&mut self, addon: &Addon, path: &Path) -> Option<String> {
+        let branch = self.preferred_branch(addon, path)?;
+        let name = addon.name();
+        self.logger.update(format!("[{name}] Switching to branch `{branch}`"));
+        match self.timings.time(&name, "switch", || self.git.switch(path, &branch)) {
+            Ok(_) => Some(branch),
+            Err(_) => {
+                self.logger.error(format!("[{name}] failed to switch git branches"));
+                None
+            }
+        }
+    }
+
+    /// Check out the branch, tag, or commit an addon asks for in a fresh clone
+    fn checkout_target(&mut self, addon: &Addon, path: &Path) -> Result<(), Error> {
+        // The hash of a tarball is verified when it is downloaded
+        if addon.target == Target::Archive {
+            return Ok(());
+        }
+        let name = addon.name();
+        if let Some(branch) = addon.branch() {
+            self.logger.update(format!("[{name}] Switching to branch `{branch}`"));
+            self.timings
+                .time(&name, "switch", || self.git.switch(path, branch))
+                .map_err(|err| Error::context(format!("[{name}] failed to switch to branch `{branch}`"), err))?;
+        }
+
+        if let Some(checksum) = addon.checksum() {
+            self.logger.update(format!("[{name}] Setting branch to checksum `{checksum}`"));
+            self.timings
+                .time(&name, "reset", || self.git.reset(path, ResetType::Hard, Some(checksum)))
+                .map_err(|err| Error::context(format!("[{name}] failed to check out `{checksum}`"), err))?;
+        }
+        Ok(())
+    }
+
+    /// Add addons along with any addons they depend on
+    ///
+    /// Dependencies are read from each installed addon's `config.json` and added recursively.
+    /// Addons that are only installed as a dependency are marked as such in the config.
+    pub fn add(&mut self, addons: impl IntoIterator<Item=Addon>, options: AddOptions) -> Result<OperationReport, Error> {
+        let addons = self.dedup(addons.into_iter().collect())?;
+        let mut queue = addons
+            .into_iter()
+            .map(|addon| (addon, None))
+            .collect::<VecDeque<(Addon, Option<String>)>>();
+        let requested = queue.iter().map(|(addon, _)| addon.clone()).collect::<Vec<_>>();
+        // Addons handled so far by the directory they are installed in
+        let mut seen = HashMap::<String, Addon>::new();
+        let mut total = queue.len();
+        let mut report = OperationReport::new("add");
+        // Already installed addons that are updated once everything is added
+        let mut present = Vec::new();
+
+        if options.gitignore && Path::new(&self.addons_entry()).is_absolute() {
+            self.logger.warning(format!(
+                "{} is outside of the project, it isn't added to {GITIGNORE}",
+                self.addons_entry()
+            ));
+        } else if options.gitignore {
+            let entry = self.addons_entry();
+            match gitignore::ensure_ignored(&self.base, &entry) {
+                Ok(true) => self.logger.success(format!("added {entry}/ to {GITIGNORE}")),
+                Ok(false) => {}
+                Err(err) => self.logger.error(format!("failed to update {GITIGNORE}: {err}")),
+            }
+        }
+
+        // Only configs llam created, a `$schema` the user set or removed is left alone
+        if options.update_luarc && self.luarc_schema && self.rc.is_new() && self.rc.schema.is_none() {
+            self.rc.schema = Some(LUALS_SCHEMA.to_string());
+        }
+
         let mut success = 0;
 
-        let addon_path = self.base.join(ADDONS_DIR);
-        for addon in addons.iter() {
+        let addon_path = self.addons_path();
+        while let Some((addon, parent)) = queue.pop_front() {
+            let addon = &addon;
             let name = addon.name();
             let path = addon_path.join(name.as_ref());
+
+            let started = Instant::now();
+
+            // Cloning over another addon's directory would replace it
+            let collision = seen
+                .get(name.as_ref())
+                .or_else(|| self.rc.get_addons().get(name.as_ref()))
+                .filter(|other| !other.same_repository(addon))
+                .map(|other| other.to_string());
+            if let Some(other) = collision {
+                self.fail(&mut report, &name, started, format!(
+                    "[{name}] `{addon}` would be installed in the same directory as `{other}`, set a different name with --name"
+                ));
+                continue;
+            }
+
+            // Already handled in this run, either listed twice or a dependency cycle
+            if seen.insert(name.to_string(), addon.clone()).is_some() {
+                total -= 1;
+                if options.update_luarc {
+                    self.record_dependency(&name, parent.as_deref());
+                }
+                continue;
+            }
+
+            // The same repository installed under another alias
+            let installed_as = self
+                .rc
+                .get_addons()
+                .iter()
+                .find(|(other, installed)| other.as_ref() != name.as_ref() && installed.same_repository(addon))
+                .map(|(other, _)| other.to_string());
+            if let Some(other) = installed_as.filter(|_| !options.force) {
+                self.logger.warning(format!(
+                    "[{name}] is already installed as `{other}`, use --force to install it again"
+                ));
+                if options.update_luarc {
+                    self.record_dependency(&other, parent.as_deref());
+                }
+                let checksum = self.installed_checksum(&addon_path.join(&other));
+                report.record(&name, Outcome::Skipped, checksum.clone(), checksum, started);
+                continue;
+            }
+
+            let width = total.to_string().len();
             self.logger.update(format!(
                 "{:0>width$}/{total} Cloning {name}",
                 success,
-                width = total.len()
             ));
 
-            if !path.exists() || !self.rc.get_addons().contains_key(name.as_ref()) {
+            if !options.update_luarc {
+                if path.exists() {
+                    self.logger.warning(format!("{name} is already installed"));
+                    let checksum = self.installed_checksum(&path);
+                    report.record(&name, Outcome::Skipped, checksum.clone(), checksum, started);
+                    continue;
+                }
+
+                if self.clone_addon(addon).is_err() {
+                    self.fail(&mut report, &name, started, format!("failed to clone addon: {name}"));
+                    continue;
+                }
+                self.checkout_preferred_branch(addon, &path);
+                if let Err(err) = self.checkout_target(addon, &path) {
+                    std::fs::remove_dir_all(&path).ok();
+                    self.fail(&mut report, &name, started, err.to_string());
+                    continue;
+                }
+                if let Err(err) = self.verify_addon(&name, &path, options.strict) {
+                    std::fs::remove_dir_all(&path).ok();
+                    self.fail(&mut report, &name, started, err.to_string());
+                    continue;
+                }
+                self.check_luals_version(&name, &path);
+
+                self.logger.success(format!("{name} added"));
+                let checksum = self.installed_checksum(&path);
+                report.record(&name, Outcome::Added, None, checksum, started);
+            } else if !path.exists() || !self.rc.get_addons().contains_key(name.as_ref()) {
+                // Another profile already cloned the addon
+                let shared = path.exists() && self.rc.in_other_addon_set(&name);
                 self.rc.add_or_update_addon(addon);
-                if self.clone_addon(name.clone()).is_err() {
-                    self.logger.error(format!("failed to clone addon: {name}"));
+                if !shared && self.clone_addon(addon).is_err() {
+                    self.rc.get_addons_mut().remove(name.as_ref());
+                    self.fail(&mut report, &name, started, format!("failed to clone addon: {name}"));
+                    continue;
+                }
+                if let Some(branch) = self.checkout_preferred_branch(addon, &path) {
+                    if let Some(addon) = self.rc.get_addons_mut().get_mut(&name) {
+                        addon.checkout = AddonTarget::Branch(branch);
+                    }
+                }
+                // The clone of another profile stays at the revision that profile uses
+                let checkout = if shared { Ok(()) } else { self.checkout_target(addon, &path) };
+                if let Err(err) = checkout {
+                    std::fs::remove_dir_all(&path).ok();
+                    self.rc.get_addons_mut().remove(name.as_ref());
+                    self.fail(&mut report, &name, started, err.to_string());
+                    continue;
+                }
+                if let Err(err) = self.verify_addon(&name, &path, options.strict) {
+                    if !shared {
+                        std::fs::remove_dir_all(&path).ok();
+                    }
+                    self.rc.get_addons_mut().remove(name.as_ref());
+                    self.fail(&mut report, &name, started, err.to_string());
                     continue;
                 }
+                self.check_luals_version(&name, &path);
+                self.contribute_ignore_dirs(&name, &path);
 
                 self.logger.success(format!("{name} added"));
+                let checksum = self.installed_checksum(&path);
+                report.record(&name, Outcome::Added, None, checksum, started);
             } else {
                 let branch_diff = addon
-                    .branch
-                    .as_ref()
-                    .map(|v| Cli::branch_name(&path).map(|n| &n != v).unwrap_or_default())
+                    .branch()
+                    .map(|v| self.git.branch_name(&path).map(|n| n != v).unwrap_or_default())
                     .unwrap_or_default();
                 let checksum_diff = addon
-                    .checksum
-                    .as_ref()
+                    .checksum()
                     .map(|v| {
-                        Cli::checksum(&path, None)
-                            .map(|n| &n != v)
+                        self.installed_checksum(&path)
+                            .map(|n| n != v)
                             .unwrap_or_default()
                     })
                     .unwrap_or_default();
 
                 self.rc.add_or_update_addon(addon);
-                if branch_diff || checksum_diff {
-                    self.logger.warning(format!("{name} update available"));
+                if options.update && parent.is_none() {
+                    present.push(addon.clone());
+                } else {
+                    if branch_diff || checksum_diff {
+                        self.logger.warning(format!("{name} update available"));
+                    }
+                    let checksum = self.installed_checksum(&path);
+                    report.record(&name, Outcome::Skipped, checksum.clone(), checksum, started);
                 }
             };
 
+            if options.update_luarc {
+                self.record_dependency(&name, parent.as_deref());
+            }
+
+            for mut dependency in self.dependencies(&name, &path) {
+                dependency.dependency = true;
+                if !seen.contains_key(dependency.name().as_ref()) {
+                    total += 1;
+                }
+                queue.push_back((dependency, Some(name.to_string())));
+            }
+
             success += 1;
         }
 
+        if !options.update_luarc {
+            self.logger.success(format!("[Add] {} ({LUARC} left untouched)", report.summary()));
+            return Ok(report);
+        }
+
         self.logger.update("Updating .luarc.json");
 
-        let path = ADDONS_DIR.to_string();
+        self.expose_addons_dir();
+        self.sync_library();
+        self.save(|| format!("llam: add {}", describe(&requested)));
+
+        if !present.is_empty() {
+            report.addons.extend(self.update(present)?.addons);
+        }
+
+        self.logger.success(format!("[Add] {}", report.summary()));
+        Ok(report)
+    }
+
+    /// Add the addons directory to `workspace.userThirdParty`
+    fn expose_addons_dir(&mut self) {
+        let path = self.addons_entry();
         match self.rc.workspace.as_mut() {
             Some(workspace) => {
                 if !workspace.user_third_party.contains(&path) {
@@ -141,167 +771,700 @@ impl<L: Logger> Manager<L> {
                 });
             }
         }
+    }
+
+    /// Record the addons that are cloned in the addons directory but not in the config, e.g.
+    /// after a config that couldn't be parsed was replaced
+    ///
+    /// Addons on a branch follow it, detached addons are pinned to their checked out commit.
+    /// Returns the names of the recovered addons.
+    pub fn recover_addons(&mut self) -> Result<Vec<String>, Error> {
+        let mut recovered = Vec::new();
+        for path in self.unknown_addon_dirs()? {
+            let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                continue;
+            };
+
+            self.logger.update(format!("[{name}] Reading installed addon"));
+            let addon = self.git.remote_url(&path).and_then(|url| {
+                let mut addon = Addon::builder().url(url).name(&name).build()?;
+                // The name is only kept when the directory doesn't match the url
+                let alias = addon.alias.take();
+                if addon.name() != name.as_str() {
+                    addon.alias = alias;
+                }
+                match self.git.branch_name(&path)? {
+                    branch if branch == "HEAD" => addon.checkout = AddonTarget::Commit(self.git.checksum(&path, None)?),
+                    branch => addon.checkout = AddonTarget::Branch(branch),
+                }
+                Ok(addon)
+            });
 
-        if self.rc.write().is_err() {
-            self.logger.error("failed to write updates to .luarc.json");
+            match addon {
+                Ok(addon) => {
+                    self.rc.add_or_update_addon(&addon);
+                    self.logger.success(format!("{name} recovered"));
+                    recovered.push(name);
+                }
+                Err(err) => self.logger.error(format!("[{name}] failed to recover addon: {err}")),
+            }
         }
 
-        self.logger.success(format!("[Add] {success}/{total} Finished!"));
-        Ok(())
+        if !recovered.is_empty() {
+            self.expose_addons_dir();
+            self.sync_library();
+        }
+        self.rc.write()?;
+        Ok(recovered)
     }
 
-    pub fn remove(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<(), Error> {
-        let addons = match addons.into() {
-            SomeOrAll::Some(addons) => addons,
-            SomeOrAll::All => self.rc.get_addons().values().cloned().collect()
-        };
+    /// Log an addon failure and record it in the report
+    fn fail(&mut self, report: &mut OperationReport, name: &str, started: Instant, message: String) {
+        self.logger.error(&message);
+        report.failed(name, message, started);
+    }
 
-        let total = addons.len().to_string();
-        self.logger.update(format!("{:0>width$}/{total} Removing ...", 0, width = total.len()));
+    /// Restore an addon to the branch and commit it was on before a failed update, along with its
+    /// config entry, then record the failure
+    fn rollback(
+        &mut self,
+        report: &mut OperationReport,
+        name: &str,
+        path: &Path,
+        snapshot: &Snapshot,
+        started: Instant,
+        message: String,
+    ) {
+        self.logger.error(&message);
+        if let Some(previous) = snapshot.addon.clone() {
+            self.rc.get_addons_mut().insert(name.to_string().into(), previous);
+        }
 
-        let addon_path = self.base.join(ADDONS_DIR);
-        for (i, addon) in addons.iter().enumerate() {
-            let name = addon.name();
-            let path = addon_path.join(name.as_ref());
-            self.logger.update(format!(
-                "{:0>width$}/{total} Removing {name}",
-                i + 1,
-                width = total.len()
-            ));
+        let Snapshot { branch, checksum, .. } = snapshot;
+        self.logger.update(format!("[{name}] Rolling back to `{branch}` at `{checksum}`"));
+        // A detached `HEAD` has no branch to switch back to
+        let switched = if branch == "HEAD" { Ok(()) } else { self.git.switch(path, branch) };
+        let restored = switched.and_then(|_| self.git.reset(path, ResetType::Hard, Some(checksum)));
 
-            if self.rc.get_addons().contains_key(name.as_ref()) {
-                self.rc.get_addons_mut().remove(name.as_ref());
+        match restored {
+            Ok(()) => {
+                self.logger.warning(format!("[{name}] rolled back to `{checksum}`"));
+                report.rolled_back(name, message, checksum, started);
             }
-
-            if path.exists() {
-                std::fs::remove_dir_all(path)?;
+            Err(err) => {
+                let message = format!("{message}; failed to roll back to `{checksum}`: {err}");
+                self.logger.error(&message);
+                report.failed(name, message, started);
             }
         }
+    }
+
+    /// Checked out commit of an installed addon
+    fn installed_checksum(&self, path: &Path) -> Option<String> {
+        if !path.exists() {
+            return None;
+        }
+        if let Some(hash) = archive::installed_hash(path) {
+            return Some(hash);
+        }
+        self.git.checksum(path, None).ok().filter(|checksum| !checksum.is_empty())
+    }
 
-        if self.rc.write().is_err() {
-            self.logger.error("failed to write updates to .luarc.json");
+    /// Write the config, committing it to the project's repository when enabled
+    fn save(&mut self, message: impl FnOnce() -> String) {
+        if let Err(err) = self.rc.write() {
+            self.logger.error(format!("failed to write updates to {LUARC}: {err}"));
+            return;
         }
 
-        self.logger.success(format!("[Remove] {total}/{total} Finished!"));
-        Ok(())
+        if self.commit {
+            // The config may be in an editor folder instead of the project root
+            let file = self.rc.path().strip_prefix(&self.base).unwrap_or(self.rc.path()).to_path_buf();
+            self.logger.update(format!("Committing {LUARC}"));
+            match self.git.commit_file(&self.base, file, message()) {
+                Ok(true) => self.logger.success(format!("{LUARC} committed")),
+                Ok(false) => {}
+                Err(err) => self.logger.error(format!("failed to commit {LUARC}: {err}")),
+            }
+        }
     }
 
-    pub fn update(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<(), Error> {
-        // Collect all that are in the config
-        let addons = match addons.into() {
-            SomeOrAll::Some(addons) => addons,
-            SomeOrAll::All => self.rc.get_addons().values().cloned().collect()
+    /// Warn when a cloned addon doesn't look like a luals addon, or error when `strict`
+    fn verify_addon(&mut self, name: &str, path: &Path, strict: bool) -> Result<(), Error> {
+        let error = match AddonConfig::is_addon(path) {
+            Ok(true) => return Ok(()),
+            Ok(false) => Error::custom(format!(
+                "[{name}] doesn't look like a luals addon, it has no {ADDON_CONFIG} or {LIBRARY_DIR} directory"
+            )),
+            Err(err) => Error::custom(format!("[{name}] {err}")),
         };
 
-        let mut success = 0;
-        let addon_path = self.base.join(ADDONS_DIR);
-        for addon in addons.iter() {
-            let name = addon.name();
+        if strict {
+            return Err(error);
+        }
+        self.logger.warning(error.to_string());
+        Ok(())
+    }
 
-            if !self.rc.get_addons().contains_key(name.as_ref()) {
-                continue;
+    /// Warn when an addon declares a minimum luals version that is newer than the installed one
+    ///
+    /// Nothing is checked when the installed version can't be detected.
+    fn check_luals_version(&mut self, name: &str, path: &Path) {
+        let Ok(Some(AddonConfig { min_version: Some(required), .. })) = AddonConfig::read(path) else {
+            return;
+        };
+
+        let required = match Version::from_str(&required) {
+            Ok(required) => required,
+            Err(err) => {
+                self.logger.warning(format!("[{name}] {err} in {ADDON_CONFIG}"));
+                return;
             }
-            self.rc.add_or_update_addon(addon);
-            let addon = self.rc.get_addons().get(&name).unwrap();
+        };
+        if let Some(installed) = self.luals_version().filter(|installed| **installed < required).cloned() {
+            self.logger.warning(format!(
+                "[{name}] requires luals {required} or newer but {installed} is installed"
+            ));
+        }
+    }
 
-            let path = addon_path.join(name.as_ref());
+    /// Add the directories an addon's `config.json` ignores to `workspace.ignoreDir`
+    ///
+    /// Entries the user already added are left alone, the others are recorded on the addon so
+    /// they can be removed with it.
+    fn contribute_ignore_dirs(&mut self, name: &str, path: &Path) {
+        let Ok(Some(config)) = AddonConfig::read(path) else {
+            return;
+        };
+        let dirs = config.ignore_dirs();
+        if dirs.is_empty() {
+            return;
+        }
 
-            self.logger.update(format!("[{name}] Getting branch name"));
-            let branch = Cli::branch_name(&path)?;
+        let contributed = self
+            .rc
+            .get_addons()
+            .values()
+            .flat_map(|addon| addon.ignore_dirs.iter().cloned())
+            .collect::<HashSet<_>>();
+        let existing = self.rc.workspace.as_ref().map(|w| w.ignore_dir.clone()).unwrap_or_default();
+        let owned = dirs
+            .iter()
+            .filter(|dir| !existing.contains(dir) || contributed.contains(*dir))
+            .cloned()
+            .collect::<Vec<_>>();
 
-            self.logger.update(format!("[{name}] Getting default branch name"));
-            let default_branch = Cli::default_branch_name(&path)?;
+        self.rc.workspace_mut().add_ignore_dirs(dirs);
+        if let Some(addon) = self.rc.get_addons_mut().get_mut(name) {
+            addon.ignore_dirs = owned;
+        }
+    }
 
-            self.logger.update(format!("[{name}] Getting current checksum"));
-            let checksum = Cli::checksum(&path, None)?;
+    /// Remove the `workspace.ignoreDir` entries a removed addon added, unless another addon
+    /// also added them
+    fn retract_ignore_dirs(&mut self, removed: &Addon) {
+        if removed.ignore_dirs.is_empty() {
+            return;
+        }
 
-            match addon.branch.as_ref() {
-                Some(b) if b != &branch => {
-                    self.logger.update(format!("[{name}] Fetching latest repository changes"));
-                    if Cli::fetch(&path).is_err() {
-                        self.logger.error(format!("[{name}] failed to fetch latest changes from git"));
-                        continue;
-                    };
+        let still_used = self
+            .rc
+            .get_addons()
+            .values()
+            .flat_map(|addon| addon.ignore_dirs.iter().cloned())
+            .collect::<HashSet<_>>();
+        if let Some(workspace) = self.rc.workspace.as_mut() {
+            workspace
+                .ignore_dir
+                .retain(|dir| !removed.ignore_dirs.contains(dir) || still_used.contains(dir));
+        }
+    }
 
-                    self.logger.update(format!("[{name}] Switching to branch `{b}`"));
-                    if Cli::switch(&path, b).is_err() {
-                        self.logger.error(format!("[{name}] failed to switch git branches"));
-                        continue;
-                    };
+    /// Addons declared as dependencies in an installed addon's `config.json`
+    fn dependencies(&mut self, name: &str, path: &Path) -> Vec<Addon> {
+        let config = match AddonConfig::read(path) {
+            Ok(Some(config)) => config,
+            Ok(None) => return Vec::new(),
+            Err(err) => {
+                self.logger.error(format!("[{name}] {err}"));
+                return Vec::new();
+            }
+        };
 
-                    self.logger.update(format!("[{name}] Pulling latest changes"));
-                    if Cli::pull(&path, false).is_err() {
-                        self.logger.error(format!("[{name}] failed to pull latest changes"));
-                        continue;
-                    };
+        config
+            .dependencies
+            .iter()
+            .filter_map(|spec| {
+                Addon::from_str(spec)
+                    .map_err(|err| {
+                        self.logger.error(format!("[{name}] invalid dependency `{spec}`: {err}"))
+                    })
+                    .ok()
+            })
+            .collect()
+    }
 
-                    if let Some(checksum) = addon.checksum.as_deref() {
-                        self.logger.update(format!(
-                            "[{name}] Setting branch to checksum `{checksum}`"
-                        ));
-                        if Cli::reset(&path, ResetType::Hard, Some(checksum)).is_err() {
-                            self.logger.error(format!("[{name}] failed to reset git branch"));
-                            continue;
-                        };
-                    }
-                }
-                None if branch != default_branch => {
-                    self.logger.update(format!("[{name}] Fetching latest repository changes"));
-                    if Cli::fetch(&path).is_err() {
-                        self.logger.error(format!("[{name}] failed to fetch latest changes from git"));
+    /// Record why an addon is installed. Addons added explicitly are no longer considered
+    /// dependencies, otherwise `parent` is added to the addons that require it.
+    fn record_dependency(&mut self, name: &str, parent: Option<&str>) {
+        let Some(addon) = self.rc.get_addons_mut().get_mut(name) else {
+            return;
+        };
+
+        match parent {
+            Some(parent) => {
+                addon.required_by.insert(parent.to_string());
+            }
+            None => addon.dependency = false,
+        }
+    }
+
+    /// Enable or disable addons by name without removing them from the config or disk
+    pub fn set_enabled(&mut self, names: impl IntoIterator<Item = String>, enabled: bool) -> Result<(), Error> {
+        let state = if enabled { "enabled" } else { "disabled" };
+        for name in names {
+            match self.rc.get_addons_mut().get_mut(name.as_str()) {
+                Some(addon) => {
+                    addon.enabled = enabled;
+                    self.logger.success(format!("{name} {state}"));
+                }
+                None => self.logger.error(format!("unknown addon: {name}")),
+            }
+        }
+
+        self.sync_library();
+        self.rc.write()
+    }
+
+    /// Pin or unpin addons, pinned addons are skipped by `update`
+    pub fn set_pinned(&mut self, names: impl IntoIterator<Item = String>, pinned: bool) -> Result<(), Error> {
+        let state = if pinned { "pinned" } else { "unpinned" };
+        for name in names {
+            match self.rc.get_addons_mut().get_mut(name.as_str()) {
+                Some(addon) => {
+                    addon.pinned = pinned;
+                    self.logger.success(format!("{name} {state}"));
+                }
+                None => self.logger.error(format!("unknown addon: {name}")),
+            }
+        }
+
+        self.rc.write()
+    }
+
+    /// Library path exposed to luals for an addon
+    ///
+    /// Points at the addon's `library` directory when it has one, otherwise the addon itself.
+    fn library_path(&self, name: &str) -> String {
+        let dir = self.addons_entry();
+        let library = format!("{dir}/{name}/library");
+        if self.base.join(&library).exists() {
+            library
+        } else {
+            format!("{dir}/{name}")
+        }
+    }
+
+    /// Make `workspace.library` expose exactly the enabled addons
+    ///
+    /// Entries inside the addons directory that belong to disabled or unknown addons are removed,
+    /// entries outside of it are left untouched.
+    fn sync_library(&mut self) {
+        let exposed = self
+            .rc
+            .get_addons()
+            .iter()
+            .filter(|(_, addon)| addon.enabled)
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        let exposed = exposed
+            .iter()
+            .map(|name| self.library_path(name))
+            .collect::<Vec<_>>();
+
+        let addons_dir = self.addons_path();
+        let workspace = self.rc.workspace_mut();
+        workspace.library.retain(|entry| {
+            !self.base.join(expand_vars(entry).as_ref()).starts_with(&addons_dir)
+                || exposed.contains(entry)
+        });
+        for entry in exposed {
+            if !workspace.library.contains(&entry) {
+                workspace.library.push(entry);
+            }
+        }
+    }
+
+    /// Remove addons from the config and disk
+    ///
+    /// Addons are matched against the installed addons by name or by the url they are cloned from.
+    pub fn remove(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<OperationReport, Error> {
+        let addons = match addons.into() {
+            SomeOrAll::Some(addons) => addons
+                .iter()
+                .map(|addon| self.find_installed(addon))
+                .collect::<Result<Vec<_>, _>>()?,
+            SomeOrAll::All => self.rc.get_addons().values().cloned().collect()
+        };
+        let addons = self.dedup(addons)?;
+
+        let total = addons.len().to_string();
+        self.logger.update(format!("{:0>width$}/{total} Removing ...", 0, width = total.len()));
+        let mut report = OperationReport::new("remove");
+
+        let started = Instant::now();
+        let addon_path = self.addons_path();
+        let mut installed = Vec::new();
+        for addon in addons.iter() {
+            let name = addon.name();
+            let path = addon_path.join(name.as_ref());
+            let checksum = self.installed_checksum(&path);
+
+            if let Some(removed) = self.rc.get_addons_mut().remove(name.as_ref()) {
+                self.retract_ignore_dirs(&removed);
+            }
+
+            // The clone is still used by another profile
+            if path.exists() && !self.rc.in_other_addon_set(&name) {
+                installed.push((name, path, checksum));
+            } else {
+                report.record(&name, Outcome::Removed, checksum, None, started);
+            }
+        }
+
+        self.logger.update(format!("Removing {} addon directories", installed.len()));
+        let paths = installed.iter().map(|(_, path, _)| path.clone()).collect::<Vec<_>>();
+        let results = disk::remove_dirs(&paths, self.remove_threads);
+        for ((name, path, checksum), result) in installed.into_iter().zip(results) {
+            match result {
+                Ok(()) => report.record(&name, Outcome::Removed, checksum, None, started),
+                Err(err) => self.fail(
+                    &mut report,
+                    &name,
+                    started,
+                    format!("[{name}] failed to remove {}: {err}", path.display()),
+                ),
+            }
+        }
+        self.sync_library();
+        self.save(|| format!("llam: remove {}", describe(&addons)));
+
+        self.logger.success(format!("[Remove] {total}/{total} Finished!"));
+        Ok(report)
+    }
+
+    /// Drop addons that are listed more than once
+    ///
+    /// Errors when the same name is listed with a different source, branch, or checksum since it
+    /// isn't clear which one is meant.
+    fn dedup(&mut self, addons: Vec<Addon>) -> Result<Vec<Addon>, Error> {
+        let mut unique: Vec<Addon> = Vec::with_capacity(addons.len());
+        for addon in addons {
+            let name = addon.name();
+            match unique.iter().find(|a| a.name() == name) {
+                None => unique.push(addon),
+                Some(first) if first.clone_url() == addon.clone_url()
+                    && first.checkout == addon.checkout =>
+                {
+                    self.logger.warning(format!("{name} is listed more than once"));
+                }
+                Some(first) => {
+                    return Err(Error::custom(format!(
+                        "`{first}` and `{addon}` are both named `{name}`, set a different name for one of them with --name"
+                    )))
+                }
+            }
+        }
+        Ok(unique)
+    }
+
+    /// The installed addon an addon spec refers to, matched by name or clone url
+    ///
+    /// Specs that don't match an installed addon are returned as is. Errors when the spec matches
+    /// more than one installed addon, e.g. the same url installed under different aliases.
+    fn find_installed(&mut self, addon: &Addon) -> Result<Addon, Error> {
+        let name = addon.name();
+        let url = addon.clone_url();
+        let candidates = self
+            .rc
+            .get_addons()
+            .iter()
+            .filter(|(key, installed)| {
+                *key == &name || (addon.alias.is_none() && installed.clone_url() == url)
+            })
+            .collect::<Vec<_>>();
+
+        match candidates.as_slice() {
+            [] => Ok(addon.clone()),
+            [(_, installed)] => Ok((*installed).clone()),
+            _ => Err(Error::custom(format!(
+                "`{addon}` matches multiple installed addons: {}",
+                candidates.iter().map(|(key, _)| key.to_string()).collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Check which addons have updates available without changing anything
+    ///
+    /// Pinned addons are compared against their pinned revision, all other addons against the
+    /// latest commit of their branch on the remote.
+    pub fn check_updates(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> UpdateCheck {
+        let names = match addons.into() {
+            SomeOrAll::Some(addons) => addons.iter().map(|a| a.name().to_string()).collect(),
+            SomeOrAll::All => self.rc.get_addons().keys().map(|k| k.to_string()).collect::<Vec<_>>(),
+        };
+
+        let mut check = UpdateCheck::default();
+        for name in names {
+            let Some(addon) = self.rc.get_addons().get(name.as_str()).cloned() else {
+                check.errors.push((name.clone(), format!("unknown addon: {name}")));
+                continue;
+            };
+
+            self.logger.update(format!("[{name}] Checking for updates"));
+            let path = self.addons_path().join(name.as_str());
+            if !path.exists() {
+                check.outdated.push(Outdated { name, current: None, latest: None });
+                continue;
+            }
+            // Tarballs only change when their url or hash is changed in the config
+            if addon.target == Target::Archive {
+                let current = archive::installed_hash(&path);
+                let latest = addon.checksum().map(str::to_string);
+                if latest.is_some() && current != latest {
+                    check.outdated.push(Outdated { name, current, latest });
+                }
+                continue;
+            }
+
+            let result = self.git.checksum(&path, None).and_then(|current| {
+                let latest = match addon.checksum() {
+                    // A pinned revision that isn't known locally needs a fetch
+                    Some(pinned) => self.git.resolve(&path, pinned).ok(),
+                    None => {
+                        let branch = match addon.branch() {
+                            Some(branch) => branch.to_string(),
+                            None => self.git.default_branch_name(&path)?,
+                        };
+                        Some(self.remote_checksum(&addon.clone_url(), &branch)?)
+                    }
+                };
+                Ok((current, latest))
+            });
+
+            match result {
+                Ok((current, latest)) if latest.as_ref() != Some(&current) => {
+                    check.outdated.push(Outdated { name, current: Some(current), latest })
+                }
+                Ok(_) => {}
+                Err(err) => check.errors.push((name, err.to_string())),
+            }
+        }
+
+        if let Some(cache) = self.cache.as_mut() {
+            cache.save(SystemTime::now()).log(&mut self.logger);
+        }
+
+        for outdated in check.outdated.iter() {
+            self.logger.warning(outdated);
+        }
+        for (name, err) in check.errors.iter() {
+            self.logger.error(format!("[{name}] {err}"));
+        }
+        check
+    }
+
+    /// Latest commit of a remote branch, using the cache when it is enabled
+    fn remote_checksum(&mut self, url: &str, branch: &str) -> Result<String, Error> {
+        let now = SystemTime::now();
+        if let Some(checksum) = self.cache.as_ref().and_then(|cache| cache.get(url, branch, now)) {
+            return Ok(checksum.to_string());
+        }
+
+        let checksum = self.git.remote_checksum(url, Some(branch))?;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert(url, branch, &checksum, now);
+        }
+        Ok(checksum)
+    }
+
+    pub fn update(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<OperationReport, Error> {
+        // Collect all that are in the config, pinned addons are only updated when named and forced
+        let (addons, force_pinned) = match addons.into() {
+            SomeOrAll::Some(addons) => (self.dedup(addons)?, self.force_pinned),
+            SomeOrAll::All => (self.rc.get_addons().values().cloned().collect(), false),
+        };
+        self.update_addons(addons, force_pinned, HashMap::new())
+    }
+
+    /// Update the addons, restoring the config entries in `previous` instead of the current
+    /// entries when an addon's update fails
+    fn update_addons(
+        &mut self,
+        addons: Vec<Addon>,
+        force_pinned: bool,
+        mut previous: HashMap<String, Addon>,
+    ) -> Result<OperationReport, Error> {
+        let mut report = OperationReport::new("update");
+        let addon_path = self.addons_path();
+        // Repositories fetched so far, worktrees of the same clone only need a single fetch
+        let mut fetched = HashSet::new();
+        for addon in addons.iter() {
+            let started = Instant::now();
+            let name = addon.name();
+
+            if !self.rc.get_addons().contains_key(name.as_ref()) {
+                report.record(&name, Outcome::Skipped, None, None, started);
+                continue;
+            }
+            if self.rc.get_addons()[name.as_ref()].pinned && !force_pinned {
+                self.logger.warning(format!("[{name}] is pinned, use --force-pinned to update it"));
+                report.record(&name, Outcome::Skipped, None, None, started);
+                continue;
+            }
+            // Restored if any step of the update fails
+            let previous = previous
+                .remove(name.as_ref())
+                .or_else(|| self.rc.get_addons().get(name.as_ref()).cloned());
+            self.rc.add_or_update_addon(addon);
+            let path = addon_path.join(name.as_ref());
+            if self.rc.get_addons()[name.as_ref()].target == Target::Archive {
+                self.update_archive(&mut report, &name, &path, previous, started);
+                continue;
+            }
+
+            let stored = self.rc.get_addons()[name.as_ref()].clone();
+            if let Some(branch) = self.preferred_branch(&stored, &path) {
+                if let Some(addon) = self.rc.get_addons_mut().get_mut(&name) {
+                    addon.checkout = AddonTarget::Branch(branch);
+                }
+            }
+
+            let (branch, mut default_branch, checksum) = match self.checked_out(&name, &path) {
+                Ok(state) => state,
+                Err(err) => {
+                    // Nothing was changed on disk yet, only the config entry
+                    if let Some(previous) = previous {
+                        self.rc.get_addons_mut().insert(name.to_string().into(), previous);
+                    }
+                    self.fail(&mut report, &name, started, format!("[{name}] failed to read the installed addon: {err}"));
+                    continue;
+                }
+            };
+            let renamed = self.renamed_branch(&name, &path, &branch);
+            if let Some(renamed) = renamed.clone() {
+                default_branch = renamed;
+            }
+            let addon = self.rc.get_addons().get(&name).unwrap().clone();
+            // Tags pushed since the last fetch aren't fetched by default
+            let fetch = FetchOptions {
+                tags: matches!(addon.checkout, AddonTarget::Tag(_)),
+                depth: self.depth.or(addon.depth),
+                ..Default::default()
+            };
+
+            // A branch checked out in another project's worktree can't be switched to, shared
+            // worktrees stay detached and are reset to the commit they should be at instead
+            let shared = branch == "HEAD" && is_worktree(&path);
+            let snapshot = Snapshot {
+                branch: branch.clone(),
+                checksum: checksum.clone(),
+                addon: previous,
+            };
+
+            match addon.branch() {
+                Some(b) if b != branch && !shared => {
+                    self.logger.update(format!("[{name}] Fetching latest repository changes"));
+                    if self.fetch_once(&mut fetched, &name, &path, &fetch, &addon.mirrors).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to fetch latest changes from git"));
+                        continue;
+                    };
+
+                    self.logger.update(format!("[{name}] Switching to branch `{b}`"));
+                    if self.timings.time(&name, "switch", || self.git.switch(&path, b)).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to switch git branches"));
+                        continue;
+                    };
+
+                    self.logger.update(format!("[{name}] Pulling latest changes"));
+                    if self.timings.time(&name, "pull", || self.git.pull(&path, false)).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to pull latest changes"));
+                        continue;
+                    };
+                }
+                // A branch that was checked out locally on purpose, it can't be updated without
+                // switching away from it
+                None if branch != default_branch && !shared && self.keep_local_branch && renamed.is_none() => {
+                    self.logger.warning(format!(
+                        "[{name}] keeping local branch `{branch}` instead of switching to `{default_branch}`"
+                    ));
+                }
+                None if branch != default_branch && !shared => {
+                    self.logger.warning(format!(
+                        "[{name}] switching from local branch `{branch}` to default branch `{default_branch}`"
+                    ));
+                    self.logger.update(format!("[{name}] Fetching latest repository changes"));
+                    if self.fetch_once(&mut fetched, &name, &path, &fetch, &addon.mirrors).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to fetch latest changes from git"));
                         continue;
                     };
 
                     self.logger.update(format!("[{name}] Switching to branch `{default_branch}`"));
-                    if Cli::switch(&path, &default_branch).is_err() {
-                        self.logger.error(format!("[{name}] failed to switch git branches"));
+                    if self.timings.time(&name, "switch", || self.git.switch(&path, &default_branch)).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to switch git branches"));
                         continue;
                     };
 
                     self.logger.update(format!("[{name}] Pulling latest changes"));
-                    if Cli::pull(&path, false).is_err() {
-                        self.logger.error(format!("[{name}] failed to pull latest changes"));
+                    if self.timings.time(&name, "pull", || self.git.pull(&path, false)).is_err() {
+                        self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to pull latest changes"));
                         continue;
                     };
 
-                    if let Some(checksum) = addon.checksum.as_deref() {
+                    if let Some(checksum) = addon.checksum() {
                         self.logger.update(format!(
                             "[{name}] Setting branch to checksum `{checksum}`"
                         ));
-                        if Cli::reset(&path, ResetType::Hard, Some(checksum)).is_err() {
-                            self.logger.error(format!("[{name}] failed to set git branch"));
+                        if self.timings.time(&name, "reset", || self.git.reset(&path, ResetType::Hard, Some(checksum))).is_err() {
+                            self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to set git branch"));
                             continue;
                         };
                     }
                 }
-                _ => match addon.checksum.as_ref() {
-                    Some(c) if c != &checksum => {
+                _ => match addon.checksum() {
+                    Some(c) if c != checksum => {
                         self.logger.update(format!("[{name}] Fetching latest repository changes"));
-                        if Cli::fetch(&path).is_err() {
-                            self.logger.error(format!("[{name}] failed to fetch latest changes from git"));
+                        if self.fetch_once(&mut fetched, &name, &path, &fetch, &addon.mirrors).is_err() {
+                            self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to fetch latest changes from git"));
                             continue;
                         };
                         self.logger.update(format!("[{name}] Setting branch to checksum `{c}`"));
-                        if Cli::reset(&path, ResetType::Hard, Some(c)).is_err() {
-                            self.logger.error(format!("[{name}] failed to set git branch"));
+                        if self.timings.time(&name, "reset", || self.git.reset(&path, ResetType::Hard, Some(c))).is_err() {
+                            self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to set git branch"));
                             continue;
                         };
                     }
+                    // Follow the tip of the branch the addon is pinned to, or the default branch
                     None => {
-                        let latest = Cli::checksum(&path, Some(default_branch.as_str()))?;
-                        if latest != checksum {
-                            self.logger.update(format!(
-                                "[{name}] Fetching latest repository changes"
-                            ));
-                            if Cli::fetch(&path).is_err() {
-                                self.logger.error(format!("[{name}] failed to fetch latest changes from git"));
+                        let tracked = addon.branch().map_or_else(|| default_branch.clone(), str::to_string);
+                        self.logger.update(format!("[{name}] Fetching latest repository changes"));
+                        if self.fetch_once(&mut fetched, &name, &path, &fetch, &addon.mirrors).is_err() {
+                            self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to fetch latest changes from git"));
+                            continue;
+                        };
+                        let latest = match self.git.checksum(&path, Some(tracked.as_str())) {
+                            Ok(latest) => latest,
+                            Err(err) => {
+                                self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to get the latest checksum of `{tracked}`: {err}"));
                                 continue;
-                            };
+                            }
+                        };
+                        if latest != checksum {
                             self.logger.update(format!(
                                 "[{name}] Setting branch to checksum `{latest}`"
                             ));
-                            if Cli::reset(&path, ResetType::Hard, Some(latest)).is_err() {
-                                self.logger.error(format!("[{name}] failed to set git branch"));
+                            if self.timings.time(&name, "reset", || self.git.reset(&path, ResetType::Hard, Some(latest))).is_err() {
+                                self.rollback(&mut report, &name, &path, &snapshot, started, format!("[{name}] failed to set git branch"));
                                 continue;
                             };
                         }
@@ -310,46 +1473,2070 @@ impl<L: Logger> Manager<L> {
                 },
             }
 
+            let latest = self.installed_checksum(&path);
+            let outcome = if latest.as_ref() == Some(&checksum) {
+                self.logger.success(format!("{name} updated"));
+                Outcome::Skipped
+            } else {
+                let changes = latest.as_deref().map(|latest| self.changes(&addon, &path, &checksum, latest));
+                self.logger.success(format!("{name} updated{}", changes.unwrap_or_default()));
+                Outcome::Updated
+            };
+            report.record(&name, outcome, Some(checksum), latest, started);
+        }
+
+        self.save(|| format!("llam: update {}", describe(&addons)));
+
+        self.logger.success(format!("[Update] {}", report.summary()));
+
+        Ok(report)
+    }
+
+    /// Checked out branch, default branch, and checksum of an installed addon
+    fn checked_out(&mut self, name: &str, path: &Path) -> Result<(String, String, String), Error> {
+        self.logger.update(format!("[{name}] Getting branch name"));
+        let branch = self.git.branch_name(path)?;
+
+        self.logger.update(format!("[{name}] Getting default branch name"));
+        let default_branch = self.git.default_branch_name(path)?;
+
+        self.logger.update(format!("[{name}] Getting current checksum"));
+        let checksum = self.git.checksum(path, None)?;
+        Ok((branch, default_branch, checksum))
+    }
+
+    /// Download an addon distributed as a tarball again when its url or hash changed, or it is
+    /// missing
+    ///
+    /// A hash recorded for a previous url is dropped since it can't match another tarball. The
+    /// installed copy and config entry are kept when the download fails.
+    fn update_archive(&mut self, report: &mut OperationReport, name: &str, path: &Path, previous: Option<Addon>, started: Instant) {
+        let current = archive::installed_hash(path);
+        let mut addon = self.rc.get_addons()[name].clone();
+        let moved = previous.as_ref().is_some_and(|previous| previous.src != addon.src);
+        if moved && previous.as_ref().is_some_and(|previous| previous.checkout == addon.checkout) {
+            addon.checkout = AddonTarget::DefaultBranch;
+        }
+
+        let changed = addon.checksum().is_some_and(|checksum| Some(checksum) != current.as_deref());
+        if current.is_some() && !moved && !changed {
             self.logger.success(format!("{name} updated"));
+            report.record(name, Outcome::Skipped, current.clone(), current, started);
+            return;
+        }
+
+        self.rc.get_addons_mut().insert(name.to_string().into(), addon.clone());
+        if self.clone_addon(&addon).is_err() {
+            if let Some(previous) = previous {
+                self.rc.get_addons_mut().insert(name.to_string().into(), previous);
+            }
+            let message = format!("[{name}] failed to download {}", addon.src);
+            match current.as_deref() {
+                Some(current) => {
+                    self.logger.error(&message);
+                    report.rolled_back(name, message, current, started);
+                }
+                None => self.fail(report, name, started, message),
+            }
+            return;
+        }
+
+        let latest = archive::installed_hash(path);
+        let outcome = if latest == current { Outcome::Skipped } else { Outcome::Updated };
+        self.logger.success(format!("{name} updated"));
+        report.record(name, outcome, current, latest, started);
+    }
+
+    /// Move a single installed addon to another branch, tag, or commit and update it
+    ///
+    /// `reference` is a commit hash, a [target][AddonTarget] such as `tag:v1.0`, or a name that is
+    /// followed as a branch when the remote has a branch with that name and checked out as a tag
+    /// otherwise. Commits are recorded with their full checksum.
+    pub fn update_to(&mut self, addons: impl Into<SomeOrAll<Addon>>, reference: &str) -> Result<OperationReport, Error> {
+        let addon = match addons.into() {
+            SomeOrAll::Some(addons) if addons.len() == 1 => addons.into_iter().next().unwrap(),
+            _ => return Err(Error::custom("--to moves a single addon, name exactly one addon")),
+        };
+        let name = addon.name();
+        let original = self
+            .rc
+            .get_addons()
+            .get(name.as_ref())
+            .cloned()
+            .ok_or_else(|| Error::custom(format!("[{name}] is not installed")))?;
+        if original.target == Target::Archive {
+            return Err(Error::custom(format!("[{name}] is a tarball, update its url instead")));
+        }
+        let path = self.addons_path().join(name.as_ref());
+
+        let target = if AddonTarget::is_commit(reference) {
+            // Commits that haven't been fetched yet can only be resolved after fetching
+            let checksum = match self.git.resolve(&path, reference) {
+                Ok(checksum) => checksum,
+                Err(_) => {
+                    self.logger.update(format!("[{name}] Fetching latest repository changes"));
+                    self.git.fetch(&path)?;
+                    self.git.resolve(&path, reference)?
+                }
+            };
+            AddonTarget::Commit(checksum)
+        } else if let Ok(target) = reference.parse::<AddonTarget>() {
+            target
+        } else if self.git.remote_branch_exists(&path, reference)? {
+            AddonTarget::Branch(reference.to_string())
+        } else {
+            AddonTarget::Tag(reference.to_string())
+        };
+
+        self.logger.update(format!("[{name}] Moving to {target}"));
+        // Merging into the entry would keep a branch or checksum the new target clears
+        let mut addon = original.clone();
+        addon.checkout = target;
+        self.rc.get_addons_mut().insert(name.to_string().into(), addon.clone());
+        self.update_addons(vec![addon], self.force_pinned, HashMap::from([(name.to_string(), original)]))
+    }
+
+    /// Number of new commits and a link comparing them, e.g. `, 3 new commits: <url>`
+    fn changes(&self, addon: &Addon, path: &Path, old: &str, new: &str) -> String {
+        let mut changes = String::new();
+        if let Ok(count) = self.git.commit_count(path, old, new) {
+            let plural = if count == 1 { "" } else { "s" };
+            changes.push_str(&format!(", {count} new commit{plural}"));
+        }
+        if let Some(url) = addon.compare_url(old, new) {
+            changes.push_str(&format!(": {url}"));
+        }
+        changes
+    }
+
+    /// Detect that the branch an addon follows was removed from the remote, e.g. when the
+    /// upstream renamed `master` to `main`.
+    ///
+    /// Returns the remote's current default branch, which the addon follows from now on. Only
+    /// addons following a branch are checked, pinned addons, addons checked out at a tag or
+    /// commit, and detached checkouts are skipped without asking the remote.
+    fn renamed_branch(&mut self, name: &str, path: &Path, current: &str) -> Option<String> {
+        let addon = self.rc.get_addons().get(name)?;
+        if addon.pinned || addon.checksum().is_some() || current == "HEAD" {
+            return None;
+        }
+        let recorded = addon.branch().map(str::to_string);
+        let tracked = recorded.clone().unwrap_or_else(|| current.to_string());
+
+        self.logger.update(format!("[{name}] Checking that branch `{tracked}` still exists"));
+        if self.git.remote_branch_exists(path, &tracked).log_or(&mut self.logger, true) {
+            return None;
+        }
+
+        let default = self
+            .git
+            .remote_default_branch(path)
+            .map(Some)
+            .log_or(&mut self.logger, None)
+            .filter(|default| default != &tracked)?;
+
+        self.logger.warning(format!(
+            "[{name}] branch `{tracked}` no longer exists on the remote, following `{default}` instead"
+        ));
+        if recorded.is_some() {
+            if let Some(addon) = self.rc.get_addons_mut().get_mut(name) {
+                addon.checkout = AddonTarget::Branch(default.clone());
+            }
+        }
+        Some(default)
+    }
+
+    /// Delete and clone addons again, restoring the branch and checksum recorded in the config
+    ///
+    /// The config is left untouched. Addons that aren't in the config are skipped.
+    pub fn reinstall(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<OperationReport, Error> {
+        let names = match addons.into() {
+            SomeOrAll::Some(addons) => addons
+                .iter()
+                .map(|addon| self.find_installed(addon).map(|addon| addon.name().to_string()))
+                .collect::<Result<Vec<_>, _>>()?,
+            SomeOrAll::All => self.rc.get_addons().keys().map(|k| k.to_string()).collect(),
+        };
+
+        let total = names.len();
+        let width = total.to_string().len();
+        let mut report = OperationReport::new("reinstall");
+        let mut success = 0;
+        for (i, name) in names.iter().enumerate() {
+            let started = Instant::now();
+            let Some(addon) = self.rc.get_addons().get(name.as_str()).cloned() else {
+                self.fail(&mut report, name, started, format!("unknown addon: {name}"));
+                continue;
+            };
+
+            self.logger.update(format!("{:0>width$}/{total} Reinstalling {name}", i + 1));
+            let path = self.addons_path().join(name);
+            let old = self.installed_checksum(&path);
+            if path.exists() {
+                std::fs::remove_dir_all(&path)?;
+            }
+
+            if self.clone_addon(&addon).is_err() {
+                self.fail(&mut report, name, started, format!("failed to clone addon: {name}"));
+                continue;
+            }
+
+            if let Err(err) = self.checkout_target(&addon, &path) {
+                self.fail(&mut report, name, started, err.to_string());
+                continue;
+            }
+
+            self.logger.success(format!("{name} reinstalled"));
+            let checksum = self.installed_checksum(&path);
+            report.record(name, Outcome::Reinstalled, old, checksum, started);
             success += 1;
         }
 
-        if self.rc.write().is_err() {
-            self.logger.error("failed to write updates to .luarc.json")
+        self.logger.success(format!("[Reinstall] {success}/{total} Finished!"));
+        Ok(report)
+    }
+
+    /// Prune stale remote tracking branches and garbage collect the repositories of addons
+    ///
+    /// Returns the number of bytes reclaimed.
+    pub fn gc(&mut self, addons: impl Into<SomeOrAll<Addon>>) -> Result<u64, Error> {
+        let names = match addons.into() {
+            SomeOrAll::Some(addons) => addons
+                .iter()
+                .map(|addon| self.find_installed(addon).map(|addon| addon.name().to_string()))
+                .collect::<Result<Vec<_>, _>>()?,
+            SomeOrAll::All => self.rc.get_addons().keys().map(|k| k.to_string()).collect(),
+        };
+
+        let mut reclaimed = 0;
+        for name in names {
+            // Extracted tarballs have no repository to collect
+            if self.rc.get_addons().get(name.as_str()).is_some_and(|addon| addon.target == Target::Archive) {
+                continue;
+            }
+            let path = self.addons_path().join(&name);
+            if !path.exists() {
+                self.logger.warning(format!("[{name}] is not installed"));
+                continue;
+            }
+
+            let before = disk::dir_size(&path);
+            self.logger.update(format!("[{name}] Pruning remote branches"));
+            if let Err(err) = self.timings.time(&name, "remote prune", || self.git.remote_prune(&path)) {
+                self.logger.warning(format!("[{name}] {err}"));
+            }
+            self.logger.update(format!("[{name}] Collecting garbage"));
+            if let Err(err) = self.timings.time(&name, "gc", || self.git.gc(&path)) {
+                self.logger.error(format!("[{name}] {err}"));
+                continue;
+            }
+
+            let freed = before.saturating_sub(disk::dir_size(&path));
+            reclaimed += freed;
+            self.logger.success(format!("[{name}] reclaimed {} KiB", freed / 1024));
         }
 
-        self.logger.success(format!("[Update] {success}/{} Finished!", addons.len()));
+        self.logger.success(format!("[Gc] Reclaimed {} KiB", reclaimed / 1024));
+        Ok(reclaimed)
+    }
 
-        Ok(())
+    /// Addons in the config whose directory exists but isn't a git repository, e.g. when it was
+    /// copied without its `.git`. Tarballs are never repositories and aren't broken.
+    pub fn broken_addons(&mut self) -> Vec<String> {
+        let addons_dir = self.addons_path();
+        self.rc
+            .get_addons()
+            .iter()
+            .filter(|(name, addon)| {
+                let path = addons_dir.join(name.as_ref());
+                addon.target != Target::Archive && path.is_dir() && !path.join(".git").exists()
+            })
+            .map(|(name, _)| name.to_string())
+            .collect()
     }
 
-    pub fn clean(&mut self) -> Result<(), Error> {
-        // Collect all that are in the config
+    /// Report addons that can't be managed by git, cloning them again at their recorded branch
+    /// and checksum when `repair` is set
+    pub fn doctor(&mut self, repair: bool) -> Result<OperationReport, Error> {
+        let broken = self.broken_addons();
+        if broken.is_empty() {
+            self.logger.success("[Doctor] no problems found");
+            return Ok(OperationReport::new("doctor"));
+        }
 
-        if self.base.join(ADDONS_DIR).exists() {
-            for addon in (std::fs::read_dir(self.base.join(ADDONS_DIR))?).flatten() {
-                if addon.path().is_dir()
-                    && addon
-                        .path()
-                        .file_stem()
-                        .map(|v| !self.rc.get_addons().contains_key(&v.to_string_lossy()))
-                        .unwrap_or_default()
-                {
-                    self.logger.update(format!(
-                        "Removing unknown addon `{}`",
-                        addon.path().file_stem().unwrap().to_string_lossy()
-                    ));
-                    std::fs::remove_dir_all(addon.path())
-                        .map_err(Error::from)
-                        .log_with(
-                            &mut self.logger,
-                            format!("failed to remove directory: {}", addon.path().display()),
-                        );
-                }
+        for name in broken.iter() {
+            self.logger.warning(format!("[{name}] is not a git repository"));
+        }
+        if !repair {
+            self.logger.error(format!(
+                "[Doctor] {} broken addons, re-run with `--repair` to clone them again",
+                broken.len()
+            ));
+            return Ok(OperationReport::new("doctor"));
+        }
+
+        let addons = broken.iter().map(|name| self.rc.get_addons()[name.as_str()].clone()).collect::<Vec<_>>();
+        self.reinstall(addons)
+    }
+
+    /// Remove any addon directories that are not in the config
+    ///
+    /// Refuses to run when the config was not loaded from an existing file, since every
+    /// installed addon would be considered unknown. When more than [`CLEAN_CONFIRM_THRESHOLD`]
+    /// directories would be removed, `yes` must be set to confirm.
+    ///
+    /// Directories named in `exclude` are kept even when they are not in the config, e.g. an addon
+    /// that is developed locally.
+    pub fn clean(&mut self, yes: bool, exclude: &[String]) -> Result<(), Error> {
+        let unknown = self.clean_plan(exclude)?.dirs;
+
+        if !unknown.is_empty() && self.rc.is_new() {
+            return Err(Error::custom(format!(
+                "refusing to clean: no {LUARC} was loaded so all {} installed addons would be removed",
+                unknown.len()
+            )));
+        }
+
+        if unknown.len() > CLEAN_CONFIRM_THRESHOLD && !yes {
+            return Err(Error::custom(format!(
+                "clean would remove {} addon directories; re-run with `--yes` to confirm",
+                unknown.len()
+            )));
+        }
+
+        let mut removed = 0;
+        self.logger.update(format!("Removing {} unknown addons", unknown.len()));
+        for (path, result) in unknown.iter().zip(disk::remove_dirs(&unknown, self.remove_threads)) {
+            let ok = result
+                .map(|_| true)
+                .map_err(Error::from)
+                .log_with_or(
+                    &mut self.logger,
+                    format!("failed to remove directory: {}", path.display()),
+                    false,
+                );
+            if ok {
+                removed += 1;
+            }
+        }
+
+        for entry in self.prune_workspace_paths() {
+            self.logger.update(format!("Removed stale config entry `{entry}`"));
+        }
+
+        if let Err(err) = self.rc.write() {
+            self.logger.error(format!("failed to write updates to {LUARC}: {err}"));
+        }
+
+        self.logger.success(format!("[Clean] Removed {removed}/{} unknown addons", unknown.len()));
+        Ok(())
+    }
+
+    /// Remove temporary clones in `temp_root` and cached remote checksums that are older than
+    /// `age`, leaving recent ones. Clones are left behind when cloning is interrupted.
+    pub fn clean_stale(&mut self, temp_root: &Path, age: Duration, now: SystemTime) -> Result<(), Error> {
+        let stale = disk::older_than(temp_root, age, now);
+        let mut removed = 0;
+        for (path, result) in stale.iter().zip(disk::remove_dirs(&stale, self.remove_threads)) {
+            match result {
+                Ok(()) => removed += 1,
+                Err(err) => self.logger.error(format!("failed to remove {}: {err}", path.display())),
             }
         }
 
-        self.logger.success("[Clean] Finished!");
+        let mut cached = 0;
+        if let Some(cache) = self.cache.as_mut() {
+            cached = cache.remove_older_than(age, now);
+            cache.save(now)?;
+        }
+
+        self.logger.success(format!(
+            "[Clean] Removed {removed}/{} temporary clones and {cached} cached checksums",
+            stale.len()
+        ));
         Ok(())
     }
+
+    /// What [`clean`][Manager::clean] would remove, without removing anything
+    pub fn clean_plan(&mut self, exclude: &[String]) -> Result<CleanPlan, Error> {
+        let dirs = self
+            .unknown_addon_dirs()?
+            .into_iter()
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| !exclude.iter().any(|e| name == e.as_str()))
+                    .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        let entries = self.stale_workspace_paths(&dirs);
+        Ok(CleanPlan { dirs, entries })
+    }
+
+    /// Log what [`clean`][Manager::clean] would remove without changing the addons directory or
+    /// the config
+    pub fn clean_dry_run(&mut self, exclude: &[String]) -> Result<CleanPlan, Error> {
+        let plan = self.clean_plan(exclude)?;
+        for path in plan.dirs.iter() {
+            self.logger.warning(format!("would remove directory {}", path.display()));
+        }
+        for entry in plan.entries.iter() {
+            self.logger.warning(format!("would remove config entry `{entry}`"));
+        }
+        self.logger.success(format!(
+            "[Clean] Would remove {} unknown addons and {} config entries",
+            plan.dirs.len(),
+            plan.entries.len()
+        ));
+        Ok(plan)
+    }
+
+    /// Directories in the addons directory that do not belong to an addon in the config
+    ///
+    /// Only the names in the config are compared, the checkouts are never read, so an addon whose
+    /// checksum can't be determined is still known.
+    fn unknown_addon_dirs(&mut self) -> Result<Vec<PathBuf>, Error> {
+        let addons_dir = self.addons_path();
+        if !addons_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut unknown = Vec::new();
+        for addon in (std::fs::read_dir(addons_dir)?).flatten() {
+            let path = addon.path();
+            if path.is_dir()
+                && path
+                    .file_stem()
+                    .map(|v| {
+                        let name = v.to_string_lossy();
+                        !self.rc.get_addons().contains_key(&name) && !self.rc.in_other_addon_set(&name)
+                    })
+                    .unwrap_or_default()
+            {
+                unknown.push(path);
+            }
+        }
+        unknown.sort();
+        Ok(unknown)
+    }
+
+    /// Remove `workspace.userThirdParty` and `workspace.library` entries that point inside the
+    /// addons directory but no longer exist on disk.
+    ///
+    /// Entries outside of the addons directory are left untouched since they are managed by the user.
+    fn prune_workspace_paths(&mut self) -> Vec<String> {
+        let stale = self.stale_workspace_paths(&[]);
+        if let Some(workspace) = self.rc.workspace.as_mut() {
+            for entries in [&mut workspace.user_third_party, &mut workspace.library] {
+                entries.retain(|entry| !stale.contains(entry));
+            }
+        }
+        stale
+    }
+
+    /// `workspace.userThirdParty` and `workspace.library` entries that point inside the addons
+    /// directory but don't exist, or won't once the `removed` directories are removed
+    fn stale_workspace_paths(&self, removed: &[PathBuf]) -> Vec<String> {
+        let addons_dir = self.addons_path();
+        let is_stale = |entry: &&String| {
+            let path = self.base.join(expand_vars(entry).as_ref());
+            path.starts_with(&addons_dir)
+                && (!path.exists() || removed.iter().any(|dir| path.starts_with(dir)))
+        };
+
+        self.rc
+            .workspace
+            .iter()
+            .flat_map(|workspace| workspace.user_third_party.iter().chain(workspace.library.iter()))
+            .filter(is_stale)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Short description of addons for a commit message, e.g. `busted@v2.0, love2d`
+fn describe(addons: &[Addon]) -> String {
+    addons
+        .iter()
+        .map(|addon| match addon.checksum() {
+            Some(checksum) => format!("{}@{checksum}", addon.name()),
+            None => addon.name().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Unique temporary directory an addon is cloned into before it is moved into the addons directory
+///
+/// A uuid is always included so addons pinned to the same checksum, or the same addon cloned
+/// concurrently, never share a directory.
+fn temp_clone_dir(addon: &Addon) -> PathBuf {
+    let prefix = match addon.checksum() {
+        Some(checksum) => checksum.to_string(),
+        None => addon.name().to_string(),
+    };
+
+    temp_clone_root().join(format!("{prefix}-{}", uuid::Uuid::now_v7()))
+}
+
+/// Whether the addon at `path` is a worktree, e.g. of a shared clone, whose `.git` is a file
+/// pointing at the repository instead of the repository itself
+fn is_worktree(path: &Path) -> bool {
+    path.join(".git").is_file()
+}
+
+/// Directory the temporary clones of every project are made in
+pub fn temp_clone_root() -> PathBuf {
+    std::env::temp_dir().join("llam")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    use crate::test_util::{git, init_remote, TempDir};
+
+    #[cfg(unix)]
+    use crate::test_util::{FakeGit, FAKE_CLONE};
+
+    /// Logger that discards everything so tests don't spawn a spinner
+    pub(crate) use crate::logging::NullLogger as Silent;
+
+    /// Logger that keeps the warnings
+    #[derive(Default)]
+    struct Warnings(Vec<String>);
+    impl Logger for Warnings {
+        fn update(&mut self, _: impl std::fmt::Display) {}
+        fn error(&mut self, _: impl std::fmt::Display) {}
+        fn success(&mut self, _: impl std::fmt::Display) {}
+        fn warning(&mut self, log: impl std::fmt::Display) {
+            self.0.push(log.to_string());
+        }
+        fn prompt(&mut self, question: impl std::fmt::Display) -> Result<String, Error> {
+            Silent.prompt(question)
+        }
+        fn finish(&mut self) {}
+    }
+
+    #[test]
+    fn missing_project_path_is_displayed() {
+        let root = TempDir::new();
+        let dir = root.join("missing \"project\"");
+        let err = Manager::new(&dir, Silent).err().unwrap().to_string();
+        assert_eq!(err, format!("the project path does not exist: {}", dir.display()));
+        assert!(!dir.exists());
+
+        let relative = Manager::new_readonly("missing-project", Silent).err().unwrap().to_string();
+        let absolute = std::env::current_dir().unwrap().join("missing-project");
+        assert_eq!(relative, format!("the project path does not exist: {}", absolute.display()));
+    }
+
+    #[test]
+    fn null_logger_is_the_default() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("orphan")).unwrap();
+        std::fs::write(dir.join(LUARC), "{}").unwrap();
+
+        let mut manager: Manager = Manager::new(&dir, NullLogger).unwrap();
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.set_enabled(["love2d".to_string()], false).unwrap();
+        manager.clean(false, &[]).unwrap();
+        manager.logger.finish();
+
+        assert!(!dir.join(ADDONS_DIR).join("orphan").exists());
+        assert!(!LuaRc::detect(&dir).unwrap().get_addons()["love2d"].enabled);
+    }
+
+    #[test]
+    fn list_in_empty_project_creates_nothing() {
+        let dir = TempDir::new();
+        let mut manager = Manager::new_readonly(&dir, Silent).unwrap();
+        assert!(manager.rc.get_addons().is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn update_check_exit_codes() {
+        let mut check = UpdateCheck::default();
+        assert_eq!(check.exit_code(), 0);
+
+        check.outdated.push(Outdated {
+            name: "love2d".to_string(),
+            current: Some("1234567890".to_string()),
+            latest: Some("abcdef1234".to_string()),
+        });
+        assert_eq!(check.exit_code(), 1);
+        assert_eq!(check.outdated[0].to_string(), "love2d: 1234567 -> abcdef1");
+
+        check.errors.push(("lovr".to_string(), "failed".to_string()));
+        assert_eq!(check.exit_code(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_updates_compares_remote() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                rev-parse) echo 1111111111111111111111111111111111111111 ;;
+                symbolic-ref) echo refs/remotes/origin/main ;;
+                ls-remote) printf '2222222222222222222222222222222222222222\trefs/heads/main\n' ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+
+        let check = manager.check_updates(SomeOrAll::All);
+        assert_eq!(check.exit_code(), 1);
+        assert_eq!(
+            check.outdated,
+            [
+                Outdated {
+                    name: "love2d".to_string(),
+                    current: Some("1111111111111111111111111111111111111111".to_string()),
+                    latest: Some("2222222222222222222222222222222222222222".to_string()),
+                },
+                Outdated { name: "lovr".to_string(), current: None, latest: None },
+            ]
+        );
+
+        assert!(fake.calls_to(&["fetch", "reset"]).is_empty());
+    }
+
+    #[test]
+    fn temp_clone_dirs_are_unique() {
+        let addon = Addon::from("love2d@1234");
+        let fork = Addon::from("https://github.com/someone/love2d@1234");
+
+        let first = temp_clone_dir(&addon);
+        let second = temp_clone_dir(&fork);
+        assert_ne!(first, second);
+        assert_ne!(first, temp_clone_dir(&addon));
+        assert!(first.starts_with(std::env::temp_dir().join("llam")));
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with("1234-"));
+    }
+
+    #[test]
+    fn clean_prunes_stale_workspace_paths() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d/library")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        let workspace = manager.rc.workspace_mut();
+        workspace.user_third_party = vec![ADDONS_DIR.to_string(), "/opt/lua/third-party".to_string()];
+        workspace.library = vec![
+            format!("{ADDONS_DIR}/love2d/library"),
+            format!("{ADDONS_DIR}/lovr/library"),
+            "../shared/library".to_string(),
+        ];
+
+        manager.clean(false, &[]).unwrap();
+
+        let rc = LuaRc::detect(&dir).unwrap();
+        let workspace = rc.workspace.unwrap();
+        assert_eq!(workspace.user_third_party, [ADDONS_DIR, "/opt/lua/third-party"]);
+        assert_eq!(
+            workspace.library,
+            [format!("{ADDONS_DIR}/love2d/library"), "../shared/library".to_string()]
+        );
+    }
+
+    #[test]
+    fn clean_keeps_addons_with_unreadable_checksums() {
+        let dir = TempDir::new();
+        // Not a git repository, so its checksum can't be read
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("orphan")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        let mut addon = Addon::from("love2d");
+        addon.checkout = AddonTarget::DefaultBranch;
+        manager.rc.add_or_update_addon(&addon);
+        manager.rc.write().unwrap();
+        assert!(manager.git.checksum(dir.join(ADDONS_DIR).join("love2d"), None).is_err());
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.clean(false, &[]).unwrap();
+        assert!(dir.join(ADDONS_DIR).join("love2d").exists());
+        assert!(!dir.join(ADDONS_DIR).join("orphan").exists());
+    }
+
+    #[test]
+    fn clean_keeps_excluded_directories() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join(LUARC), "{}").unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("local-addon")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("orphan")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.clean(false, &["local-addon".to_string()]).unwrap();
+        assert!(dir.join(ADDONS_DIR).join("local-addon").exists());
+        assert!(!dir.join(ADDONS_DIR).join("orphan").exists());
+    }
+
+    #[test]
+    fn clean_dry_run_changes_nothing() {
+        let dir = TempDir::new();
+        let config = format!(
+            r#"{{ "workspace": {{ "library": ["{0}/love2d", "{0}/orphan", "{0}/gone", "/opt/lua"] }} }}"#,
+            ADDONS_DIR
+        );
+        std::fs::write(dir.join(LUARC), &config).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("orphan")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        let plan = manager.clean_dry_run(&[]).unwrap();
+        assert_eq!(plan.dirs, [dir.join(ADDONS_DIR).join("orphan")]);
+        assert_eq!(plan.entries, [format!("{ADDONS_DIR}/orphan"), format!("{ADDONS_DIR}/gone")]);
+        assert!(dir.join(ADDONS_DIR).join("orphan").exists());
+        assert_eq!(std::fs::read_to_string(dir.join(LUARC)).unwrap(), config);
+
+        manager.clean(false, &[]).unwrap();
+        assert!(!dir.join(ADDONS_DIR).join("orphan").exists());
+        let library = LuaRc::detect(&dir).unwrap().workspace.unwrap().library;
+        assert_eq!(library, [format!("{ADDONS_DIR}/love2d"), "/opt/lua".to_string()]);
+    }
+
+    #[test]
+    fn clean_stale_temp_clones_and_cache() {
+        let dir = TempDir::new();
+        let temp = dir.join("temp");
+        for name in ["interrupted", "recent"] {
+            std::fs::create_dir_all(temp.join(name)).unwrap();
+        }
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        std::fs::File::open(temp.join("interrupted")).unwrap().set_modified(now - day * 8).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        let mut cache = RemoteCache::load(dir.join(crate::cache::CACHE_FILE), day * 30);
+        cache.insert("https://example.com/old.git", "main", "abc123", now - day * 8);
+        cache.insert("https://example.com/new.git", "main", "def456", now - day);
+        manager.cache = Some(cache);
+
+        manager.clean_stale(&temp, day * 7, now).unwrap();
+        assert!(!temp.join("interrupted").exists());
+        assert!(temp.join("recent").exists());
+        let cache = RemoteCache::load(dir.join(crate::cache::CACHE_FILE), day * 30);
+        assert_eq!(cache.get("https://example.com/old.git", "main", now), None);
+        assert_eq!(cache.get("https://example.com/new.git", "main", now), Some("def456"));
+    }
+
+    #[test]
+    fn clean_refuses_without_loaded_config() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+
+        // No `.luarc.json` exists so the config is created empty
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        assert!(manager.clean(true, &[]).is_err());
+        assert!(dir.join(ADDONS_DIR).join("love2d").exists());
+
+        // A config that genuinely has no addons is allowed to clean
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.clean(false, &[]).unwrap();
+        assert!(!dir.join(ADDONS_DIR).join("love2d").exists());
+    }
+
+    #[test]
+    fn clean_requires_confirmation_over_threshold() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join(LUARC), "{}").unwrap();
+        for i in 0..=CLEAN_CONFIRM_THRESHOLD {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(format!("addon-{i}"))).unwrap();
+        }
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        assert!(manager.clean(false, &[]).is_err());
+        assert_eq!(
+            std::fs::read_dir(dir.join(ADDONS_DIR)).unwrap().count(),
+            CLEAN_CONFIRM_THRESHOLD + 1
+        );
+
+        manager.clean(true, &[]).unwrap();
+        assert_eq!(std::fs::read_dir(dir.join(ADDONS_DIR)).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timings_record_git_operations() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo develop; else echo abc123; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.timings = Timings::new(true);
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.update(SomeOrAll::All).unwrap();
+
+        let operations = manager
+            .timings
+            .entries()
+            .iter()
+            .map(|t| (t.addon.as_str(), t.operation))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operations,
+            [("love2d", "fetch"), ("love2d", "switch"), ("love2d", "pull")]
+        );
+        assert!(manager.timings.to_string().contains("love2d"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_prefers_branch_when_it_exists() {
+        let fake = FakeGit::new(&format!(
+            "{FAKE_CLONE}\n{}",
+            r#"if [ "$1" = "ls-remote" ] && [ "$5" != "develop" ]; then exit 2; fi"#
+        ));
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.prefer_branch = Some("develop".to_string());
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert_eq!(
+            manager.rc.get_addons()["love2d"].branch(),
+            Some("develop")
+        );
+
+        assert!(fake.calls().contains(&"switch develop".to_string()));
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.prefer_branch = Some("staging".to_string());
+        manager.add([Addon::from("lovr")], AddOptions::default()).unwrap();
+        assert_eq!(manager.rc.get_addons()["lovr"].branch(), None);
+
+        assert!(!fake.calls().contains(&"switch staging".to_string()));
+
+        // A tag isn't replaced by the preferred branch
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.prefer_branch = Some("develop".to_string());
+        manager.add([Addon::from("luassert@v1.9")], AddOptions::default()).unwrap();
+        assert_eq!(manager.rc.get_addons()["luassert"].checkout, AddonTarget::Tag("v1.9".to_string()));
+    }
+
+    #[test]
+    fn disabled_addons_are_not_exposed() {
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d/library")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("lovr")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        manager.rc.workspace_mut().library.push("/usr/share/lua".to_string());
+
+        manager.set_enabled(["love2d".to_string()], false).unwrap();
+
+        let rc = LuaRc::detect(&dir).unwrap();
+        let workspace = rc.workspace.as_ref().unwrap();
+        assert_eq!(workspace.library, ["/usr/share/lua".to_string(), format!("{ADDONS_DIR}/lovr")]);
+        assert!(!workspace.addons["love2d"].enabled);
+        assert!(dir.join(ADDONS_DIR).join("love2d").exists());
+
+        manager.set_enabled(["love2d".to_string()], true).unwrap();
+        let rc = LuaRc::detect(&dir).unwrap();
+        let workspace = rc.workspace.as_ref().unwrap();
+        assert!(workspace.library.contains(&format!("{ADDONS_DIR}/love2d/library")));
+        assert!(workspace.addons["love2d"].enabled);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_resolves_dependencies() {
+        let fake = FakeGit::new(&format!(
+            "{FAKE_CLONE}\n{}",
+            indoc::indoc! {r#"
+                for last; do :; done
+                case "$3" in
+                    */a.git) echo '{"dependencies": ["b"]}' > "$last/config.json" ;;
+                    */b.git) echo '{"dependencies": ["c"]}' > "$last/config.json" ;;
+                    */x.git) echo '{"dependencies": ["y"]}' > "$last/config.json" ;;
+                    */y.git) echo '{"dependencies": ["x"]}' > "$last/config.json" ;;
+                esac
+            "#}
+        ));
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager
+            .add([Addon::from("a"), Addon::from("x")], AddOptions::default())
+            .unwrap();
+
+        for name in ["a", "b", "c", "x", "y"] {
+            assert!(dir.join(ADDONS_DIR).join(name).exists(), "{name}");
+        }
+
+        let addons = manager.rc.get_addons();
+        assert!(!addons["a"].dependency);
+        assert!(addons["a"].required_by.is_empty());
+        assert!(addons["b"].dependency);
+        assert_eq!(addons["b"].required_by, BTreeSet::from(["a".to_string()]));
+        assert!(addons["c"].dependency);
+        assert_eq!(addons["c"].required_by, BTreeSet::from(["b".to_string()]));
+
+        // The cycle is only cloned once per addon
+        assert!(!addons["x"].dependency);
+        assert_eq!(addons["x"].required_by, BTreeSet::from(["y".to_string()]));
+        assert_eq!(addons["y"].required_by, BTreeSet::from(["x".to_string()]));
+        assert_eq!(fake.calls_to(&["clone"]).len(), 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_detects_directory_collisions() {
+        let fake = FakeGit::new(&format!(
+            "{FAKE_CLONE}\n{}",
+            indoc::indoc! {r#"
+                for last; do :; done
+                case "$3" in
+                    */someone/lib*) echo '{"dependencies": ["https://github.com/b/utils"]}' > "$last/config.json" ;;
+                esac
+            "#}
+        ));
+        let a = || Addon::from("https://github.com/a/utils");
+        let b = || Addon::from("https://github.com/b/utils");
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+
+        // Both requested in the same batch, nothing is cloned
+        let err = manager.add([a(), b()], AddOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("--name"), "{err}");
+        assert_eq!(fake.calls_to(&["clone"]).len(), 0);
+
+        // A dependency that collides with an addon of the same batch
+        let report = manager
+            .add([a(), Addon::from("https://github.com/someone/lib")], AddOptions::default())
+            .unwrap();
+        assert_eq!(report.count(Outcome::Added), 2);
+        assert_eq!(report.count(Outcome::Failed), 1);
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+
+        // An addon that collides with one in the config
+        let report = manager.add([b()], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+        assert!(manager.rc.get_addons()["utils"].same_repository(&a()));
+
+        let mut renamed = b();
+        renamed.alias = Some("b-utils".to_string());
+        let report = manager.add([renamed], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Added);
+        assert_eq!(fake.calls_to(&["clone"]).len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_without_updating_luarc() {
+        let fake = FakeGit::new(FAKE_CLONE);
+
+        let dir = TempDir::new();
+        std::fs::write(dir.join(LUARC), "{\n  \"runtime\": { \"version\": \"LuaJIT\" }\n}").unwrap();
+        let before = std::fs::read(dir.join(LUARC)).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager
+            .add(
+                [Addon::from("love2d")],
+                AddOptions {
+                    update_luarc: false,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(dir.join(ADDONS_DIR).join("love2d").is_dir());
+        assert_eq!(std::fs::read(dir.join(LUARC)).unwrap(), before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_records_outcomes() {
+        let script = format!(
+            "if [ \"$1\" = \"rev-parse\" ]; then echo {}; fi\n{FAKE_CLONE}",
+            "1".repeat(40)
+        );
+        let fake = FakeGit::new(&script);
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("lovr")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        let report = manager.add([Addon::from("love2d"), Addon::from("lovr")], AddOptions::default()).unwrap();
+
+        let file = dir.join("report.json");
+        report.write(&file).unwrap();
+        let report: OperationReport = serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+
+        assert_eq!(report.operation, "add");
+        let outcomes = report
+            .addons
+            .iter()
+            .map(|a| (a.name.as_str(), a.outcome, a.old_checksum.is_some(), a.new_checksum.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [("love2d", Outcome::Added, false, true), ("lovr", Outcome::Skipped, true, true)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_follows_renamed_default_branch() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1 $2" in
+                "symbolic-ref "*) echo refs/remotes/origin/master ;;
+                "rev-parse --abbrev-ref") echo master ;;
+                "rev-parse "*) echo abc123 ;;
+                "ls-remote --exit-code") if [ "$5" = "master" ]; then exit 2; fi ;;
+                "ls-remote --symref") printf 'ref: refs/heads/main\tHEAD\nabc123\tHEAD\n' ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("lovr")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        let mut lovr = Addon::from("lovr");
+        lovr.checkout = AddonTarget::Branch("master".to_string());
+        manager.rc.add_or_update_addon(&lovr);
+        // Checked out at a tag or commit, there is no branch to check
+        for pinned in ["luassert@v1.9", "busted@1234567"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(Addon::from(pinned).name().as_ref())).unwrap();
+            manager.rc.add_or_update_addon(&Addon::from(pinned));
+        }
+        manager.update(SomeOrAll::All).unwrap();
+
+        assert_eq!(manager.rc.get_addons()["love2d"].branch(), None);
+        assert_eq!(manager.rc.get_addons()["lovr"].branch(), Some("main"));
+        assert_eq!(manager.rc.get_addons()["luassert"].branch(), None);
+
+        assert_eq!(fake.calls_to(&["switch"]), ["switch main", "switch main"]);
+        assert_eq!(
+            fake.calls_to(&["ls-remote"]).iter().filter(|call| call.starts_with("ls-remote --exit-code")).count(),
+            2
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_reports_mixed_results() {
+        let script = format!("case \"$*\" in clone*lovr*) exit 1 ;; esac\n{FAKE_CLONE}");
+        let fake = FakeGit::new(&script);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let report = manager
+            .add([Addon::from("love2d"), Addon::from("lovr")], AddOptions::default())
+            .unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.addons.iter().map(|a| (a.name.as_str(), a.outcome)).collect::<Vec<_>>(),
+            [("love2d", Outcome::Added), ("lovr", Outcome::Failed)]
+        );
+        let failed = report.failures().collect::<Vec<_>>();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].error.as_deref(), Some("failed to clone addon: lovr"));
+
+        let report = manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.operation, "remove");
+        assert_eq!(report.addons[0].outcome, Outcome::Removed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_sets_schema_of_created_config() {
+        let fake = FakeGit::new(FAKE_CLONE);
+        let schema = |dir: &Path| LuaRc::detect(dir).unwrap().schema;
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert_eq!(schema(&dir).as_deref(), Some(LUALS_SCHEMA));
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.luarc_schema = false;
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert_eq!(schema(&dir), None);
+
+        // A schema set by the user is kept
+        let dir = TempDir::new();
+        std::fs::write(dir.join(LUARC), r#"{ "$schema": "./schema.json" }"#).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert_eq!(schema(&dir).as_deref(), Some("./schema.json"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_addons_dir() {
+        let fake = FakeGit::new(FAKE_CLONE);
+        let workspace = |dir: &Path| LuaRc::detect(dir).unwrap().workspace.unwrap();
+
+        // Outside of the project the directory is referenced absolute and never ignored
+        let dir = TempDir::new();
+        let addons = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.addons_dir = addons.to_path_buf();
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert!(addons.join("love2d").exists());
+        assert!(!dir.join(ADDONS_DIR).exists());
+        assert!(!dir.join(GITIGNORE).exists());
+        let entry = addons.to_string_lossy().to_string();
+        assert_eq!(workspace(&dir).user_third_party, [entry.as_str()]);
+        assert_eq!(workspace(&dir).library, [format!("{entry}/love2d")]);
+
+        manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert!(!addons.join("love2d").exists());
+        assert!(workspace(&dir).library.is_empty());
+
+        // Inside of the project it is stored relative
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.addons_dir = dir.join("vendor/addons");
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert!(dir.join("vendor/addons/love2d").exists());
+        assert_eq!(workspace(&dir).user_third_party, ["vendor/addons"]);
+        assert_eq!(workspace(&dir).library, ["vendor/addons/love2d"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_counts_outcomes_separately() {
+        let script = format!("case \"$*\" in clone*lovr*) exit 1 ;; esac\n{FAKE_CLONE}");
+        let fake = FakeGit::new(&script);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.add([Addon::from("love2d"), Addon::from("busted")], AddOptions::default()).unwrap();
+
+        let addons = ["love2d", "luassert", "busted", "lovr"].map(Addon::from);
+        let report = manager.add(addons, AddOptions::default()).unwrap();
+        assert_eq!(report.count(Outcome::Added), 1);
+        assert_eq!(report.count(Outcome::Skipped), 2);
+        assert_eq!(report.count(Outcome::Failed), 1);
+        assert_eq!(report.summary(), "1 added, 2 up-to-date, 1 failed");
+        assert_eq!(OperationReport::new("add").summary(), "nothing to do");
+    }
+
+    #[test]
+    fn projects_share_one_clone() {
+        let root = TempDir::new();
+        let remote = root.join("love2d");
+        let url = init_remote(&remote);
+
+        let shared = root.join("shared");
+        let projects = [root.join("first"), root.join("second")];
+        for dir in projects.iter() {
+            std::fs::create_dir_all(dir).unwrap();
+            let mut manager = Manager::new(dir, Silent).unwrap();
+            manager.shared_clones = Some(shared.clone());
+            let report = manager.add([Addon::from(url.as_str())], AddOptions::default()).unwrap();
+            assert!(report.is_success(), "{report:?}");
+        }
+
+        assert_eq!(std::fs::read_dir(&shared).unwrap().count(), 1);
+        let clone = shared::clone_dir(&shared, &url);
+        for dir in projects.iter() {
+            let addon = dir.join(ADDONS_DIR).join("love2d");
+            assert!(addon.join("config.json").exists());
+            // A worktree's `.git` is a file pointing into the shared clone
+            let git_file = std::fs::read_to_string(addon.join(".git")).unwrap();
+            let worktrees = clone.join(".git").join("worktrees");
+            assert!(git_file.contains(&worktrees.display().to_string()), "{git_file}");
+        }
+
+        // Both detached worktrees move to the new commit without checking out `main`
+        std::fs::write(remote.join("config.json"), r#"{ "version": "2" }"#).unwrap();
+        git(&remote, &["commit", "-q", "-am", "second"]);
+        let latest = Cli::default().checksum(&remote, None).unwrap();
+        for dir in projects.iter() {
+            let mut manager = Manager::new(dir, Silent).unwrap();
+            manager.shared_clones = Some(shared.clone());
+            let report = manager.update(SomeOrAll::All).unwrap();
+            assert!(report.is_success(), "{report:?}");
+            assert_eq!(report.addons[0].outcome, Outcome::Updated);
+
+            let addon = dir.join(ADDONS_DIR).join("love2d");
+            assert_eq!(manager.git.checksum(&addon, None).unwrap(), latest);
+            assert_eq!(manager.git.branch_name(&addon).unwrap(), "HEAD");
+        }
+    }
+
+    #[test]
+    fn update_to_tag_and_commit() {
+        let root = TempDir::new();
+        let remote = root.join("love2d");
+        let url = init_remote(&remote);
+        let mut commits = Vec::new();
+        for version in ["1", "2", "3"] {
+            std::fs::write(remote.join("config.json"), format!(r#"{{ "version": "{version}" }}"#)).unwrap();
+            git(&remote, &["add", "."]);
+            git(&remote, &["commit", "-q", "-m", version]);
+            commits.push(Cli::default().checksum(&remote, None).unwrap());
+        }
+        git(&remote, &["tag", "v1.0", &commits[0]]);
+
+        let dir = root.join("project");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        manager.add([Addon::from(url.as_str())], AddOptions::default()).unwrap();
+        let path = dir.join(ADDONS_DIR).join("love2d");
+
+        let report = manager.update_to(SomeOrAll::Some(vec![Addon::from("love2d")]), "v1.0").unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(manager.git.checksum(&path, None).unwrap(), commits[0]);
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert_eq!(rc.get_addons()["love2d"].checkout, AddonTarget::Tag("v1.0".to_string()));
+
+        let report = manager.update_to(SomeOrAll::Some(vec![Addon::from("love2d")]), &commits[1][..7]).unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(manager.git.checksum(&path, None).unwrap(), commits[1]);
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert_eq!(rc.get_addons()["love2d"].checkout, AddonTarget::Commit(commits[1].clone()));
+
+        // A branch drops the pinned commit instead of resetting back to it
+        git(&remote, &["branch", "dev", &commits[0]]);
+        let report = manager.update_to(SomeOrAll::Some(vec![Addon::from("love2d")]), "branch:dev").unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(manager.git.checksum(&path, None).unwrap(), commits[0]);
+        assert_eq!(manager.git.branch_name(&path).unwrap(), "dev");
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert_eq!(rc.get_addons()["love2d"].checkout, AddonTarget::Branch("dev".to_string()));
+
+        let report = manager.update_to(SomeOrAll::Some(vec![Addon::from("love2d")]), "default").unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(manager.git.checksum(&path, None).unwrap(), commits[2]);
+        assert_eq!(manager.git.branch_name(&path).unwrap(), "main");
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert_eq!(rc.get_addons()["love2d"].checkout, AddonTarget::DefaultBranch);
+
+        // Only a single addon can be moved
+        assert!(manager.update_to(SomeOrAll::All, "v1.0").is_err());
+        let both = vec![Addon::from("love2d"), Addon::from("lovr")];
+        assert!(manager.update_to(SomeOrAll::Some(both), "v1.0").is_err());
+        assert!(manager.update_to(SomeOrAll::Some(vec![Addon::from("lovr")]), "v1.0").is_err());
+    }
+
+    #[test]
+    fn recover_addons_after_broken_config() {
+        let root = TempDir::new();
+        let remote = root.join("love2d");
+        let url = init_remote(&remote);
+
+        let dir = root.join("project");
+        let addons = dir.join(ADDONS_DIR);
+        std::fs::create_dir_all(&addons).unwrap();
+        git(&addons, &["clone", "-q", &url, "love2d"]);
+        git(&addons, &["clone", "-q", &url, "love"]);
+        git(&addons.join("love"), &["switch", "-q", "--detach"]);
+        std::fs::write(dir.join(LUARC), r#"{ "workspace": { "addons": [] } }"#).unwrap();
+
+        let Err(Error::InvalidConfig(config, _)) = Manager::new(&dir, Silent) else {
+            panic!("expected the config to be invalid");
+        };
+        let backup = LuaRc::backup(&config).unwrap();
+        assert!(backup.exists() && !config.exists());
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        assert_eq!(manager.recover_addons().unwrap(), ["love", "love2d"]);
+
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        let checksum = Cli::default().checksum(addons.join("love"), None).unwrap();
+        let love = &rc.get_addons()["love"];
+        assert_eq!((love.alias.as_deref(), love.checksum()), (Some("love"), Some(checksum.as_str())));
+        let love2d = &rc.get_addons()["love2d"];
+        assert_eq!((love2d.alias.as_deref(), love2d.branch()), (None, Some("main")));
+        let workspace = rc.workspace.unwrap();
+        assert_eq!(workspace.user_third_party, [ADDONS_DIR]);
+        assert_eq!(workspace.library, [format!("{ADDONS_DIR}/love"), format!("{ADDONS_DIR}/love2d")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn profiles_share_clones() {
+        let fake = FakeGit::new(FAKE_CLONE);
+        let library = |dir: &Path| LuaRc::detect(dir).unwrap().workspace.unwrap().library;
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.add([Addon::from("love2d"), Addon::from("lovr")], AddOptions::default()).unwrap();
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+
+        manager.rc.set_profile(Some("gamedev".to_string()));
+        let report = manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+        assert_eq!(library(&dir), [format!("{ADDONS_DIR}/love2d")]);
+
+        // The default addons are still installed
+        manager.clean(false, &[]).unwrap();
+        assert!(dir.join(ADDONS_DIR).join("lovr").exists());
+
+        manager.rc.set_profile(None);
+        manager.set_enabled(Vec::new(), true).unwrap();
+        assert_eq!(library(&dir), [format!("{ADDONS_DIR}/love2d"), format!("{ADDONS_DIR}/lovr")]);
+
+        // Removing an addon from one profile keeps the clone for the other
+        manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert!(dir.join(ADDONS_DIR).join("love2d").exists());
+        let mut rc = LuaRc::detect(&dir).unwrap();
+        assert!(rc.workspace.as_ref().unwrap().profiles["gamedev"].contains_key("love2d"));
+        assert!(!rc.get_addons().contains_key("love2d"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lfs_files_are_pulled() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                clone)
+                    for last; do :; done
+                    mkdir -p "$last"
+                    case "$last" in *lovr*) echo "*.png filter=lfs diff=lfs" > "$last/.gitattributes" ;; esac ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.add([Addon::from("lovr")], AddOptions::default()).unwrap();
+        assert!(fake.calls_to(&["lfs"]).is_empty());
+
+        manager.lfs = true;
+        manager.add([Addon::from("love2d")], AddOptions::default()).unwrap();
+        assert!(fake.calls_to(&["lfs"]).is_empty());
+        manager.reinstall(vec![Addon::from("lovr")]).unwrap();
+        assert_eq!(fake.calls_to(&["lfs"]), ["lfs version", "lfs pull"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn duplicate_addons_are_processed_once() {
+        let fake = FakeGit::new(FAKE_CLONE);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let report = manager
+            .add(["love2d", "love2d", "lovr"].map(Addon::from), AddOptions::default())
+            .unwrap();
+        assert_eq!(report.summary(), "2 added");
+
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+
+        let url = "https://github.com/LuaCATS/love2d";
+        let report = manager.remove(vec![Addon::from("love2d"), Addon::from(url)]).unwrap();
+        assert_eq!(report.summary(), "1 removed");
+    }
+
+    #[test]
+    fn duplicate_names_with_different_sources_conflict() {
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+
+        let addons = vec![Addon::from("love2d"), Addon::from("https://github.com/someone/love2d")];
+        assert!(manager.add(addons.clone(), AddOptions::default()).is_err());
+        assert!(manager.update(addons).is_err());
+        assert!(manager.update(vec![Addon::from("love2d"), Addon::from("love2d@1234567")]).is_err());
+        assert!(!dir.join(ADDONS_DIR).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_warns_about_old_luals() {
+        let script = format!(
+            "{FAKE_CLONE}\ncase \"$*\" in clone*love2d*) for last; do :; done; echo '{{\"minVersion\": \"3.8\"}}' > \"$last/config.json\" ;; esac"
+        );
+        let fake = FakeGit::new(&script);
+
+        for (installed, warned) in [(Some("3.7.4"), true), (Some("3.8.0"), false), (None, false)] {
+            let dir = TempDir::new();
+            let mut manager = Manager::new(&dir, Warnings::default())
+                .unwrap()
+                .with_git(fake.cli())
+                .with_luals_version(installed.map(|v| Version::from_str(v).unwrap()));
+            manager.add(["love2d", "lovr"].map(Addon::from), AddOptions::default()).unwrap();
+
+            let warnings = manager.logger.0.iter().filter(|w| w.contains("requires luals")).collect::<Vec<_>>();
+            if warned {
+                assert_eq!(warnings, ["[love2d] requires luals 3.8 or newer but 3.7.4 is installed"]);
+            } else {
+                assert!(warnings.is_empty(), "{warnings:?}");
+            }
+
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn addons_contribute_ignore_dirs() {
+        let script = [FAKE_CLONE, indoc::indoc! {r#"
+            for last; do :; done
+            case "$*" in
+                clone*love2d*) echo '{"settings": {"Lua.workspace.ignoreDir": ["build", "tmp"]}}' > "$last/config.json" ;;
+                clone*lovr*) echo '{"settings": {"Lua.workspace.ignoreDir": ["tmp"]}}' > "$last/config.json" ;;
+            esac
+        "#}]
+        .join("\n");
+        let fake = FakeGit::new(&script);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.workspace_mut().add_ignore_dirs(["build".to_string()]);
+        manager.add(["love2d", "lovr"].map(Addon::from), AddOptions::default()).unwrap();
+
+        let ignored = |manager: &Manager<Silent>| manager.rc.workspace.as_ref().unwrap().ignore_dir.clone();
+        assert_eq!(ignored(&manager), ["build", "tmp"]);
+        // `build` was added by the user
+        assert_eq!(manager.rc.get_addons()["love2d"].ignore_dirs, ["tmp"]);
+        assert_eq!(manager.rc.get_addons()["lovr"].ignore_dirs, ["tmp"]);
+
+        manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert_eq!(ignored(&manager), ["build", "tmp"]);
+        manager.remove(vec![Addon::from("lovr")]).unwrap();
+        assert_eq!(ignored(&manager), ["build"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_skips_repository_installed_under_alias() {
+        let fake = FakeGit::new(FAKE_CLONE);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let aliased = Addon::builder().url("https://github.com/someone/love2d").name("love").build().unwrap();
+        manager.add([aliased], AddOptions::default()).unwrap();
+        assert_eq!(fake.calls_to(&["clone"]).len(), 1);
+
+        let report = manager.add([Addon::from("https://github.com/someone/love2d.git")], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Skipped);
+        assert_eq!(fake.calls_to(&["clone"]).len(), 1);
+        assert!(!dir.join(ADDONS_DIR).join("love2d").exists());
+        assert_eq!(manager.rc.get_addons().keys().collect::<Vec<_>>(), ["love"]);
+
+        let report = manager
+            .add(
+                [Addon::from("https://github.com/someone/love2d")],
+                AddOptions { force: true, ..Default::default() },
+            )
+            .unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Added);
+        assert_eq!(fake.calls_to(&["clone"]).len(), 2);
+        assert_eq!(manager.rc.get_addons().keys().collect::<Vec<_>>(), ["love", "love2d"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_with_unknown_checksum_rolls_back() {
+        let fake = FakeGit::new(&format!("{FAKE_CLONE}\ncase \"$1\" in reset) exit 1 ;; esac"));
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let report = manager.add([Addon::from("love2d@1234567"), Addon::from("lovr")], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert_eq!(report.addons[1].outcome, Outcome::Added);
+        assert!(!dir.join(ADDONS_DIR).join("love2d").exists());
+        assert!(!manager.rc.get_addons().contains_key("love2d"));
+        assert!(!LuaRc::detect(&dir).unwrap().get_addons().contains_key("love2d"));
+
+        // Only cloning without recording the addon also cleans up
+        let options = AddOptions { update_luarc: false, ..Default::default() };
+        let report = manager.add([Addon::from("love2d@1234567")], options).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert!(!dir.join(ADDONS_DIR).join("love2d").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_verifies_addon_layout() {
+        // `lovr` is a plain repository, `love2d` ships a luals config
+        let script = format!(
+            "{FAKE_CLONE}\ncase \"$*\" in clone*love2d*) for last; do :; done; echo {{}} > \"$last/config.json\" ;; esac"
+        );
+        let fake = FakeGit::new(&script);
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let addons = || ["love2d", "lovr"].map(Addon::from);
+        let report = manager.add(addons(), AddOptions::default()).unwrap();
+        assert_eq!(report.summary(), "2 added");
+        manager.remove(SomeOrAll::All).unwrap();
+
+        let options = AddOptions { strict: true, ..Default::default() };
+        let report = manager.add(addons(), options).unwrap();
+        assert_eq!(report.summary(), "1 added, 1 failed");
+        let failure = report.failures().next().unwrap();
+        assert_eq!(failure.name, "lovr");
+        assert!(failure.error.as_deref().unwrap().contains("doesn't look like a luals addon"));
+        assert!(!dir.join(ADDONS_DIR).join("lovr").exists());
+        assert!(!manager.rc.get_addons().contains_key("lovr"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mirrors_are_used_when_the_primary_fails() {
+        let fake = FakeGit::new(&format!(indoc::indoc! {r#"
+            case "$*" in
+                clone*github.com*) exit 1 ;;
+                "fetch -p") exit 1 ;;
+                symbolic-ref*) echo "refs/remotes/origin/main" ;;
+                rev-parse\ --abbrev-ref*) echo main ;;
+                rev-parse*) echo abc123 ;;
+            esac
+            {}
+        "#}, FAKE_CLONE));
+
+        let dir = TempDir::new();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let addon = Addon::builder()
+            .url("love2d")
+            .checksum("1234567")
+            .mirror("https://mirror.example.com/love2d.git")
+            .build()
+            .unwrap();
+        let report = manager.add([addon.clone()], AddOptions::default()).unwrap();
+        assert_eq!(report.summary(), "1 added");
+        assert_eq!(manager.rc.get_addons()["love2d"], addon);
+
+        let report = manager.update(SomeOrAll::All).unwrap();
+        assert!(report.is_success(), "{report:?}");
+
+        let calls = fake
+            .calls_to(&["clone", "fetch", "reset"])
+            .into_iter()
+            // Drop the clone's progress flag and temporary directory name
+            .map(|l| match l.strip_prefix("clone --progress ") {
+                Some(args) => format!("clone {}", args.split(' ').next().unwrap()),
+                None => l.to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "clone https://github.com/LuaCATS/love2d.git",
+                "clone https://mirror.example.com/love2d.git",
+                // `add` checks out the requested commit right after cloning
+                "reset --hard 1234567",
+                "fetch -p",
+                "fetch -p https://mirror.example.com/love2d.git +refs/heads/*:refs/remotes/origin/*",
+                "reset --hard 1234567",
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_by_name_or_url() {
+        let dir = TempDir::new();
+        for name in ["love2d", "lovr", "utils", "tools", "helpers"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(name)).unwrap();
+        }
+
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        let url = "https://github.com/someone/utils";
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        manager.rc.add_or_update_addon(&Addon::builder().url(url).name("tools").build().unwrap());
+
+        // Name match
+        manager.remove(vec![Addon::from("love2d")]).unwrap();
+        assert!(!manager.rc.get_addons().contains_key("love2d"));
+        assert!(!dir.join(ADDONS_DIR).join("love2d").exists());
+
+        // Url match installed under an alias
+        let report = manager.remove(vec![Addon::from(url)]).unwrap();
+        assert_eq!(report.addons[0].name, "tools");
+        assert!(!manager.rc.get_addons().contains_key("tools"));
+        assert!(!dir.join(ADDONS_DIR).join("tools").exists());
+        assert!(dir.join(ADDONS_DIR).join("utils").exists());
+
+        // Ambiguous url match
+        for alias in ["utils", "helpers"] {
+            manager.rc.add_or_update_addon(&Addon::builder().url(url).name(alias).build().unwrap());
+        }
+        let err = manager.remove(vec![Addon::from(url)]).unwrap_err();
+        assert!(err.to_string().contains("helpers, utils"), "{err}");
+        assert!(manager.rc.get_addons().contains_key("utils"));
+        assert!(manager.rc.get_addons().contains_key("lovr"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stored_depth_is_used() {
+        let script = indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo develop; else echo abc123; fi ;;
+            esac
+        "#};
+        let fake = FakeGit::new(&format!("{script}{FAKE_CLONE}"));
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let addon = Addon::builder().url("love2d").depth(1).build().unwrap();
+        manager.rc.add_or_update_addon(&addon);
+        manager.rc.write().unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let addon = manager.rc.get_addons()["love2d"].clone();
+        assert_eq!(addon.depth, Some(1));
+        manager.clone_addon(&addon).unwrap();
+        manager.update(SomeOrAll::All).unwrap();
+
+        manager.depth = Some(5);
+        manager.clone_addon(&addon).unwrap();
+
+        let calls = fake
+            .calls_to(&["clone", "fetch"])
+            .iter()
+            .map(|l| l.split(' ').take(3).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            ["clone --progress --depth=1", "fetch -p --depth=1", "clone --progress --depth=5"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_local_branch() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo feature; else echo abc123; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+
+
+        manager.keep_local_branch = true;
+        manager.update(SomeOrAll::All).unwrap();
+        assert!(fake.calls_to(&["switch", "reset"]).is_empty());
+
+        manager.keep_local_branch = false;
+        manager.update(SomeOrAll::All).unwrap();
+        assert_eq!(fake.calls_to(&["switch", "reset"]), ["switch main"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pinned_addons_are_not_updated() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo feature; else echo abc123; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.set_pinned(["love2d".to_string()], true).unwrap();
+        assert!(LuaRc::detect(&dir).unwrap().get_addons()["love2d"].pinned);
+
+        let switched = || fake.log().contains("switch main");
+
+        let report = manager.update(SomeOrAll::All).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Skipped);
+        manager.update(vec![Addon::from("love2d")]).unwrap();
+        assert!(!switched());
+
+        // Explicitly named addons can be forced, `--all` still skips them
+        manager.force_pinned = true;
+        manager.update(SomeOrAll::All).unwrap();
+        assert!(!switched());
+        manager.update(vec![Addon::from("love2d")]).unwrap();
+        assert!(switched());
+        assert!(manager.rc.get_addons()["love2d"].pinned);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reinstall_restores_recorded_checksum() {
+        let fake = FakeGit::new(FAKE_CLONE);
+
+        let dir = TempDir::new();
+        let addon_dir = dir.join(ADDONS_DIR).join("love2d");
+        std::fs::create_dir_all(&addon_dir).unwrap();
+        std::fs::write(addon_dir.join("corrupted"), "").unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let addon = Addon::from("love2d@1234567");
+        manager.rc.add_or_update_addon(&addon);
+
+        let report = manager.reinstall(vec![Addon::from("love2d")]).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Reinstalled);
+        assert!(addon_dir.is_dir());
+        assert!(!addon_dir.join("corrupted").exists());
+        assert_eq!(manager.rc.get_addons()["love2d"], addon);
+
+        let calls = fake
+            .calls_to(&["clone", "switch", "reset"])
+            .into_iter()
+            .map(|l| l.split(' ').take(3).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "clone --progress https://github.com/LuaCATS/love2d.git",
+                "reset --hard 1234567",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gc_runs_for_every_addon() {
+        let fake = FakeGit::new(r#"if [ "$1" = "gc" ]; then rm -f garbage; fi"#);
+
+        let dir = TempDir::new();
+        for name in ["love2d", "lovr"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(name)).unwrap();
+            std::fs::write(dir.join(ADDONS_DIR).join(name).join("garbage"), [0; 2048]).unwrap();
+        }
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+
+        assert_eq!(manager.gc(SomeOrAll::All).unwrap(), 4096);
+        assert_eq!(
+            fake.calls(),
+            ["remote prune origin", "gc --quiet --prune=now", "remote prune origin", "gc --quiet --prune=now"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn doctor_repairs_addons_without_git() {
+        let fake = FakeGit::new(FAKE_CLONE);
+
+        let dir = TempDir::new();
+        let addons = dir.join(ADDONS_DIR);
+        std::fs::create_dir_all(addons.join("love2d")).unwrap();
+        std::fs::create_dir_all(addons.join("lovr").join(".git")).unwrap();
+
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d@1234567"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        assert_eq!(manager.broken_addons(), ["love2d"]);
+
+        // Only reported without `repair`
+        let report = manager.doctor(false).unwrap();
+        assert!(report.addons.is_empty());
+        assert!(fake.calls().is_empty());
+
+        let report = manager.doctor(true).unwrap();
+        assert_eq!(report.addons.len(), 1);
+        assert_eq!(report.addons[0].outcome, Outcome::Reinstalled);
+        let calls = fake.log();
+        assert!(calls.contains("clone --progress https://github.com/LuaCATS/love2d.git"), "{calls}");
+        assert!(calls.contains("reset --hard 1234567"), "{calls}");
+        assert!(!calls.contains("lovr"), "{calls}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_updates_present_addons() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc123; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+
+        let report = manager.add([Addon::from("love2d@1234567")], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Skipped);
+        assert!(fake.calls_to(&["reset"]).is_empty());
+
+        let options = AddOptions { update: true, ..Default::default() };
+        let report = manager.add([Addon::from("love2d@1234567")], options).unwrap();
+        assert_eq!(report.addons.len(), 1);
+        assert_eq!(fake.calls_to(&["reset"]), ["reset --hard 1234567"]);
+        assert_eq!(manager.rc.get_addons()["love2d"].checksum(), Some("1234567"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_update_rolls_back() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc123; fi ;;
+                reset) if [ "$3" = "1234567" ]; then exit 1; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+
+        let report = manager.update(vec![Addon::from("love2d@1234567")]).unwrap();
+
+        let entry = &report.addons[0];
+        assert_eq!(entry.outcome, Outcome::Failed);
+        assert!(entry.rolled_back);
+        assert_eq!(entry.old_checksum.as_deref(), Some("abc123"));
+        assert_eq!(entry.new_checksum.as_deref(), Some("abc123"));
+        assert_eq!(manager.rc.get_addons()["love2d"], Addon::from("love2d"));
+
+        let changes = fake.calls_to(&["switch", "reset"]);
+        assert_eq!(
+            changes,
+            ["reset --hard 1234567", "switch main", "reset --hard abc123"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_addon_fails_only_that_addon() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse)
+                    case "$PWD" in */love2d) exit 1 ;; esac
+                    if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc1234; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        for name in ["love2d", "lovr"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(name)).unwrap();
+        }
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+
+        let report = manager.update(vec![Addon::from("love2d@1111111"), Addon::from("lovr@def4567")]).unwrap();
+        assert_eq!(report.addons[0].name, "love2d");
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert!(!report.addons[0].rolled_back);
+        assert_ne!(report.addons[1].outcome, Outcome::Failed);
+        assert!(fake.calls().contains(&"reset --hard def4567".to_string()));
+
+        // The failed addon keeps its entry and the other addon's update is saved
+        let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join(LUARC)).unwrap()).unwrap();
+        let addons = &config["workspace"]["addons"];
+        assert_eq!(addons["love2d"].get("checkout"), None);
+        assert_eq!(addons["lovr"]["checkout"], serde_json::json!({ "commit": "def4567" }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_remote_branch_fails_only_that_addon() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc123; fi ;;
+                log) case "$PWD" in */love2d) exit 128 ;; *) echo def456 ;; esac ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        for name in ["love2d", "lovr"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(name)).unwrap();
+        }
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        let report = manager.update(SomeOrAll::All).unwrap();
+
+        let outcomes = report.addons.iter().map(|a| (a.name.as_str(), a.outcome, a.rolled_back)).collect::<Vec<_>>();
+        assert_eq!(outcomes, [("love2d", Outcome::Failed, true), ("lovr", Outcome::Skipped, false)]);
+        // The run finished and wrote the config
+        assert!(dir.join(LUARC).exists());
+
+        let calls = fake.calls();
+        assert!(calls.contains(&"reset --hard def456".to_string()), "{calls:?}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_pull_rolls_back() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc123; fi ;;
+                pull) echo "CONFLICT (content): Merge conflict in config.json" >&2; exit 1 ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+
+        let mut addon = Addon::from("love2d");
+        addon.checkout = AddonTarget::Branch("develop".to_string());
+        let report = manager.update(vec![addon]).unwrap();
+
+        let entry = &report.addons[0];
+        assert_eq!(entry.outcome, Outcome::Failed);
+        assert!(entry.rolled_back);
+        assert_eq!(manager.rc.get_addons()["love2d"], Addon::from("love2d"));
+
+        let changes = fake.calls_to(&["switch", "pull", "reset"]);
+        assert_eq!(changes, ["switch develop", "pull", "switch main", "reset --hard abc123"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn branch_without_checksum_follows_its_branch() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo develop; else echo abc123; fi ;;
+                ls-remote) echo "abc123	refs/heads/develop" ;;
+                log) if [ "$4" = "origin/develop" ]; then echo def456; else echo main789; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        let mut addon = Addon::from("love2d");
+        addon.checkout = AddonTarget::Branch("develop".to_string());
+        manager.rc.add_or_update_addon(&addon);
+        manager.update(vec![addon]).unwrap();
+
+        let calls = fake.calls();
+        assert!(calls.contains(&"log -n 1 origin/develop --pretty=format:%H".to_string()), "{calls:?}");
+        assert!(calls.contains(&"reset --hard def456".to_string()), "{calls:?}");
+        assert!(!calls.iter().any(|call| call.contains("origin/main")), "{calls:?}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_fetches_each_repository_once() {
+        // `love2d` and `love2d-fork` are worktrees of the same shared clone
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1 $2" in
+                "symbolic-ref"*) echo "refs/remotes/origin/main" ;;
+                "rev-parse --abbrev-ref") echo main ;;
+                "rev-parse --git-common-dir") case "$PWD" in */lovr) echo .git ;; *) echo /shared/love2d.git ;; esac ;;
+                "rev-parse"*) echo abc123 ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        for name in ["love2d", "love2d-fork", "lovr"] {
+            std::fs::create_dir_all(dir.join(ADDONS_DIR).join(name)).unwrap();
+        }
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d"));
+        manager.rc.add_or_update_addon(&Addon::from("https://github.com/someone/love2d-fork"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr"));
+        let report = manager.update(SomeOrAll::All).unwrap();
+        assert_eq!(report.addons.len(), 3);
+
+        assert_eq!(fake.calls_to(&["fetch"]).len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tag_updates_fetch_tags() {
+        let fake = FakeGit::new(indoc::indoc! {r#"
+            case "$1" in
+                symbolic-ref) echo "refs/remotes/origin/main" ;;
+                rev-parse) if [ "$2" = "--abbrev-ref" ]; then echo main; else echo abc123; fi ;;
+            esac
+        "#});
+
+        let dir = TempDir::new();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("love2d")).unwrap();
+        std::fs::create_dir_all(dir.join(ADDONS_DIR).join("lovr")).unwrap();
+        let mut manager = Manager::new(&dir, Silent).unwrap().with_git(fake.cli());
+        manager.rc.add_or_update_addon(&Addon::from("love2d@v11.5"));
+        manager.rc.add_or_update_addon(&Addon::from("lovr@1234567"));
+        manager.update(SomeOrAll::All).unwrap();
+
+        let fetches = fake.calls_to(&["fetch"]);
+        assert_eq!(fetches, ["fetch -p --tags", "fetch -p"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn archives_are_extracted_and_hashed() {
+        let dir = TempDir::new();
+        let release = |version: &str| {
+            let files = dir.join(format!("release-{version}"));
+            std::fs::create_dir_all(files.join("penlight/library")).unwrap();
+            std::fs::write(files.join("penlight/library/pl.lua"), format!("-- {version}")).unwrap();
+            let tarball = files.join("penlight.tar.gz");
+            let status = std::process::Command::new("tar")
+                .args(["-czf", &tarball.to_string_lossy(), "-C", &files.to_string_lossy(), "penlight"])
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let hash = Cli::default().hash_object(&tarball).unwrap();
+            (reqwest::Url::from_file_path(&tarball).unwrap().to_string(), tarball, hash)
+        };
+        let (url, tarball, hash) = release("1.0");
+        let (moved, _, moved_hash) = release("1.1");
+        let path = dir.join(ADDONS_DIR).join("penlight");
+
+        // A hash that doesn't match the download leaves nothing behind
+        let mut manager = Manager::new(&dir, Silent).unwrap();
+        let report = manager.add([Addon::from(format!("{url}@0000000"))], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert!(!path.exists());
+        assert!(!manager.rc.get_addons().contains_key("penlight"));
+
+        let report = manager.add([Addon::from(url.as_str())], AddOptions::default()).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Added);
+        assert_eq!(report.addons[0].new_checksum.as_deref(), Some(hash.as_str()));
+        assert_eq!(std::fs::read_to_string(path.join("library/pl.lua")).unwrap(), "-- 1.0");
+        let addon = &manager.rc.get_addons()["penlight"];
+        assert_eq!(addon.target, Target::Archive);
+        assert_eq!(addon.checksum(), Some(hash.as_str()));
+        assert!(manager.broken_addons().is_empty());
+
+        // Nothing changed so nothing is downloaded
+        std::fs::remove_file(&tarball).unwrap();
+        let report = manager.update(SomeOrAll::All).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Skipped);
+
+        // A new url replaces the old hash with the hash of the new tarball
+        let report = manager.update(vec![Addon::from(moved.as_str())]).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Updated);
+        assert_eq!(report.addons[0].old_checksum.as_deref(), Some(hash.as_str()));
+        assert_eq!(report.addons[0].new_checksum.as_deref(), Some(moved_hash.as_str()));
+        assert_eq!(std::fs::read_to_string(path.join("library/pl.lua")).unwrap(), "-- 1.1");
+        assert_eq!(manager.rc.get_addons()["penlight"].checksum(), Some(moved_hash.as_str()));
+
+        // The old url is gone, the installed copy is kept
+        let report = manager.update(vec![Addon::from(url.as_str())]).unwrap();
+        assert_eq!(report.addons[0].outcome, Outcome::Failed);
+        assert_eq!(std::fs::read_to_string(path.join("library/pl.lua")).unwrap(), "-- 1.1");
+        assert_eq!(manager.rc.get_addons()["penlight"].src, moved);
+    }
 }
@@ -0,0 +1,337 @@
+//! Machine readable and human readable output of commands like `list`.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{lua_rc::{diagnostics::DiagnosticGroup, Diagnostics}, Addon, Error};
+
+/// How a command prints its results
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Human readable lines
+    #[default]
+    Human,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Serialize `value` in the format, `None` for [`Format::Human`] which each command prints
+    /// itself
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<Option<String>, Error> {
+        Ok(Some(match self {
+            Self::Human => return Ok(None),
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+        }))
+    }
+}
+
+/// Summary of an installed addon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddonSummary {
+    pub name: String,
+    pub source: String,
+    pub target: String,
+    /// What the addon is checked out to, e.g. `branch main`
+    pub checkout: String,
+    pub enabled: bool,
+    pub dependency: bool,
+    pub pinned: bool,
+}
+
+impl AddonSummary {
+    pub fn new(name: impl std::fmt::Display, addon: &Addon) -> Self {
+        Self {
+            name: name.to_string(),
+            source: addon.src.clone(),
+            target: addon.target.to_string(),
+            checkout: addon.checkout.to_string(),
+            enabled: addon.enabled,
+            dependency: addon.dependency,
+            pinned: addon.pinned,
+        }
+    }
+}
+
+/// Output of `list`
+///
+/// The addons are wrapped in a table since toml documents can't be a list.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddonList {
+    pub addons: Vec<AddonSummary>,
+}
+
+impl AddonList {
+    /// Human readable lines sorted by name, the addons that were added explicitly are listed
+    /// before the addons that were only installed as dependencies
+    pub fn human(&self) -> String {
+        let mut addons = self.addons.iter().collect::<Vec<_>>();
+        addons.sort_by(|a, b| a.name.cmp(&b.name));
+        let (dependencies, added) = addons.into_iter().partition::<Vec<_>, _>(|addon| addon.dependency);
+
+        let mut output = String::new();
+        for (title, addons) in [("Addons", added), ("Dependencies", dependencies)] {
+            if addons.is_empty() {
+                continue;
+            }
+            output.push_str(&format!("{title}:\n"));
+            for addon in addons {
+                let disabled = if addon.enabled { "" } else { " [disabled]" };
+                let pinned = if addon.pinned { " [pinned]" } else { "" };
+                output.push_str(&format!(
+                    "  {} ({}): {}{disabled}{pinned}\n",
+                    addon.name, addon.target, addon.checkout
+                ));
+            }
+        }
+        output
+    }
+}
+
+/// Details of an installed addon for `info`, `raw` is the entry exactly as it is stored in the
+/// config
+///
+/// Other formats than [`Format::Human`] serialize the [`AddonSummary`], or the stored entry when
+/// `raw` is set. A raw entry without a format is json.
+pub fn info(name: &str, addon: &Addon, raw: bool, format: Format) -> Result<String, Error> {
+    if raw {
+        let format = if format == Format::Human { Format::Json } else { format };
+        return Ok(format!("{}\n", format.render(addon)?.unwrap_or_default().trim_end()));
+    }
+
+    let summary = AddonSummary::new(name, addon);
+    if let Some(output) = format.render(&summary)? {
+        return Ok(format!("{}\n", output.trim_end()));
+    }
+    let mut output = format!(
+        "{name}\n  source: {} ({})\n  checkout: {}\n  enabled: {}\n  pinned: {}\n",
+        summary.source, summary.target, summary.checkout, summary.enabled, summary.pinned
+    );
+    if !addon.required_by.is_empty() {
+        let dependents = addon.required_by.iter().cloned().collect::<Vec<_>>();
+        output.push_str(&format!("  required by: {}\n", dependents.join(", ")));
+    }
+    Ok(output)
+}
+
+/// Name of a setting as it is written in the config, e.g. `undefined-global`
+fn config_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// Disabled diagnostics and changed severities grouped by their [`DiagnosticGroup`], with the
+/// groups and the diagnostics in each group sorted by name
+pub fn diagnostics(diagnostics: &Diagnostics) -> String {
+    let mut groups = BTreeMap::<DiagnosticGroup, (Option<String>, BTreeMap<String, Vec<String>>)>::new();
+    for (group, severity) in diagnostics.group_severity.iter() {
+        groups.entry(*group).or_default().0 = Some(config_name(severity));
+    }
+    let states = diagnostics
+        .disable
+        .iter()
+        .map(|diagnostic| (diagnostic, "disabled".to_string()))
+        .chain(diagnostics.severity.iter().map(|(diagnostic, severity)| (diagnostic, config_name(severity))));
+    for (diagnostic, state) in states {
+        let (_, entries) = groups.entry(diagnostic.group()).or_default();
+        entries.entry(config_name(diagnostic)).or_default().push(state);
+    }
+
+    let mut output = String::new();
+    for (group, (severity, entries)) in groups {
+        let name = config_name(&group);
+        match severity {
+            Some(severity) => output.push_str(&format!("{name}: {severity}\n")),
+            None => output.push_str(&format!("{name}\n")),
+        }
+        for (diagnostic, states) in entries {
+            output.push_str(&format!("  {diagnostic}: {}\n", states.join(", ")));
+        }
+    }
+    output
+}
+
+/// Addons as an indented tree of the addons that were installed as dependencies of each addon
+///
+/// Addons that were added explicitly are the roots. Dependencies whose dependents were all
+/// removed are listed as roots as well.
+pub fn tree(addons: &BTreeMap<Cow<'static, str>, Addon>) -> String {
+    fn branch(
+        addons: &BTreeMap<Cow<'static, str>, Addon>,
+        name: &str,
+        prefix: &str,
+        path: &mut Vec<String>,
+        output: &mut String,
+    ) {
+        path.push(name.to_string());
+        let children = addons
+            .iter()
+            .filter(|(child, addon)| addon.required_by.contains(name) && !path.contains(&child.to_string()))
+            .collect::<Vec<_>>();
+        for (i, (child, addon)) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let glyph = if last { "└── " } else { "├── " };
+            output.push_str(&format!("{prefix}{glyph}{}\n", line(child, addon)));
+            let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+            branch(addons, child, &prefix, path, output);
+        }
+        path.pop();
+    }
+
+    fn line(name: &str, addon: &Addon) -> String {
+        let disabled = if addon.enabled { "" } else { " [disabled]" };
+        format!("{name}{disabled}")
+    }
+
+    let mut output = String::new();
+    for (name, addon) in addons {
+        let orphan = addon.required_by.iter().all(|parent| !addons.contains_key(parent.as_str()));
+        if addon.dependency && !orphan {
+            continue;
+        }
+        output.push_str(&format!("{}\n", line(name, addon)));
+        branch(addons, name, "", &mut Vec::new(), &mut output);
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::AddonTarget;
+
+    fn addons(entries: &[(&'static str, &[&str])]) -> BTreeMap<Cow<'static, str>, Addon> {
+        entries
+            .iter()
+            .map(|(name, required_by)| {
+                let mut addon = Addon::from(*name);
+                addon.dependency = !required_by.is_empty();
+                addon.required_by = required_by.iter().map(|parent| parent.to_string()).collect();
+                (Cow::Borrowed(*name), addon)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn human_list_is_grouped_and_sorted() {
+        let mut addons = addons(&[("lovr", &[]), ("c", &["lovr"]), ("love2d", &[]), ("a", &["love2d"])]);
+        addons.get_mut("lovr").unwrap().enabled = false;
+        let mut list = AddonList {
+            addons: addons.iter().map(|(name, addon)| AddonSummary::new(name, addon)).collect(),
+        };
+        list.addons.reverse();
+
+        let lines = list.human().lines().map(|line| line.split(' ').take(3).collect::<Vec<_>>().join(" ")).collect::<Vec<_>>();
+        assert_eq!(lines, ["Addons:", "  love2d", "  lovr", "Dependencies:", "  a", "  c"]);
+        assert!(list.human().contains("  lovr (LuaCATS): default branch [disabled]\n"));
+
+        assert_eq!(AddonList::default().human(), "");
+    }
+
+    #[test]
+    fn raw_info_round_trips() {
+        let mut addon = Addon::from("https://github.com/someone/love2d");
+        addon.checkout = AddonTarget::Branch("main".to_string());
+        addon.pinned = true;
+        addon.required_by.insert("lovr".to_string());
+
+        let raw = info("love2d", &addon, true, Format::Human).unwrap();
+        assert_eq!(serde_json::from_str::<Addon>(&raw).unwrap(), addon);
+        let raw = info("love2d", &addon, true, Format::Yaml).unwrap();
+        assert_eq!(serde_yaml::from_str::<Addon>(&raw).unwrap(), addon);
+        let raw = info("love2d", &addon, true, Format::Toml).unwrap();
+        assert_eq!(toml::from_str::<Addon>(&raw).unwrap(), addon);
+
+        let summary = info("love2d", &addon, false, Format::Json).unwrap();
+        assert_eq!(serde_json::from_str::<AddonSummary>(&summary).unwrap(), AddonSummary::new("love2d", &addon));
+
+        let human = info("love2d", &addon, false, Format::Human).unwrap();
+        assert!(human.starts_with("love2d\n  source: https://github.com/someone/love2d (github)\n"), "{human}");
+        assert!(human.ends_with("  pinned: true\n  required by: lovr\n"), "{human}");
+    }
+
+    #[test]
+    fn diagnostics_are_grouped() {
+        let config: Diagnostics = serde_json::from_value(serde_json::json!({
+            "disable": ["unused-local", "lowercase-global", "undefined-global"],
+            "severity": { "unused-local": "Hint", "count-down-loop": "Error!" },
+            "groupSeverity": { "unused": "Warning" },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            diagnostics(&config),
+            indoc::indoc! {"
+                ambiguity
+                  count-down-loop: Error!
+                global
+                  lowercase-global: disabled
+                  undefined-global: disabled
+                unused: Warning
+                  unused-local: disabled, Hint
+            "}
+        );
+    }
+
+    #[test]
+    fn dependency_tree() {
+        let addons = addons(&[
+            ("love2d", &[]),
+            ("a", &["love2d"]),
+            ("b", &["love2d"]),
+            ("c", &["a", "lovr"]),
+            ("lovr", &[]),
+        ]);
+        assert_eq!(
+            tree(&addons),
+            indoc::indoc! {"
+                love2d
+                ├── a
+                │   └── c
+                └── b
+                lovr
+                └── c
+            "}
+        );
+    }
+
+    #[test]
+    fn flat_tree_without_dependencies() {
+        let mut addons = addons(&[("love2d", &[]), ("lovr", &[])]);
+        addons.get_mut("lovr").unwrap().enabled = false;
+        assert_eq!(tree(&addons), "love2d\nlovr [disabled]\n");
+
+        // A dependency whose dependents were removed
+        let orphan = super::tree(&self::addons(&[("a", &["removed"])]));
+        assert_eq!(orphan, "a\n");
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let mut disabled = Addon::from("https://github.com/someone/lovr@1234567");
+        disabled.enabled = false;
+        disabled.dependency = true;
+        disabled.pinned = true;
+        let list = AddonList {
+            addons: vec![
+                AddonSummary::new("love2d", &Addon::from("love2d")),
+                AddonSummary::new("lovr", &disabled),
+            ],
+        };
+
+        let json = Format::Json.render(&list).unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<AddonList>(&json).unwrap(), list);
+        let yaml = Format::Yaml.render(&list).unwrap().unwrap();
+        assert_eq!(serde_yaml::from_str::<AddonList>(&yaml).unwrap(), list);
+        let toml = Format::Toml.render(&list).unwrap().unwrap();
+        assert_eq!(toml::from_str::<AddonList>(&toml).unwrap(), list);
+
+        assert_eq!(Format::Human.render(&list).unwrap(), None);
+    }
+}
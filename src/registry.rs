@@ -0,0 +1,119 @@
+//! Searching the LuaCATS organization for addons, used by `add --interactive`.
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{Addon, AddonTarget, Error};
+
+/// GitHub search endpoint for repositories
+static SEARCH_URL: &str = "https://api.github.com/search/repositories";
+
+/// A repository found by [`search`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Repository {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResults {
+    items: Vec<Repository>,
+}
+
+/// Repositories of the LuaCATS organization matching `query`, in the order GitHub ranks them
+pub async fn search(query: &str) -> Result<Vec<Repository>, Error> {
+    let response = reqwest::Client::new()
+        .get(SEARCH_URL)
+        .query(&[("q", format!("{query} org:LuaCATS")), ("per_page", "20".to_string())])
+        .header(reqwest::header::USER_AGENT, "llam")
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await?
+        .error_for_status()?;
+    parse(&response.text().await?)
+}
+
+/// Repositories of a GitHub search response
+fn parse(body: &str) -> Result<Vec<Repository>, Error> {
+    Ok(serde_json::from_str::<SearchResults>(body)?.items)
+}
+
+/// Numbered lines listing the repositories for the user to pick from, starting at 1
+pub fn numbered(repositories: &[Repository]) -> String {
+    let width = repositories.len().to_string().len();
+    repositories
+        .iter()
+        .enumerate()
+        .map(|(i, repository)| match repository.description.as_deref() {
+            Some(description) if !description.is_empty() => {
+                format!("{:>width$}) {}: {description}\n", i + 1, repository.name)
+            }
+            _ => format!("{:>width$}) {}\n", i + 1, repository.name),
+        })
+        .collect()
+}
+
+/// Addon for the repository picked by its number in [`numbered`]
+///
+/// An empty `reference` follows the default branch. Otherwise it is a [target][AddonTarget] such
+/// as `branch:dev`, or a tag or commit like the `@` suffix of an addon.
+pub fn select(repositories: &[Repository], choice: &str, reference: &str) -> Result<Addon, Error> {
+    let repository = choice
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| repositories.get(number.checked_sub(1)?))
+        .ok_or_else(|| {
+            Error::custom(format!("`{}` is not a number from 1 to {}", choice.trim(), repositories.len()))
+        })?;
+
+    let mut addon = Addon::from_str(&repository.name)?;
+    let reference = reference.trim();
+    if !reference.is_empty() {
+        addon.checkout = reference
+            .parse::<AddonTarget>()
+            .unwrap_or_else(|_| AddonTarget::revision(reference));
+    }
+    Ok(addon)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn repositories() -> Vec<Repository> {
+        parse(
+            &serde_json::json!({
+                "total_count": 2,
+                "items": [
+                    { "name": "love2d", "description": "LÖVE definitions", "stargazers_count": 10 },
+                    { "name": "lovr", "description": null },
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn select_from_search_results() {
+        let repositories = repositories();
+        assert_eq!(numbered(&repositories), "1) love2d: LÖVE definitions\n2) lovr\n");
+
+        let addon = select(&repositories, "2\n", "").unwrap();
+        assert_eq!(addon.name(), "lovr");
+        assert_eq!(addon.clone_url(), "https://github.com/LuaCATS/lovr.git");
+        assert_eq!(addon.checkout, AddonTarget::DefaultBranch);
+
+        let checkout = |reference| select(&repositories, "1", reference).unwrap().checkout;
+        assert_eq!(checkout("branch:11.5"), AddonTarget::Branch("11.5".to_string()));
+        assert_eq!(checkout("v11.5"), AddonTarget::Tag("v11.5".to_string()));
+        assert_eq!(checkout("1a2b3c4"), AddonTarget::Commit("1a2b3c4".to_string()));
+
+        for choice in ["0", "3", "love2d", ""] {
+            assert!(select(&repositories, choice, "").is_err(), "{choice}");
+        }
+    }
+}
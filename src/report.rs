@@ -0,0 +1,168 @@
+//! Machine readable summary of what an operation did to each addon.
+
+use std::{path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// What happened to a single addon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Added,
+    Updated,
+    Reinstalled,
+    Removed,
+    Skipped,
+    Failed,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 6] = [
+        Self::Added,
+        Self::Updated,
+        Self::Reinstalled,
+        Self::Removed,
+        Self::Skipped,
+        Self::Failed,
+    ];
+
+    /// Label used in summaries, e.g. `3 up-to-date`
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Updated => "updated",
+            Self::Reinstalled => "reinstalled",
+            Self::Removed => "removed",
+            Self::Skipped => "up-to-date",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Outcome of an operation for a single addon
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddonReport {
+    pub name: String,
+    pub outcome: Outcome,
+    /// Checked out commit before the operation, `None` when the addon wasn't installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_checksum: Option<String>,
+    /// Checked out commit after the operation, `None` when the addon isn't installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_checksum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The addon was restored to `old_checksum` after the operation failed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rolled_back: bool,
+    pub duration_ms: u64,
+}
+
+/// Per addon summary of an `add`, `update`, or `remove`
+///
+/// Returned by the [`Manager`][crate::Manager] operations and written as JSON with [`OperationReport::write`], e.g. as a CI artifact.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationReport {
+    pub operation: String,
+    pub addons: Vec<AddonReport>,
+}
+
+impl OperationReport {
+    pub fn new(operation: impl std::fmt::Display) -> Self {
+        Self {
+            operation: operation.to_string(),
+            addons: Vec::new(),
+        }
+    }
+
+    /// Record the outcome for an addon whose work started at `started`
+    pub fn record(
+        &mut self,
+        name: impl std::fmt::Display,
+        outcome: Outcome,
+        old_checksum: Option<String>,
+        new_checksum: Option<String>,
+        started: Instant,
+    ) {
+        self.addons.push(AddonReport {
+            name: name.to_string(),
+            outcome,
+            old_checksum,
+            new_checksum,
+            error: None,
+            rolled_back: false,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Record that an addon failed with the given error
+    pub fn failed(&mut self, name: impl std::fmt::Display, error: impl std::fmt::Display, started: Instant) {
+        self.addons.push(AddonReport {
+            name: name.to_string(),
+            outcome: Outcome::Failed,
+            old_checksum: None,
+            new_checksum: None,
+            error: Some(error.to_string()),
+            rolled_back: false,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Record that an addon failed and was restored to `checksum`
+    pub fn rolled_back(
+        &mut self,
+        name: impl std::fmt::Display,
+        error: impl std::fmt::Display,
+        checksum: impl std::fmt::Display,
+        started: Instant,
+    ) {
+        let checksum = checksum.to_string();
+        self.addons.push(AddonReport {
+            name: name.to_string(),
+            outcome: Outcome::Failed,
+            old_checksum: Some(checksum.clone()),
+            new_checksum: Some(checksum),
+            error: Some(error.to_string()),
+            rolled_back: true,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Addons that failed
+    pub fn failures(&self) -> impl Iterator<Item = &AddonReport> {
+        self.addons.iter().filter(|addon| addon.outcome == Outcome::Failed)
+    }
+
+    /// Number of addons with the given outcome
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.addons.iter().filter(|addon| addon.outcome == outcome).count()
+    }
+
+    /// Counts of each outcome that occurred, e.g. `2 added, 3 up-to-date, 1 failed`
+    pub fn summary(&self) -> String {
+        let counts = Outcome::ALL
+            .iter()
+            .map(|outcome| (outcome, self.count(*outcome)))
+            .filter(|(_, count)| *count > 0)
+            .map(|(outcome, count)| format!("{count} {}", outcome.label()))
+            .collect::<Vec<_>>();
+
+        match counts.is_empty() {
+            true => "nothing to do".to_string(),
+            false => counts.join(", "),
+        }
+    }
+
+    /// Whether every addon succeeded or was skipped
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Write the report as pretty printed JSON
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
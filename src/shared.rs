@@ -0,0 +1,54 @@
+//! Addon clones shared between projects.
+//!
+//! In shared mode every addon is cloned once into a directory outside of the project and each
+//! project checks it out with a `git worktree`, so the history is only stored once on disk.
+
+use std::path::{Path, PathBuf};
+
+/// Default location of the shared clones, `<data dir>/llam/addons`
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("llam").join("addons"))
+}
+
+/// Directory name of the shared clone for a url
+///
+/// Derived from the whole url so forks with the same repository name don't share a clone.
+pub fn clone_name(url: &str) -> String {
+    let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    let mut name = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => name.push(c),
+            _ if !name.ends_with('_') => name.push('_'),
+            _ => {}
+        }
+    }
+    name.trim_matches(['_', '.']).to_string()
+}
+
+/// Path of the shared clone for a url inside `root`
+pub fn clone_dir(root: impl AsRef<Path>, url: &str) -> PathBuf {
+    root.as_ref().join(clone_name(url))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clone_names() {
+        assert_eq!(
+            clone_name("https://github.com/LuaCATS/love2d.git"),
+            "github.com_LuaCATS_love2d"
+        );
+        assert_eq!(clone_name("https://github.com/LuaCATS/love2d/"), "github.com_LuaCATS_love2d");
+        assert_eq!(
+            clone_name("ssh://git@github.com/someone/love2d"),
+            "git_github.com_someone_love2d"
+        );
+        assert_eq!(clone_name("file:///home/user/../addon"), "home_user_.._addon");
+    }
+}
@@ -0,0 +1,126 @@
+//! Fixtures shared by the tests of every module
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[cfg(unix)]
+use crate::git::Cli;
+
+/// Temporary directory that is removed when it is dropped, also when a test fails
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("llam-test-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Fake git script snippet that creates the target directory of a `git clone`
+#[cfg(unix)]
+pub(crate) const FAKE_CLONE: &str =
+    r#"if [ "$1" = "clone" ]; then for last; do :; done; mkdir -p "$last"; fi"#;
+
+/// A fake `git` script that records every invocation's arguments, one line per call, to
+/// `calls.log` next to it
+#[cfg(unix)]
+pub(crate) struct FakeGit {
+    dir: TempDir,
+    binary: PathBuf,
+}
+
+#[cfg(unix)]
+impl FakeGit {
+    /// `script` is run after the arguments are recorded
+    pub(crate) fn new(script: &str) -> Self {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let binary = dir.join("git");
+        std::fs::write(
+            &binary,
+            format!("#!/bin/sh\necho \"$@\" >> \"{}\"\n{script}\n", dir.join("calls.log").display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        Self { dir, binary }
+    }
+
+    pub(crate) fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    /// Directory the script is in, which is otherwise empty apart from `calls.log`
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub(crate) fn cli(&self) -> Cli {
+        Cli::new(&self.binary)
+    }
+
+    /// Everything recorded so far, empty when git wasn't called
+    pub(crate) fn log(&self) -> String {
+        std::fs::read_to_string(self.dir.join("calls.log")).unwrap_or_default()
+    }
+
+    /// Arguments of every call so far
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.log().lines().map(str::to_string).collect()
+    }
+
+    /// Arguments of the calls of any of the git commands, e.g. `["switch", "reset"]`
+    pub(crate) fn calls_to(&self, commands: &[&str]) -> Vec<String> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.split(' ').next().is_some_and(|command| commands.contains(&command)))
+            .collect()
+    }
+}
+
+/// Run a real git command in `dir` for test setup
+pub(crate) fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .args(["-c", "user.name=llam", "-c", "user.email=llam@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Create a repository at `path` on `main` with a single commit of an empty `config.json`,
+/// returning its `file://` url
+pub(crate) fn init_remote(path: &Path) -> String {
+    std::fs::create_dir_all(path).unwrap();
+    git(path, &["init", "-q", "-b", "main"]);
+    std::fs::write(path.join("config.json"), "{}").unwrap();
+    git(path, &["add", "."]);
+    git(path, &["commit", "-q", "-m", "initial"]);
+    format!("file://{}", path.display())
+}
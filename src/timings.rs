@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+/// Duration of a single git operation for an addon
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    pub addon: String,
+    pub operation: &'static str,
+    pub duration: Duration,
+}
+
+/// Records how long each git operation takes per addon
+///
+/// Nothing is recorded unless the timings are enabled. The [`Display`][std::fmt::Display]
+/// implementation renders a summary table of every recorded operation.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    enabled: bool,
+    entries: Vec<Timing>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn entries(&self) -> &[Timing] {
+        &self.entries
+    }
+
+    /// Run `f` and record how long it took under the given addon and operation
+    pub fn time<T>(
+        &mut self,
+        addon: impl std::fmt::Display,
+        operation: &'static str,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        if !self.enabled {
+            return f();
+        }
+
+        let start = Instant::now();
+        let result = f();
+        self.entries.push(Timing {
+            addon: addon.to_string(),
+            operation,
+            duration: start.elapsed(),
+        });
+        result
+    }
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .entries
+            .iter()
+            .map(|t| t.addon.len())
+            .chain(["addon".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(f, "{:<width$}  {:<9}  {:>8}", "addon", "operation", "ms")?;
+        for timing in self.entries.iter() {
+            writeln!(
+                f,
+                "{:<width$}  {:<9}  {:>8}",
+                timing.addon,
+                timing.operation,
+                timing.duration.as_millis()
+            )?;
+        }
+
+        let total: Duration = self.entries.iter().map(|t| t.duration).sum();
+        write!(f, "{:<width$}  {:<9}  {:>8}", "total", "", total.as_millis())
+    }
+}